@@ -1,33 +1,130 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::sync::LazyLock;
-use std::time::Duration;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
+use chrono::Utc;
+use clap_complete::Shell;
 use color_eyre::eyre::{self, Result, WrapErr, eyre};
+use color_eyre::owo_colors::OwoColorize;
 use dialoguer::Confirm;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use directories::BaseDirs;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 use itertools::Itertools;
+use prettytable::format::FormatBuilder;
+use prettytable::{Table, row};
+use reqwest::Url;
 use tokio::fs;
-use tokio::process::Command;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{Semaphore, mpsc};
 use tokio::task::JoinSet;
 use tracing::{debug, error, info, instrument, warn};
 
-use crate::common::{LOG_BASE_DIR, META, PROJ_DIRS, REQWEST_CLIENT};
-use crate::types::meta::{InstanceMeta, InstanceSettings};
-use crate::types::version::{GameVersion, VersionMetadata, VersionNumber};
-use crate::utils::net::{download_jre, get_version_metadata};
+use crate::common::{
+    DATA_BASE_DIR, LOG_BASE_DIR, META, MIRROR, OFFLINE, PROGRESS, QUIET, REQWEST_CLIENT,
+    TRANSFER_STATS, config_local_dir, release_meta_lock,
+};
+use crate::error::McdlError;
+use crate::types::flags::FlagPreset;
+use crate::types::jre::{Arch, HeapSize, ImageType, JreSpec, JvmImpl, Vendor, release_major};
+use crate::types::loader::{Loader, latest_stable};
+use crate::types::meta::{
+    AppMeta, ExportManifest, InstanceJavaSettings, InstanceMeta, InstanceServerSettings,
+    InstanceSettings,
+};
+use crate::types::net::CacheHeader;
+use crate::types::stats::{TransferKind, TransferStat};
+use crate::types::version::{
+    GameVersion, GameVersionList, VersionDownload, VersionMetadata, VersionNumber,
+};
+use crate::utils::archive::{read_zip_file, unzip_dir, zip_dir_as, zip_dirs};
+use crate::utils::hash::sha1_hex;
+use crate::utils::loader::{Fabric, LoaderApi, Quilt};
+use crate::utils::macros::status;
+use crate::utils::net::{
+    CACHE_BASE_DIR, MAX_ATTEMPTS, download_jre, download_resumable, get_available_jre_releases,
+    get_version_metadata, read_cached_jre, remove_cached_jre,
+};
+use crate::utils::parse::{parse_instance_name, parse_java_version, parse_paste_url};
+use crate::utils::properties::ServerProperties;
 
-static INSTANCE_BASE_DIR: LazyLock<PathBuf> =
-    LazyLock::new(|| PROJ_DIRS.data_local_dir().join("instance"));
-static JRE_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| PROJ_DIRS.data_local_dir().join("jre"));
+/// Where crash reports are uploaded to unless another endpoint is configured
+const DEFAULT_PASTE_URL: &str = "https://api.mclo.gs/1/log";
+/// The target server output is logged with, kept out of the verbose output since it's already shown
+pub(crate) const SERVER_OUTPUT_TARGET: &str = "mcdl::server";
+
+pub(crate) static INSTANCE_BASE_DIR: LazyLock<PathBuf> =
+    LazyLock::new(|| DATA_BASE_DIR.join("instance"));
+static JRE_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| DATA_BASE_DIR.join("jre"));
+static STORE_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| DATA_BASE_DIR.join("store"));
+static BACKUP_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| DATA_BASE_DIR.join("backup"));
 static INSTANCE_SETTINGS_BASE_DIR: LazyLock<PathBuf> =
-    LazyLock::new(|| PROJ_DIRS.config_local_dir().join("instance"));
+    LazyLock::new(|| config_local_dir().join("instance"));
+/// The file name of the settings of an instance installed with `--output-dir`, which are kept in it
+const OUTPUT_DIR_SETTINGS: &str = "mcdl.toml";
 static PB_STYLE: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{prefix:.bold.blue.bright} {spinner:.green.bright} {wide_msg}")
         .unwrap()
         .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏-")
 });
+/// The style of a bar while a JRE is extracted, which replaces the spinner style until it is done
+static EXTRACT_PB_STYLE: LazyLock<ProgressStyle> = LazyLock::new(|| {
+    ProgressStyle::with_template(
+        "{prefix:.bold.blue.bright} {spinner:.green.bright} {msg} [{bar:30.green.bright/white}] {percent}% [{elapsed}, ETA {eta}]",
+    )
+    .unwrap()
+    .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏-")
+    .progress_chars("=> ")
+});
+
+/// The directory of an instance, under [`INSTANCE_BASE_DIR`] unless it was installed with `--output-dir`
+pub(crate) fn instance_dir(meta: &AppMeta, key: &str) -> PathBuf {
+    match meta.instances.get(key).and_then(|i| i.location.as_ref()) {
+        Some(location) => location.clone(),
+        None => INSTANCE_BASE_DIR.join(key),
+    }
+}
+
+/// The settings file of an instance, which is in the instance if it was installed with `--output-dir`
+fn settings_path(meta: &AppMeta, key: &str) -> PathBuf {
+    match meta.instances.get(key).and_then(|i| i.location.as_ref()) {
+        Some(location) => location.join(OUTPUT_DIR_SETTINGS),
+        None => INSTANCE_SETTINGS_BASE_DIR.join(format!("{key}.toml")),
+    }
+}
+
+/// Creates a ticking spinner, or a hidden progress bar with `--quiet`
+fn spinner(prefix: impl Into<Cow<'static, str>>) -> ProgressBar {
+    if QUIET.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+
+    let pb = PROGRESS.add(
+        ProgressBar::new_spinner()
+            .with_style(PB_STYLE.clone())
+            .with_prefix(prefix),
+    );
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+/// The file name of the client jar in an instance installed with `--client`
+const CLIENT_JAR: &str = "client.jar";
+/// Written to an instance directory while its server runs in the background
+const SERVER_PID_FILE: &str = "server.pid";
+/// The output of a server running in the background
+const SERVER_LOG_FILE: &str = "server.log";
+/// Where Mojang publishes the Minecraft EULA
+const EULA_URL: &str = "https://aka.ms/MinecraftEULA";
+const SERVER_STOP_TIMEOUT: Duration = Duration::from_mins(1);
 
 macro_rules! META {
     () => {
@@ -35,125 +132,239 @@ macro_rules! META {
     };
 }
 
+/// Options that apply to every version in an install
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug)]
+pub(crate) struct InstallOptions {
+    /// Keep installing the remaining versions if one of them fails
+    pub keep_going: bool,
+    /// The heap size variant of the JRE to install
+    pub heap_size: HeapSize,
+    /// The JVM implementation of the JRE to install
+    pub jvm_impl: JvmImpl,
+    /// The image type of the JRE to install
+    pub image_type: ImageType,
+    /// The vendor of the JRE to install
+    pub vendor: Vendor,
+    /// The architecture to download the JRE for, instead of this machine's
+    pub arch: Option<Arch>,
+    /// A full JRE version to pin, instead of the latest GA release
+    pub jre_version: Option<String>,
+    /// Reinstall versions that are already installed, keeping their worlds
+    pub force_reinstall: bool,
+    /// Also download the client jar into each instance
+    pub client: bool,
+    /// The maximum number of version metadata requests to make at once
+    pub metadata_jobs: NonZeroUsize,
+    /// The maximum number of downloads to run at once
+    pub download_jobs: NonZeroUsize,
+    /// Print download and extraction timings once done
+    pub stats: bool,
+    /// The name to install the instance as, instead of its version
+    pub name: Option<String>,
+    /// The absolute path of a directory to install the instance in, instead of the instance directory
+    pub output_dir: Option<PathBuf>,
+    /// The mod loader to run the server with
+    pub loader: Loader,
+    /// The version of the mod loader, or the latest stable version if `None`
+    pub loader_version: Option<String>,
+    /// The set of JVM flags to run the server with
+    pub flag_preset: FlagPreset,
+    /// Download JREs again even if their archives are cached
+    pub refresh_jre: bool,
+    /// Don't ask for confirmation before downloading
+    pub yes: bool,
+    /// Accept the Minecraft EULA without asking
+    pub accept_eula: bool,
+}
+
+impl InstallOptions {
+    /// The JRE to install for a Java major version,
+    /// which is the major version of the pinned JRE if there is one
+    fn jre_spec(&self, major: u8) -> JreSpec {
+        let major = self
+            .jre_version
+            .as_deref()
+            .and_then(release_major)
+            .unwrap_or(major);
+        JreSpec::new(major)
+            .with_heap_size(self.heap_size)
+            .with_jvm_impl(self.jvm_impl)
+            .with_image_type(self.image_type)
+            .with_vendor(self.vendor)
+            .with_arch(self.arch)
+            .with_release(self.jre_version.clone())
+    }
+
+    /// The settings of a new instance that runs on Java `jre_version`,
+    /// with the launcher and version of its mod loader if it has one
+    fn instance_settings(
+        &self,
+        jre_version: u8,
+        launcher: Option<(PathBuf, String)>,
+    ) -> InstanceSettings {
+        let mut settings = InstanceSettings::new(jre_version);
+        if let Some((launcher, loader_version)) = launcher {
+            settings.server.jar = launcher;
+            settings.server.loader = self.loader;
+            settings.server.loader_version = Some(loader_version);
+        }
+        settings.java.heap_size = self.heap_size;
+        settings.java.jvm_impl = self.jvm_impl;
+        settings.java.image_type = self.image_type;
+        settings.java.vendor = self.vendor;
+        settings.java.arch = self.arch;
+        settings.java.release.clone_from(&self.jre_version);
+        settings.java.flag_preset = self.flag_preset;
+        settings
+    }
+}
+
+/// Options for a single run of an instance
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default)]
+pub(crate) struct RunOptions {
+    /// Stop the server after it has been running for this long
+    pub max_runtime: Option<Duration>,
+    /// Overrides the initial heap size setting
+    pub min_memory: Option<String>,
+    /// Overrides the maximum heap size setting
+    pub max_memory: Option<String>,
+    /// Overrides the flag preset setting
+    pub flag_preset: Option<FlagPreset>,
+    /// Overrides the server port setting
+    pub port: Option<u16>,
+    /// Save the memory, flag preset, and port overrides to the instance settings
+    pub save: bool,
+    /// Start the server in the background and return immediately
+    pub detach: bool,
+    /// Back up the world before starting, even if the settings don't ask for it
+    pub backup: bool,
+    /// Overrides the endpoint crash reports are uploaded to
+    pub paste_url: Option<Url>,
+    /// Don't offer to upload a crash report if the server crashes
+    pub no_upload: bool,
+    /// Console commands to send once the server has started
+    pub exec: Vec<String>,
+    /// Print the command line instead of starting the server
+    pub print_command: bool,
+    /// Also log the server's output, tagged with the instance, to the log file
+    pub capture_output: bool,
+}
+
+/// Options that apply to every instance in an uninstall
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UninstallOptions {
+    /// Keep uninstalling the remaining instances if one of them fails
+    pub keep_going: bool,
+    /// Keep the instance's JRE even if no other instance uses it
+    pub keep_jre: bool,
+    /// Keep the instance's world backups, which are no longer tracked afterwards
+    pub keep_backups: bool,
+}
+
 // ideally there is one public function for each subcommand
 
+#[allow(clippy::too_many_lines)]
 #[instrument(err, ret(level = "debug"), skip(versions))]
-pub(crate) async fn install_versions(versions: Vec<&GameVersion>) -> Result<()> {
+pub(crate) async fn install_versions(
+    versions: Vec<&GameVersion>,
+    options: &InstallOptions,
+) -> Result<()> {
     info!("Installing {} versions", versions.len());
 
-    let mut install_threads = JoinSet::new();
-    let bars = MultiProgress::new();
-
-    let mut jres_installed: Vec<u8> = Vec::new();
+    if let Some(dir) = &options.output_dir
+        && !options.force_reinstall
+        && std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some())
+    {
+        return Err(eyre!(
+            "{} is not empty, use --force to install into it anyway",
+            dir.display()
+        ));
+    }
 
-    for version in versions {
-        let version_display = version.id.to_string();
-        debug!(version = version_display, version.url, "Entering loop");
+    let mut task_labels = HashMap::new();
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
 
-        let cloned_meta = META.clone();
-        let pb_server = bars.add(
-            ProgressBar::new_spinner()
-                .with_style(PB_STYLE.clone())
-                .with_prefix(version.id.to_string()),
-        );
-        pb_server.enable_steady_tick(Duration::from_millis(100));
+    let server_bars = versions
+        .iter()
+        .map(|version| {
+            let pb_server = spinner(version.id.to_string());
+            pb_server.set_message("Queued...");
+            pb_server
+        })
+        .collect_vec();
+    let version_metas =
+        fetch_version_metadata(&versions, &server_bars, options.metadata_jobs).await?;
 
-        pb_server.set_message("Getting version metadata...");
-        let version_meta: VersionMetadata = get_version_metadata(version).await?;
-        let jre_version = version_meta.java_version.major_version;
+    if !confirm_downloads(&version_metas, options)? {
+        for pb in &server_bars {
+            pb.finish_with_message("Cancelled");
+        }
+        return Ok(());
+    }
+    let options = &InstallOptions {
+        accept_eula: options.accept_eula || ask_eula()?,
+        ..options.clone()
+    };
 
-        // spawn a thread to install the version
-        let thread_version_display = version_meta.id.to_string();
-        install_threads.spawn(async move {
-            debug!(version = thread_version_display, "Entering install thread");
-
-            if !version_meta.downloads.contains_key("server") {
-                pb_server.finish_with_message("Cancelled (no server jar)");
-                debug!(
-                    version = thread_version_display,
-                    "Exiting install thread (no server jar)"
-                );
-                return Ok::<(), eyre::Report>(());
-            }
+    let download_permits = Arc::new(Semaphore::new(options.download_jobs.get()));
+    let mut install_threads = JoinSet::new();
+    let mut jres_installed: Vec<String> = Vec::new();
 
-            let instance_dir = INSTANCE_BASE_DIR.join(version_meta.id.to_string());
+    for ((version, pb_server), version_meta) in versions.iter().zip(server_bars).zip(version_metas)
+    {
+        let version_display = version.id.to_string();
+        debug!(version = version_display, version.url, "Entering loop");
 
-            // only necessary while there is one instance per version
-            if META.lock().instance_installed(&version_meta.id.to_string()) {
-                pb_server.finish_with_message("Cancelled (already installed)");
-                debug!(
-                    version = thread_version_display,
-                    "Exiting install thread (already installed)"
-                );
-                return Ok::<(), eyre::Report>(());
+        let version_meta = match version_meta {
+            Ok(version_meta) => version_meta,
+            Err(e) if options.keep_going => {
+                pb_server.finish_with_message("Failed (could not get version metadata)");
+                failed.push((version_display, e));
+                continue;
             }
+            Err(e) => return Err(e),
+        };
 
-            let url = version_meta
-                .downloads
-                .get("server")
-                .expect("infallible")
-                .url
-                .clone();
-
-            pb_server.set_message("Downloading server jar...");
-            let server_jar = REQWEST_CLIENT
-                .get(url)
-                .send()
-                .await
-                .wrap_err("Failed to download server jar")?
-                .bytes()
-                .await
-                .wrap_err("Failed to read server jar to bytes")?;
-
-            // write to disk
-            pb_server.set_message("Writing server jar to disk...");
-            fs::create_dir_all(&instance_dir).await.wrap_err(format!(
-                "Failed to create instance directory for {}",
-                version_meta.id
-            ))?;
-
-            fs::write(instance_dir.join("server.jar"), server_jar)
-                .await
-                .wrap_err(format!(
-                    "Failed to write server jar for {}",
-                    version_meta.id
-                ))?;
-
-            // write eula
-            pb_server.set_message("Writing eula.txt...");
-            fs::write(instance_dir.join("eula.txt"), "eula=true")
-                .await
-                .wrap_err(format!("Failed to write eula.txt for {}", version_meta.id))?;
-
-            // write settings
-            pb_server.set_message("Writing settings...");
-            let settings = InstanceSettings::new(jre_version);
-            let settings_path =
-                INSTANCE_SETTINGS_BASE_DIR.join(format!("{}.toml", version_meta.id));
-
-            settings.save(&settings_path).await?;
-
-            // update meta
-            pb_server.set_message("Updating metadata...");
-            let mut instance_meta = InstanceMeta::new(version_meta.id, jre_version);
-            instance_meta.add_file(&instance_dir);
-            instance_meta.add_file(&settings_path);
-
-            let mut meta = cloned_meta.lock();
-            meta.add_instance(instance_meta);
-            meta.save()?;
+        // old and experimental versions may not have a server at all
+        if !version_meta.downloads.contains_key("server") {
+            pb_server.finish_with_message("Skipped (no server jar for this version)");
+            warn!(
+                version = version_display,
+                "No server jar available, skipping"
+            );
+            continue;
+        }
 
-            pb_server.finish_with_message("Done!");
+        let required = version_meta.java_version.major_version;
+        let jre = options.jre_spec(required);
+        if jre.major < required {
+            let e =
+                eyre!("JRE {jre} is too old for {version_display}, which needs Java {required}");
+            pb_server.finish_with_message(format!("Failed (needs Java {required})"));
+            if options.keep_going {
+                failed.push((version_display, e));
+                continue;
+            }
+            return Err(e);
+        }
 
-            info!(version = thread_version_display, "Installed version");
-            debug!(version = thread_version_display, "Exiting install thread");
-            Ok::<(), eyre::Report>(())
-        });
+        // spawn a thread to install the version
+        let handle = install_threads.spawn(install_server(
+            version_meta,
+            pb_server,
+            options.clone(),
+            download_permits.clone(),
+        ));
+        task_labels.insert(handle.id(), version_display.clone());
 
         // if the JRE is already installed, skip it
-        if META!().jre_installed(jre_version) || jres_installed.contains(&jre_version) {
+        let jre_key = jre.key();
+        if META!().jre_installed(&jre_key) || jres_installed.contains(&jre_key) {
             debug!(
-                jre = jre_version,
+                jre = jre_key,
                 version = version_display,
                 "Skipping JRE install"
             );
@@ -161,67 +372,709 @@ pub(crate) async fn install_versions(versions: Vec<&GameVersion>) -> Result<()>
         }
 
         // otherwise, install it
-        jres_installed.push(jre_version);
+        jres_installed.push(jre_key);
 
         info!(
-            jre = jre_version,
+            jre = %jre,
             version = version_display,
             "Installing JRE"
         );
 
-        let pb_jre = bars.add(
-            ProgressBar::new_spinner()
-                .with_style(PB_STYLE.clone())
-                .with_prefix(format!("JRE {jre_version} for {}", version.id)),
-        );
-        pb_jre.enable_steady_tick(Duration::from_millis(100));
+        let pb_jre = spinner(format!("JRE {jre} for {}", version.id));
 
         // at the same time, spawn a thread to install the JRE
-        install_threads.spawn(async move {
-            pb_jre.set_message("Installing JRE...");
-            install_jre(jre_version, &pb_jre)
-                .await
-                .wrap_err(format!("Failed to install JRE {jre_version}"))?;
+        let label = format!("JRE {jre}");
+        let handle = install_threads.spawn(queue_jre(
+            jre,
+            pb_jre,
+            download_permits.clone(),
+            options.refresh_jre,
+        ));
+        task_labels.insert(handle.id(), label);
+
+        debug!(version = version_display, version.url, "Exiting loop");
+    }
+
+    while let Some(result) = install_threads.join_next_with_id().await {
+        let (id, result) = result?;
+        let label = task_labels.remove(&id).expect("infallible");
+
+        match result.wrap_err("Failed to install server or JRE") {
+            Ok(()) => succeeded.push(label),
+            Err(e) if options.keep_going => failed.push((label, e)),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if options.stats {
+        print_transfer_stats();
+    }
+
+    finish_batch("install", &succeeded, &failed)
+}
+
+/// Prints a table of the download and extraction timings recorded so far
+fn print_transfer_stats() {
+    let stats = TRANSFER_STATS.lock();
+    if stats.is_empty() {
+        println!("\nNothing was downloaded or extracted");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
+    table.set_titles(row![b => "Item", "Step", "Size", "Response", "Total", "Throughput"]);
+
+    for stat in stats.iter() {
+        table.add_row(row![
+            stat.label,
+            stat.kind,
+            HumanBytes(stat.bytes),
+            stat.response_time
+                .map_or_else(|| "-".to_string(), |t| format!("{t:.2?}")),
+            format!("{:.2?}", stat.elapsed),
+            format!("{}/s", HumanBytes(stat.throughput())),
+        ]);
+    }
+
+    println!();
+    table.printstd();
+}
+
+/// Which of a version's jars to download
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum JarSide {
+    Server,
+    Client,
+}
+
+impl JarSide {
+    /// The key of the jar in [`VersionMetadata::downloads`], which also names it in messages
+    fn as_str(self) -> &'static str {
+        match self {
+            JarSide::Server => "server",
+            JarSide::Client => "client",
+        }
+    }
+}
+
+/// Makes sure a jar is in the store, downloading it once a permit is available if not
+///
+/// Instances hardlink their jars to the entry, so patching a jar in place changes it for every
+/// instance installed from it. Entries that no longer match their checksum are downloaded again.
+async fn store_jar(
+    id: &VersionNumber,
+    side: JarSide,
+    download: &VersionDownload,
+    pb: &ProgressBar,
+    download_permits: &Semaphore,
+) -> Result<PathBuf> {
+    let store_path = STORE_BASE_DIR.join(&download.sha1);
+
+    if store_path.is_file() {
+        let jar = fs::read(&store_path).await.wrap_err(format!(
+            "Failed to read store entry {}",
+            store_path.display()
+        ))?;
+        if sha1_hex(&jar) == download.sha1 {
+            debug!(
+                sha1 = download.sha1,
+                side = side.as_str(),
+                "Jar found in store"
+            );
+            return Ok(store_path);
+        }
+
+        warn!(?store_path, "Store entry is damaged, downloading it again");
+    }
+
+    pb.set_message("Queued for download...");
+    let _permit = download_permits.acquire().await?;
+
+    fs::create_dir_all(STORE_BASE_DIR.as_path())
+        .await
+        .wrap_err("Failed to create store directory")?;
+    download_jar(id, side, download, &store_path, pb).await?;
+
+    Ok(store_path)
+}
 
-            Ok::<(), eyre::Report>(())
+/// Links the client jar of a version into an instance as [`CLIENT_JAR`], returning its SHA-1
+///
+/// Very old versions have no client download, which is skipped with a warning.
+async fn install_client_jar(
+    version_meta: &VersionMetadata,
+    instance_dir: &Path,
+    pb: &ProgressBar,
+    download_permits: &Semaphore,
+) -> Result<Option<String>> {
+    let Some(download) = version_meta.downloads.get(JarSide::Client.as_str()) else {
+        warn!(version = %version_meta.id, "No client jar available, skipping");
+        PROGRESS.suspend(|| eprintln!("Warning: {} has no client jar", version_meta.id));
+        return Ok(None);
+    };
+
+    let store_path = store_jar(
+        &version_meta.id,
+        JarSide::Client,
+        download,
+        pb,
+        download_permits,
+    )
+    .await?;
+
+    pb.set_message("Linking client jar...");
+    link_or_copy(&store_path, &instance_dir.join(CLIENT_JAR))
+        .await
+        .wrap_err(format!("Failed to link client jar for {}", version_meta.id))?;
+
+    Ok(Some(download.sha1.clone()))
+}
+
+/// Releases references to store entries, removing the entries that are no longer used
+///
+/// New references should be added first, so entries that are still needed are kept
+fn release_store_entries(meta: &mut AppMeta, hashes: &[String]) -> Result<()> {
+    for hash in hashes {
+        if meta.remove_store_ref(hash) {
+            let path = STORE_BASE_DIR.join(hash);
+            if !path.exists() {
+                continue;
+            }
+            info!(?path, "Removing unreferenced store entry");
+            std::fs::remove_file(&path)
+                .wrap_err(format!("Failed to remove store entry {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Downloads a jar to `store_path`, verifying it against its checksum
+///
+/// The jar is written to a `.part` file next to it first, so an interrupted download is resumed.
+async fn download_jar(
+    id: &VersionNumber,
+    side: JarSide,
+    download: &VersionDownload,
+    store_path: &Path,
+    pb: &ProgressBar,
+) -> Result<()> {
+    let side_name = side.as_str();
+    pb.set_message(format!("Downloading {side_name} jar..."));
+    let part_path = store_path.with_extension("part");
+    // the mirror serves the same jars, so they are still checked against Mojang's checksums
+    let url = MIRROR.get().map_or_else(
+        || download.url.clone(),
+        |mirror| mirror.rewrite(&download.url),
+    );
+    debug!(url, "Downloading jar");
+    let start = Instant::now();
+    let response_time = download_resumable(
+        &url,
+        &part_path,
+        pb,
+        |n| pb.set_message(format!("Retrying ({n}/{MAX_ATTEMPTS})...")),
+        |status| eyre!("Unexpected error (status code {status}): {url}"),
+    )
+    .await
+    .wrap_err(format!("Failed to download {side_name} jar"))?
+    .response_time;
+    let jar = fs::read(&part_path)
+        .await
+        .wrap_err(format!("Failed to read downloaded {side_name} jar"))?;
+    TRANSFER_STATS.lock().push(TransferStat {
+        label: match side {
+            JarSide::Server => format!("Server {id}"),
+            JarSide::Client => format!("Client {id}"),
+        },
+        kind: TransferKind::Download,
+        bytes: jar.len() as u64,
+        response_time: Some(response_time),
+        elapsed: start.elapsed(),
+    });
+
+    let sha1 = sha1_hex(&jar);
+    if sha1 != download.sha1 {
+        // resuming would keep the bad data, so start over next time
+        let _ = fs::remove_file(&part_path).await;
+        return Err(McdlError::ChecksumMismatch {
+            file: format!("{side_name} jar of {id}"),
+            expected: download.sha1.clone(),
+            actual: sha1,
+        }
+        .into());
+    }
+
+    fs::rename(&part_path, store_path)
+        .await
+        .wrap_err(format!("Failed to write {side_name} jar for {id}"))
+}
+
+/// Installs a JRE once a download permit is available, run in its own task by [`install_versions`]
+async fn queue_jre(
+    jre: JreSpec,
+    pb: ProgressBar,
+    download_permits: Arc<Semaphore>,
+    refresh: bool,
+) -> Result<()> {
+    pb.set_message("Queued for download...");
+    let _permit = download_permits.acquire_owned().await?;
+
+    pb.set_message("Installing JRE...");
+    install_jre(&jre, &pb, refresh)
+        .await
+        .wrap_err(format!("Failed to install JRE {jre}"))
+}
+
+/// Fetches the metadata for each version concurrently, returning the results in order
+///
+/// Metadata requests are latency-bound rather than bandwidth-bound,
+/// so they are limited separately from downloads
+#[instrument(err, skip_all)]
+async fn fetch_version_metadata(
+    versions: &[&GameVersion],
+    bars: &[ProgressBar],
+    jobs: NonZeroUsize,
+) -> Result<Vec<Result<VersionMetadata>>> {
+    let permits = Arc::new(Semaphore::new(jobs.get()));
+    let mut metadata_threads = JoinSet::new();
+
+    for (i, (version, pb)) in versions.iter().zip(bars).enumerate() {
+        let version = (*version).clone();
+        let permits = permits.clone();
+        let pb = pb.clone();
+        metadata_threads.spawn(async move {
+            let _permit = permits.acquire_owned().await.expect("semaphore closed");
+            pb.set_message("Getting version metadata...");
+            (i, get_version_metadata(&version).await)
         });
+    }
 
-        debug!(version = version_display, version.url, "Exiting loop");
+    let mut version_metas = versions.iter().map(|_| None).collect_vec();
+    while let Some(result) = metadata_threads.join_next().await {
+        let (i, result) = result?;
+        version_metas[i] = Some(result);
+    }
+
+    Ok(version_metas
+        .into_iter()
+        .map(|result| result.expect("infallible"))
+        .collect())
+}
+
+/// Asks whether the user accepts the Minecraft EULA, which servers refuse to start without
+///
+/// Outside a terminal, the EULA is not accepted.
+fn ask_eula() -> Result<bool> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        let message = "The Minecraft EULA was not accepted, pass --accept-eula to accept it";
+        warn!("{message}");
+        PROGRESS.suspend(|| eprintln!("Warning: {message}"));
+        return Ok(false);
+    }
+
+    let prompt = format!("Do you accept the Minecraft EULA ({EULA_URL})?");
+    Ok(PROGRESS.suspend(|| Confirm::new().with_prompt(prompt).default(false).interact())?)
+}
+
+/// Asks whether to go ahead with an install, showing how much it would download
+///
+/// Server jars already in the store, installed JREs, and instances that
+/// won't be reinstalled are not counted. The size of a JRE isn't known up front.
+/// Doesn't ask with `--yes` or outside a terminal
+fn confirm_downloads(
+    version_metas: &[Result<VersionMetadata>],
+    options: &InstallOptions,
+) -> Result<bool> {
+    if options.yes || !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Ok(true);
+    }
+
+    let mut size = 0;
+    let mut jres = HashSet::new();
+    for version_meta in version_metas.iter().flatten() {
+        let Some(download) = version_meta.downloads.get("server") else {
+            continue;
+        };
+        let key = options
+            .name
+            .clone()
+            .unwrap_or_else(|| version_meta.id.to_string());
+        if !options.force_reinstall && META!().instance_installed(&key) {
+            continue;
+        }
+
+        if !STORE_BASE_DIR.join(&download.sha1).exists() {
+            size += download.size;
+        }
+        if options.client
+            && let Some(client) = version_meta.downloads.get(JarSide::Client.as_str())
+            && !STORE_BASE_DIR.join(&client.sha1).exists()
+        {
+            size += client.size;
+        }
+        let jre_key = options
+            .jre_spec(version_meta.java_version.major_version)
+            .key();
+        if !META!().jre_installed(&jre_key) {
+            jres.insert(jre_key);
+        }
+    }
+
+    let mut downloads = vec![];
+    if size > 0 {
+        let jars = if options.client {
+            "jars"
+        } else {
+            "server jars"
+        };
+        downloads.push(format!("{} of {jars}", HumanBytes(size)));
+    }
+    match jres.len() {
+        0 => {}
+        1 => downloads.push("1 JRE".to_string()),
+        n => downloads.push(format!("{n} JREs")),
+    }
+    if downloads.is_empty() {
+        return Ok(true);
+    }
+
+    let prompt = format!("Download {}?", downloads.join(" and "));
+    Ok(PROGRESS.suspend(|| Confirm::new().with_prompt(prompt).default(true).interact())?)
+}
+
+/// Installs the server for a single version, run in its own task by [`install_versions`]
+#[allow(clippy::too_many_lines)]
+#[instrument(err, ret(level = "debug"), skip_all, fields(version = %version_meta.id))]
+async fn install_server(
+    version_meta: VersionMetadata,
+    pb_server: ProgressBar,
+    options: InstallOptions,
+    download_permits: Arc<Semaphore>,
+) -> Result<()> {
+    debug!("Entering install thread");
+
+    let Some(download) = version_meta.downloads.get("server") else {
+        pb_server.finish_with_message("Cancelled (no server jar)");
+        debug!("Exiting install thread (no server jar)");
+        return Ok(());
+    };
+
+    let key = options
+        .name
+        .clone()
+        .unwrap_or_else(|| version_meta.id.to_string());
+    let location = install_location(&options, &key);
+    let instance_dir = location
+        .clone()
+        .unwrap_or_else(|| INSTANCE_BASE_DIR.join(&key));
+
+    if !options.force_reinstall && META.lock().instance_installed(&key) {
+        pb_server.finish_with_message("Cancelled (already installed)");
+        debug!("Exiting install thread (already installed)");
+        return Ok(());
+    }
+
+    let store_path = store_jar(
+        &version_meta.id,
+        JarSide::Server,
+        download,
+        &pb_server,
+        &download_permits,
+    )
+    .await?;
+
+    pb_server.set_message("Linking server jar...");
+    fs::create_dir_all(&instance_dir).await.wrap_err(format!(
+        "Failed to create instance directory for {}",
+        version_meta.id
+    ))?;
+
+    link_or_copy(&store_path, &instance_dir.join("server.jar"))
+        .await
+        .wrap_err(format!("Failed to link server jar for {}", version_meta.id))?;
+
+    let client_sha1 = if options.client {
+        install_client_jar(&version_meta, &instance_dir, &pb_server, &download_permits).await?
+    } else {
+        None
+    };
+
+    let launcher = install_loader(
+        options.loader,
+        &version_meta.id,
+        options.loader_version.as_deref(),
+        &instance_dir,
+        &pb_server,
+        &download_permits,
+    )
+    .await?;
+
+    // write eula
+    pb_server.set_message("Writing eula.txt...");
+    fs::write(
+        instance_dir.join("eula.txt"),
+        format!("eula={}", options.accept_eula),
+    )
+    .await
+    .wrap_err(format!("Failed to write eula.txt for {}", version_meta.id))?;
+
+    // write settings
+    pb_server.set_message("Writing settings...");
+    let jre_version = options
+        .jre_spec(version_meta.java_version.major_version)
+        .major;
+    let settings = options.instance_settings(jre_version, launcher);
+    let settings_path = location.as_ref().map_or_else(
+        || INSTANCE_SETTINGS_BASE_DIR.join(format!("{key}.toml")),
+        |dir| dir.join(OUTPUT_DIR_SETTINGS),
+    );
+
+    settings.save(&settings_path).await?;
+
+    // update meta
+    pb_server.set_message("Updating metadata...");
+    let sha1 = download.sha1.clone();
+    let mut instance_meta = InstanceMeta::new(version_meta.id, jre_version);
+    instance_meta.name = options.name;
+    instance_meta.location = location;
+    instance_meta.server_url = Some(download.url.clone());
+    instance_meta.server_sha1 = Some(sha1.clone());
+    track_instance_files(
+        &mut instance_meta,
+        &instance_dir,
+        &settings_path,
+        options.loader,
+    );
+    instance_meta.add_store_entry(&sha1);
+    if let Some(client_sha1) = &client_sha1 {
+        instance_meta.add_file(&instance_dir.join(CLIENT_JAR));
+        instance_meta.add_store_entry(client_sha1);
+        instance_meta.client_sha1 = Some(client_sha1.clone());
     }
 
-    while let Some(result) = install_threads.join_next().await {
-        result?.wrap_err("Failed to install server or JRE")?;
+    let mut meta = META.lock();
+    meta.add_store_ref(&sha1);
+    if let Some(client_sha1) = &client_sha1 {
+        meta.add_store_ref(client_sha1);
+    }
+    if let Some(old) = meta.remove_instance(&key) {
+        // reinstalling, so release the old jar and carry over the backups
+        release_store_entries(&mut meta, &old.store_entries)?;
+        instance_meta.backups = old.backups;
     }
+    meta.add_instance(instance_meta);
+    meta.save()?;
+    drop(meta);
 
+    pb_server.finish_with_message("Done!");
+
+    info!("Installed version");
+    debug!("Exiting install thread");
     Ok(())
 }
 
+/// The directory to install an instance in, if not under [`INSTANCE_BASE_DIR`]
+///
+/// Reinstalling keeps an instance where it was installed, unless `--output-dir` moves it.
+fn install_location(options: &InstallOptions, key: &str) -> Option<PathBuf> {
+    options.output_dir.clone().or_else(|| {
+        META.lock()
+            .instances
+            .get(key)
+            .and_then(|i| i.location.clone())
+    })
+}
+
+/// Records the files of a newly installed instance, which are removed when it's uninstalled
+fn track_instance_files(
+    instance_meta: &mut InstanceMeta,
+    instance_dir: &Path,
+    settings_path: &Path,
+    loader: Loader,
+) {
+    if instance_meta.location.is_some() {
+        // the directory isn't mcdl's, so only the files in it are tracked
+        for path in located_instance_files(instance_dir, loader) {
+            if path.exists() {
+                instance_meta.add_file(&path);
+            }
+        }
+    } else {
+        instance_meta.add_file(instance_dir);
+        instance_meta.add_file(settings_path);
+    }
+}
+
+/// The files mcdl writes into an instance installed with `--output-dir`, apart from the client jar
+///
+/// These are all that's removed on uninstall, since the directory itself may be the user's.
+fn located_instance_files(dir: &Path, loader: Loader) -> Vec<PathBuf> {
+    let launcher_files = match loader {
+        Loader::Vanilla => &[],
+        Loader::Fabric => Fabric::FILES,
+        Loader::Quilt => Quilt::FILES,
+    };
+
+    ["server.jar", "eula.txt", OUTPUT_DIR_SETTINGS]
+        .iter()
+        .chain(launcher_files)
+        .map(|file| dir.join(file))
+        .collect()
+}
+
+/// Installs the launcher of `loader` into an instance, if it isn't vanilla,
+/// returning its path relative to the instance and the loader version
+async fn install_loader(
+    loader: Loader,
+    game_version: &VersionNumber,
+    loader_version: Option<&str>,
+    instance_dir: &Path,
+    pb: &ProgressBar,
+    download_permits: &Semaphore,
+) -> Result<Option<(PathBuf, String)>> {
+    let launcher = match loader {
+        Loader::Vanilla => return Ok(None),
+        Loader::Fabric => {
+            install_launcher::<Fabric>(
+                game_version,
+                loader_version,
+                instance_dir,
+                pb,
+                download_permits,
+            )
+            .await?
+        }
+        Loader::Quilt => {
+            install_launcher::<Quilt>(
+                game_version,
+                loader_version,
+                instance_dir,
+                pb,
+                download_permits,
+            )
+            .await?
+        }
+    };
+    Ok(Some(launcher))
+}
+
+/// Installs the launcher of a mod loader into an instance,
+/// returning its path relative to the instance and the loader version
+///
+/// Uses the latest stable loader for the game version if no loader version is given.
+#[instrument(err, ret(level = "debug"), skip(instance_dir, pb, download_permits))]
+async fn install_launcher<L: LoaderApi>(
+    game_version: &VersionNumber,
+    loader_version: Option<&str>,
+    instance_dir: &Path,
+    pb: &ProgressBar,
+    download_permits: &Semaphore,
+) -> Result<(PathBuf, String)> {
+    let name = L::NAME;
+    pb.set_message(format!("Getting {name} versions..."));
+    let loaders = L::loader_versions(game_version).await?;
+    let loader = match loader_version {
+        Some(version) => loaders
+            .iter()
+            .find(|loader| loader.version == version)
+            .ok_or_else(|| eyre!("{name} loader {version} is not available for {game_version}"))?,
+        None => latest_stable(&loaders)
+            .ok_or_else(|| eyre!("{name} does not support {game_version}"))?,
+    };
+
+    pb.set_message("Queued for download...");
+    let _permit = download_permits.acquire().await?;
+
+    pb.set_message(format!("Downloading {name} loader {}...", loader.version));
+    L::install_launcher(game_version, &loader.version, instance_dir, pb).await?;
+
+    Ok((PathBuf::from(L::LAUNCHER_JAR), loader.version.clone()))
+}
+
 // pub(crate) async fn install_version(version: &GameVersion) -> Result<()> {
 //     install_versions(vec![version]).await
 // }
 
+/// Installs a JRE, from its cached archive unless `refresh` is set
 #[instrument(err, ret(level = "debug"), skip(pb))]
-async fn install_jre(major_version: u8, pb: &ProgressBar) -> Result<()> {
-    let jre_dir = JRE_BASE_DIR.join(major_version.to_string());
+async fn install_jre(jre: &JreSpec, pb: &ProgressBar, refresh: bool) -> Result<()> {
+    let jre_key = jre.key();
+    let jre_dir = JRE_BASE_DIR.join(&jre_key);
 
-    if META!().jre_installed(major_version) {
+    if META!().jre_installed(&jre_key) {
         pb.finish_with_message("Cancelled (already installed)");
         debug!("Cancelled JRE install (this should never happen)");
         return Ok(());
     }
 
-    pb.set_message("Downloading JRE...");
-    info!("Starting JRE download");
-    let jre = download_jre(major_version).await?;
-    info!("Downloaded JRE");
+    let archive = match read_cached_jre(jre).await {
+        Some((archive, release)) if !refresh => {
+            info!(release, "Using cached JRE archive");
+            archive
+        }
+        _ => {
+            pb.set_message("Downloading JRE...");
+            info!("Starting JRE download");
+            let (archive, release) = download_jre(jre, pb, |n| {
+                pb.set_message(format!("Retrying JRE download ({n}/{MAX_ATTEMPTS})..."));
+            })
+            .await?;
+            info!(release, "Downloaded JRE");
+            archive
+        }
+    };
 
     pb.set_message("Extracting JRE...");
     info!("Starting JRE extraction");
-    extract_jre(jre, &jre_dir).wrap_err("Failed to extract JRE")?;
+    let (start, bytes) = (Instant::now(), archive.len() as u64);
+    let style = pb.style();
+    pb.set_style(EXTRACT_PB_STYLE.clone());
+    let extracted = extract_jre(archive, &jre_dir, Some(pb));
+    pb.set_style(style);
+    pb.unset_length();
+    if let Err(e) = extracted {
+        // the cached archive is likely corrupt, and would fail the same way next time
+        warn!(?e, "Removing partly extracted JRE and its cached archive");
+        let _ = std::fs::remove_dir_all(&jre_dir);
+        remove_cached_jre(jre).await;
+        return Err(e.wrap_err("Failed to extract JRE"));
+    }
+    TRANSFER_STATS.lock().push(TransferStat {
+        label: format!("JRE {jre}"),
+        kind: TransferKind::Extract,
+        bytes,
+        response_time: None,
+        elapsed: start.elapsed(),
+    });
     info!("Extracted JRE");
 
+    // a truncated archive can extract a java binary that doesn't run,
+    // but a JRE for another architecture can't be run here to find out
+    pb.set_message("Checking JRE...");
+    if jre.target_arch() != std::env::consts::ARCH {
+        debug!(
+            arch = jre.target_arch(),
+            "Skipping check of JRE for another architecture"
+        );
+    } else if let Err(e) = check_java(&get_java_path(jre), jre.major).await {
+        warn!(?e, "Removing broken JRE and its cached archive");
+        let _ = std::fs::remove_dir_all(&jre_dir);
+        remove_cached_jre(jre).await;
+        return Err(e.wrap_err(format!(
+            "JRE {jre} is broken and was removed, try again to download a fresh copy"
+        )));
+    }
+
     pb.set_message("Updating metadata...");
-    META!().add_jre(major_version);
+    META!().add_jre(&jre_key, jre.target_arch());
     META!().save()?;
 
     pb.finish_with_message("Done!");
@@ -229,178 +1082,2185 @@ async fn install_jre(major_version: u8, pb: &ProgressBar) -> Result<()> {
     Ok(())
 }
 
-#[instrument(err, ret(level = "debug"), skip(id))]
-pub(crate) fn uninstall_instance(id: VersionNumber) -> Result<()> {
-    let pb = ProgressBar::new_spinner()
-        .with_style(PB_STYLE.clone())
-        .with_prefix(id.to_string());
-    pb.enable_steady_tick(Duration::from_millis(100));
+/// Runs `java -version`, checking that it exits successfully and reports the `major` version
+async fn check_java(java_path: &Path, major: u8) -> Result<()> {
+    let output = Command::new(java_path)
+        .arg("-version")
+        .output()
+        .await
+        .wrap_err(format!("Failed to run {}", java_path.display()))?;
 
-    let mut instance_files = vec![];
+    // the version goes to stderr
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    debug!(status = ?output.status, %stderr, "Ran java -version");
+    if !output.status.success() {
+        return Err(eyre!("`java -version` exited with {}", output.status));
+    }
+
+    match parse_java_version(&stderr) {
+        Some(version) if version == major => Ok(()),
+        Some(version) => Err(eyre!("Expected Java {major}, but found Java {version}")),
+        None => Err(eyre!(
+            "Failed to read the Java version from `java -version`"
+        )),
+    }
+}
+
+/// Replaces the server jar of an instance with the one for `target`,
+/// keeping its worlds and settings other than the Java version
+#[instrument(err, ret(level = "debug"), skip(target), fields(target = %target.id))]
+pub(crate) async fn update_instance(name: &str, target: &GameVersion) -> Result<()> {
+    let id = &resolve_instance(name)?;
+    let pb = spinner(id.clone());
+
+    pb.set_message("Getting version metadata...");
+    let version_meta = get_version_metadata(target).await?;
+    let Some(download) = version_meta.downloads.get("server") else {
+        return Err(McdlError::NoServerJar(target.id.clone()).into());
+    };
+
+    let store_path = store_jar(
+        &version_meta.id,
+        JarSide::Server,
+        download,
+        &pb,
+        &Semaphore::new(1),
+    )
+    .await?;
+
+    pb.set_message("Updating settings...");
+    let settings_path = settings_path(&META.lock(), id);
+    let mut settings = InstanceSettings::from_file(&settings_path).await?;
+    let required = version_meta.java_version.major_version;
+    // a pinned JRE is kept, unless it is too old for the new version
+    if let Some(release) = settings
+        .java
+        .release
+        .take_if(|release| release_major(release).is_none_or(|major| major < required))
+    {
+        status!(
+            "Unpinned JRE {release}, since {} needs Java {required}",
+            target.id
+        );
+    }
+    let jre_version = settings
+        .java
+        .release
+        .as_deref()
+        .and_then(release_major)
+        .unwrap_or(required);
+    settings.java.version = jre_version;
+
+    // launchers are built for a game version, so the loader is installed again for the new one
+    let instance_path = instance_dir(&META.lock(), id);
+    if let Some((launcher, loader_version)) = install_loader(
+        settings.server.loader,
+        &version_meta.id,
+        settings.server.loader_version.as_deref(),
+        &instance_path,
+        &pb,
+        &Semaphore::new(1),
+    )
+    .await?
+    {
+        settings.server.jar = launcher;
+        settings.server.loader_version = Some(loader_version);
+    }
+    settings.save(&settings_path).await?;
+
+    pb.set_message("Linking server jar...");
+    let jar_path = instance_path.join(settings.server.vanilla_jar());
+    link_or_copy(&store_path, &jar_path)
+        .await
+        .wrap_err(format!("Failed to link server jar for {id}"))?;
+
+    let client_sha1 = update_client_jar(id, &version_meta, &instance_path, &pb).await?;
+
+    pb.set_message("Updating metadata...");
+    let mut meta = META.lock();
+    meta.add_store_ref(&download.sha1);
+    if let Some(sha1) = &client_sha1 {
+        meta.add_store_ref(sha1);
+    }
+    let instance = meta
+        .instances
+        .get_mut(id)
+        .ok_or_else(|| McdlError::InstanceNotFound(id.clone()))?;
+    let new_entries = std::iter::once(download.sha1.clone())
+        .chain(client_sha1.clone())
+        .collect();
+    let old_entries = std::mem::replace(&mut instance.store_entries, new_entries);
+    if instance.client_sha1.is_some() && client_sha1.is_none() {
+        instance.remove_file(&instance_path.join(CLIENT_JAR));
+    }
+    instance.client_sha1 = client_sha1;
+    // keep the key stable, even though the version changes
+    instance.name = Some(id.clone());
+    instance.id = version_meta.id.clone();
+    instance.jre = jre_version;
+    instance.server_url = Some(download.url.clone());
+    instance.server_sha1 = Some(download.sha1.clone());
+    release_store_entries(&mut meta, &old_entries)?;
+    meta.save()?;
+    drop(meta);
+
+    pb.finish_with_message(format!("Updated to {}", version_meta.id));
+    Ok(())
+}
+
+/// Replaces the client jar of an instance installed with `--client` with the one of the version
+/// it's being updated to, returning its SHA-1
+///
+/// The old client jar is removed if the new version has none.
+async fn update_client_jar(
+    id: &str,
+    version_meta: &VersionMetadata,
+    instance_path: &Path,
+    pb: &ProgressBar,
+) -> Result<Option<String>> {
+    let has_client = META
+        .lock()
+        .instances
+        .get(id)
+        .is_some_and(|instance| instance.client_sha1.is_some());
+    if !has_client {
+        return Ok(None);
+    }
+
+    let sha1 = install_client_jar(version_meta, instance_path, pb, &Semaphore::new(1)).await?;
+    let client_path = instance_path.join(CLIENT_JAR);
+    if sha1.is_none() && client_path.exists() {
+        fs::remove_file(&client_path).await.wrap_err(format!(
+            "Failed to remove old client jar {}",
+            client_path.display()
+        ))?;
+    }
+    Ok(sha1)
+}
+
+/// Checks that an instance's files exist and its server jar matches the version metadata
+///
+/// Prints the status of each file, returning `false` if any of them are missing or corrupt
+#[instrument(err, ret(level = "debug"), skip(version))]
+pub(crate) async fn verify_instance(id: &str, version: &GameVersion) -> Result<bool> {
+    let (files, recorded_sha1, client_sha1) = META!()
+        .instances
+        .get(id)
+        .map(|instance| {
+            (
+                instance.files.clone(),
+                instance.server_sha1.clone(),
+                instance.client_sha1.clone(),
+            )
+        })
+        .ok_or_else(|| McdlError::InstanceNotFound(id.to_string()))?;
+
+    // instances installed by older versions of mcdl didn't record the checksum
+    let expected_sha1 = if let Some(sha1) = recorded_sha1 {
+        sha1
+    } else {
+        let version_meta = get_version_metadata(version).await?;
+        let Some(download) = version_meta.downloads.get("server") else {
+            return Err(McdlError::NoServerJar(version.id.clone()).into());
+        };
+        download.sha1.clone()
+    };
+
+    let mut healthy = true;
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
+    table.set_titles(row![b => "File", "Status"]);
+
+    // fall back to the default jar if the settings are gone, which is reported below
+    let settings_path = settings_path(&META.lock(), id);
+    let jar = match InstanceSettings::from_file(&settings_path).await {
+        Ok(settings) => settings.server.vanilla_jar().to_path_buf(),
+        Err(_) => InstanceServerSettings::default().jar,
+    };
+    let instance_path = instance_dir(&META.lock(), id);
+    let mut jars = vec![(instance_path.join(jar), expected_sha1)];
+    if let Some(sha1) = client_sha1 {
+        jars.push((instance_path.join(CLIENT_JAR), sha1));
+    }
+
+    // jars are checked against their checksums below instead
+    for file in files
+        .iter()
+        .filter(|file| jars.iter().all(|(jar, _)| jar != *file))
+    {
+        let status = if file.exists() {
+            "ok".green().to_string()
+        } else {
+            healthy = false;
+            "missing".yellow().to_string()
+        };
+        table.add_row(row![file.display(), status]);
+    }
+
+    for (jar_path, expected_sha1) in jars {
+        let status = match fs::read(&jar_path).await {
+            Ok(data) if sha1_hex(&data) == expected_sha1 => "ok".green().to_string(),
+            Ok(_) => {
+                healthy = false;
+                "checksum mismatch".red().to_string()
+            }
+            Err(e) => {
+                healthy = false;
+                warn!(?e, path = ?jar_path, "Failed to read jar");
+                "missing".yellow().to_string()
+            }
+        };
+        table.add_row(row![jar_path.display(), status]);
+    }
+
+    table.printstd();
+    Ok(healthy)
+}
+
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn uninstall_instances(
+    names: Vec<String>,
+    options: UninstallOptions,
+) -> Result<()> {
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+
+    for name in names {
+        let result = match resolve_instance(&name) {
+            Ok(id) => uninstall_instance(&id, options).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => succeeded.push(name),
+            Err(e) if options.keep_going => failed.push((name, e)),
+            Err(e) => return Err(e),
+        }
+    }
+
+    finish_batch("uninstall", &succeeded, &failed)
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn uninstall_instance(id: &str, options: UninstallOptions) -> Result<()> {
+    let pb = spinner(id.to_string());
+
+    let mut instance_files = vec![];
+    let mut store_entries = vec![];
+
+    pb.set_message("Checking if instance exists...");
+    let installed_jre = if let Some(instance) = META!().instances.get(id) {
+        instance_files.extend(instance.files.clone());
+        store_entries.extend(instance.store_entries.clone());
+        instance.jre
+    } else {
+        return Err(McdlError::InstanceNotFound(id.to_string()).into());
+    };
+
+    // read before the settings file is removed with the rest of the instance
+    let jre = instance_jre_key(id, installed_jre).await;
+
+    pb.set_message("Removing files...");
+    for path in &instance_files {
+        if !path.exists() {
+            warn!(?path, "File does not exist");
+            continue;
+        }
+
+        if path.is_dir() {
+            info!(?path, "Removing directory");
+            std::fs::remove_dir_all(path)
+                .wrap_err(format!("Failed to remove directory {}", path.display()))?;
+        } else {
+            info!(?path, "Removing file");
+            std::fs::remove_file(path)
+                .wrap_err(format!("Failed to remove file {}", path.display()))?;
+        }
+
+        META!().instances.get_mut(id).unwrap().remove_file(path);
+        META!().save()?;
+    }
+
+    // backups aren't tracked once the instance is gone, so they would never be rotated or cleaned up
+    let backup_dir = BACKUP_BASE_DIR.join(id);
+    if !options.keep_backups && backup_dir.exists() {
+        pb.set_message("Removing backups...");
+        info!(?backup_dir, "Removing backups");
+        std::fs::remove_dir_all(&backup_dir).wrap_err(format!(
+            "Failed to remove directory {}",
+            backup_dir.display()
+        ))?;
+    }
+
+    pb.set_message("Releasing store entries...");
+    release_store_entries(&mut META!(), &store_entries)?;
+
+    pb.set_message("Updating metadata...");
+    META!().remove_instance(id);
+    META!().save()?;
+
+    if !options.keep_jre {
+        pb.set_message("Removing unused JRE...");
+        let path = JRE_BASE_DIR.join(&jre);
+        if unused_jres().await?.contains(&path) {
+            info!(?path, "Removing JRE no longer used by any instance");
+            std::fs::remove_dir_all(&path)
+                .wrap_err(format!("Failed to remove directory {}", path.display()))?;
+            META!().remove_jre(&jre);
+            META!().save()?;
+        }
+    }
+
+    pb.finish_with_message("Done!");
+    Ok(())
+}
+
+#[allow(clippy::too_many_lines)]
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn run_instance(name: &str, options: RunOptions) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let instance_path = instance_dir(&META.lock(), &id);
+    let max_runtime = options.max_runtime;
+
+    let settings_path = settings_path(&META.lock(), &id);
+    let mut settings = InstanceSettings::from_file(&settings_path).await?;
+    debug!(?settings, "Loaded instance settings");
+
+    if let Some(memory) = options.min_memory {
+        remove_heap_args(&mut settings.java, "-Xms", "--min-memory");
+        settings.java.min_memory = Some(memory);
+    }
+    if let Some(memory) = options.max_memory {
+        remove_heap_args(&mut settings.java, "-Xmx", "--memory");
+        settings.java.max_memory = Some(memory);
+    }
+    if let Some(preset) = options.flag_preset {
+        settings.java.flag_preset = preset;
+    }
+    if options.port.is_some() {
+        settings.server.port = options.port;
+    }
+    if settings
+        .java
+        .args
+        .iter()
+        .any(|arg| arg.starts_with("-Xms") || arg.starts_with("-Xmx"))
+    {
+        warn!("`java.args` sets the heap size, which overrides the memory settings");
+    }
+
+    // a dry run, so nothing is written
+    if options.print_command {
+        let (java_path, mut args) = launch_command(&settings)?;
+        // the server reads the port from `server.properties`, which `run` would have updated
+        if let Some(port) = settings.server.port {
+            args.extend(["--port".into(), port.to_string().into()]);
+        }
+        let command = std::iter::once(java_path.into_os_string())
+            .chain(args)
+            .map(|s| shell_escape::escape(s.to_string_lossy()).into_owned())
+            .join(" ");
+        println!("{command}");
+        return Ok(());
+    }
+
+    if options.save {
+        settings.save(&settings_path).await?;
+    }
+
+    if let Some(pid) = running_pid(&instance_path)? {
+        return Err(eyre!(
+            "Instance {id} is already running in the background (PID {pid})"
+        ));
+    }
+    check_eula(&instance_path)?;
+    if let Some(port) = settings.server.port {
+        set_server_port(&instance_path, port).await?;
+    }
+
+    if settings.backup.on_run || options.backup {
+        status!("Backing up world...");
+        if let Some(path) = backup_instance(&id, settings.backup.keep).await? {
+            status!("Backed up world to {}", path.display());
+        } else {
+            debug!("No world to back up");
+        }
+    }
+
+    // check if the JRE is installed and install it if not
+    let jre_version = settings.java.version;
+    let jre = settings.java.jre_spec();
+    if jre.target_arch() != std::env::consts::ARCH {
+        return Err(eyre!(
+            "{id} uses a JRE for {}, which can't run on this {} machine. \
+             Run it on a machine with that architecture, or remove `arch` from the [java] section of its settings",
+            jre.target_arch(),
+            std::env::consts::ARCH
+        ));
+    }
+    ensure_jre(&jre, &id).await?;
+
+    // make sure JRE version is correct
+    META!()
+        .instances
+        .get_mut(&id)
+        .ok_or_else(|| McdlError::InstanceNotFound(id.clone()))?
+        .jre = jre_version;
+    META!().save()?;
+    // the server may run for days, so don't keep other commands waiting on it
+    release_meta_lock();
+
+    let (java_path, args) = launch_command(&settings)?;
+    let args_string = args
+        .iter()
+        .map(|s| shell_escape::escape(s.to_string_lossy()))
+        .join(" ");
+
+    debug!(
+        "Starting server with command line: {java} {args}",
+        java = java_path.display(),
+        args = args_string
+    );
+    let mut command = Command::new(&java_path);
+    command.current_dir(&instance_path).args(&args);
+
+    // keep Ctrl-C and terminal hangups from reaching the server
+    #[cfg(unix)]
+    command.process_group(0);
+
+    if options.detach {
+        return spawn_detached(command, &instance_path).wrap_err(format!(
+            "Failed to start server with command line: {java} {args}",
+            java = java_path.display(),
+            args = args_string
+        ));
+    }
+
+    command
+        .kill_on_drop(true)
+        // the server is stopped through its console, so stdin can't be inherited
+        .stdin(Stdio::piped());
+    if !options.exec.is_empty() || options.capture_output {
+        // watched to tell when the server is ready for the commands
+        command.stdout(Stdio::piped());
+    }
+    if options.capture_output {
+        command.stderr(Stdio::piped());
+    }
+
+    let mut child = command.spawn().wrap_err(format!(
+        "Failed to start server with command line: {java} {args}",
+        java = java_path.display(),
+        args = args_string
+    ))?;
+    info!("Started server");
+
+    let console = ServerConsole::attach(
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| eyre!("Server console is not available"))?,
+    );
+    let capture = options.capture_output.then(|| id.clone());
+    let output = child.stdout.take().map(|stdout| {
+        tokio::spawn(exec_when_ready(
+            stdout,
+            console.sender.clone(),
+            options.exec,
+            capture.clone(),
+        ))
+    });
+    let errors = child
+        .stderr
+        .take()
+        .zip(capture)
+        .map(|(stderr, id)| tokio::spawn(log_stderr(stderr, id)));
+
+    let exit = wait_for_server(&mut child, &console, max_runtime).await;
+    // print whatever the server wrote last
+    if let Some(output) = output {
+        let _ = output.await;
+    }
+    if let Some(errors) = errors {
+        let _ = errors.await;
+    }
+    let status = match exit? {
+        ServerExit::MaxRuntime(status) if status.success() => {
+            status!("Server ran for {max_runtime:?} and stopped cleanly");
+            return Ok(());
+        }
+        ServerExit::Exited(status) | ServerExit::MaxRuntime(status) => status,
+        // a user stop is never a crash, so don't offer to upload a crash report
+        ServerExit::Interrupted(status) if status.success() => {
+            status!("Server stopped");
+            return Ok(());
+        }
+        ServerExit::Interrupted(status) => {
+            return Err(eyre!("Server exited with {status} while stopping"));
+        }
+    };
+    // stop forwarding stdin before prompting
+    drop(console);
+
+    if !status.success() {
+        error!(?status, "Server exited with an error");
+        // the server can fail before it gets far enough to write a crash report
+        match latest_crash_report(&instance_path)? {
+            None => println!("Server exited with an error, no crash report found"),
+            Some(_) if options.no_upload => {}
+            Some(report) if OFFLINE.load(Ordering::Relaxed) => println!(
+                "Server exited with an error, not uploading the crash report while offline: {}",
+                report.display()
+            ),
+            Some(report) => {
+                let upload = Confirm::new()
+                    .with_prompt(
+                        "Server exited with an error. Would you like to upload the crash report?",
+                    )
+                    .default(false)
+                    .interact()?;
+
+                if upload {
+                    upload_crash_report(&report, &paste_url(options.paste_url)).await?;
+                }
+            }
+        }
+
+        return Err(eyre!(
+            "Server exited with {status}. Command line: {java} {args}",
+            java = java_path.display(),
+            args = args_string
+        ));
+    }
+
+    Ok(())
+}
+
+/// Removes heap size flags starting with `prefix` from `java.args`, which
+/// come last and would otherwise override the size given with `flag`
+fn remove_heap_args(java: &mut InstanceJavaSettings, prefix: &str, flag: &str) {
+    let removed = java.remove_args(prefix);
+    if !removed.is_empty() {
+        PROGRESS.suspend(|| {
+            eprintln!(
+                "Warning: ignoring {} from `java.args` in favor of {flag}",
+                removed.join(" ")
+            );
+        });
+    }
+}
+
+/// The program and arguments that start the server of an instance with `settings`,
+/// run from the instance directory
+fn launch_command(settings: &InstanceSettings) -> Result<(PathBuf, Vec<OsString>)> {
+    let mut args: Vec<OsString> = vec![];
+    args.extend(settings.java.memory_args().into_iter().map(Into::into)); // heap size
+    args.extend(settings.java.preset_args().into_iter().map(Into::into)); // flag preset
+    args.extend(settings.java.args.iter().map(Into::into)); // jvm args
+    args.extend(vec!["-jar".into(), settings.server.jar.clone().into()]); // server jar
+    args.extend(settings.server.args.iter().map(Into::into)); // server args
+
+    let mut java_path = get_java_path(&settings.java.jre_spec());
+
+    // run java through the wrapper, if any, e.g. `nice -n 10 java ...`
+    if let Some((wrapper, wrapper_args)) = settings.server.launch_wrapper.split_first() {
+        let wrapper_path = find_executable(wrapper)
+            .ok_or_else(|| eyre!("Launch wrapper `{wrapper}` not found"))?;
+
+        args.splice(
+            0..0,
+            wrapper_args
+                .iter()
+                .map(Into::into)
+                .chain([java_path.into_os_string()]),
+        );
+        java_path = wrapper_path;
+    }
+
+    Ok((java_path, args))
+}
+
+/// The endpoint to upload crash reports to
+///
+/// `paste_url` takes precedence over the saved setting, and mclo.gs is used if neither is set
+/// or the saved setting is invalid.
+fn paste_url(paste_url: Option<Url>) -> Url {
+    if let Some(url) = paste_url {
+        return url;
+    }
+
+    let saved = META.lock().paste_url.clone();
+    if let Some(saved) = saved {
+        match parse_paste_url(&saved) {
+            Ok(url) => return url,
+            Err(e) => warn!(?e, saved, "Invalid saved paste URL, using the default"),
+        }
+    }
+
+    Url::parse(DEFAULT_PASTE_URL).expect("default paste URL is valid")
+}
+
+/// The newest crash report of an instance, or `None` if the server hasn't written any
+fn latest_crash_report(instance_path: &Path) -> Result<Option<PathBuf>> {
+    let crash_reports = instance_path.join("crash-reports");
+    if !crash_reports.is_dir() {
+        return Ok(None);
+    }
+
+    Ok(std::fs::read_dir(crash_reports)
+        .wrap_err("Failed to read crash reports directory")?
+        .filter_map(Result::ok)
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path))
+}
+
+/// Uploads a crash report to an mclogs-compatible paste service
+#[instrument(err, ret(level = "debug"))]
+async fn upload_crash_report(report: &Path, paste_url: &Url) -> Result<()> {
+    debug!("Uploading crash report");
+    let content = std::fs::read_to_string(report).wrap_err("Failed to read crash report")?;
+
+    let response = REQWEST_CLIENT
+        .post(paste_url.clone())
+        .form(&[("content", content)])
+        .send()
+        .await?;
+
+    // parse json response, which is shaped like mclo.gs's unless another service was configured
+    let response: serde_json::Value = response.error_for_status()?.json().await?;
+    let field = |key: &str| {
+        response
+            .get(key)
+            .ok_or_else(|| eyre!("Unexpected response from {paste_url}, with no `{key}`"))
+    };
+    let success = field("success")?
+        .as_bool()
+        .ok_or_else(|| eyre!("Unexpected response from {paste_url}, `success` is not a boolean"))?;
+
+    if success {
+        let url = field("url")?
+            .as_str()
+            .ok_or_else(|| eyre!("Unexpected response from {paste_url}, `url` is not a string"))?;
+        println!("Crash report uploaded to {url}");
+        debug!(url, "Crash report uploaded");
+        Ok(())
+    } else {
+        let error = field("error")?;
+        Err(eyre!(
+            "Failed to upload crash report: {}",
+            error
+                .as_str()
+                .map_or_else(|| error.to_string(), ToString::to_string)
+        ))
+    }
+}
+
+/// Prints a summary of a batch operation run with `--keep-going`,
+/// returning an error if any part of it failed
+fn finish_batch(
+    action: &str,
+    succeeded: &[String],
+    failed: &[(String, eyre::Report)],
+) -> Result<()> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+    if !succeeded.is_empty() {
+        println!("Succeeded: {}", succeeded.join(", "));
+    }
+    println!("Failed:");
+    for (target, e) in failed {
+        error!(item = target.as_str(), "{e:?}");
+        println!("  {target}: {e:#}");
+    }
+
+    Err(eyre!(
+        "{} of {} {action} operations failed",
+        failed.len(),
+        failed.len() + succeeded.len()
+    ))
+}
+
+/// Installs the JRE for an instance if it is missing, or if it was installed for another
+/// architecture (e.g. the data directory was copied from another machine) and the user agrees
+async fn ensure_jre(jre: &JreSpec, id: &str) -> Result<()> {
+    let key = jre.key();
+    let installed_arch = META!().jre_arch(&key).map(ToString::to_string);
+
+    if !META!().jre_installed(&key) {
+        debug!(jre = %jre, "Installing JRE due to config change");
+    } else if let Some(arch) = installed_arch
+        && arch != jre.target_arch()
+    {
+        let message = format!(
+            "JRE {jre} was installed for {arch}, but this machine is {}",
+            std::env::consts::ARCH
+        );
+        warn!("{message}");
+
+        // emulation (e.g. Rosetta) may still run it, so only reinstall when asked to
+        let reinstall = std::io::stdin().is_terminal()
+            && Confirm::new()
+                .with_prompt(format!("{message}. Reinstall it?"))
+                .default(true)
+                .interact()?;
+        if !reinstall {
+            eprintln!("Warning: {message}, so it may not run");
+            return Ok(());
+        }
+
+        let path = JRE_BASE_DIR.join(&key);
+        info!(?path, "Removing JRE for another architecture");
+        std::fs::remove_dir_all(&path)
+            .wrap_err(format!("Failed to remove directory {}", path.display()))?;
+        META!().remove_jre(&key);
+        META!().save()?;
+    } else {
+        return Ok(());
+    }
+
+    let pb = spinner(format!("JRE {jre} for {id}"));
+    install_jre(jre, &pb, false).await
+}
+
+/// Makes sure the Minecraft EULA has been accepted for an instance,
+/// since the server would exit right away otherwise
+fn check_eula(instance_path: &Path) -> Result<()> {
+    let path = instance_path.join("eula.txt");
+    let accepted = std::fs::read_to_string(&path).is_ok_and(|eula| {
+        eula.lines()
+            .any(|line| line.trim().eq_ignore_ascii_case("eula=true"))
+    });
+
+    if accepted {
+        return Ok(());
+    }
+    Err(eyre!(
+        "The Minecraft EULA has not been accepted. Read it at {EULA_URL}, \
+         then set `eula=true` in {} to accept it",
+        path.display()
+    ))
+}
+
+/// Backs up the world of an instance, keeping `keep` backups,
+/// or the number from its settings if `None`
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn backup_world(name: &str, keep: Option<NonZeroUsize>) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let keep = if let Some(keep) = keep {
+        keep.get()
+    } else {
+        let settings_path = settings_path(&META.lock(), &id);
+        InstanceSettings::from_file(&settings_path)
+            .await?
+            .backup
+            .keep
+    };
+
+    match backup_instance(&id, keep).await? {
+        Some(path) => status!("Backed up world to {}", path.display()),
+        None => status!("Instance {id} has no world to back up yet"),
+    }
+
+    Ok(())
+}
+
+/// Archives the world directories of an instance into a timestamped zip,
+/// keeping at most `keep` backups
+///
+/// Returns `None` if the instance doesn't have a world yet
+#[instrument(err, ret(level = "debug"))]
+async fn backup_instance(id: &str, keep: usize) -> Result<Option<PathBuf>> {
+    let instance_path = instance_dir(&META.lock(), id);
+
+    let worlds = std::fs::read_dir(&instance_path)
+        .wrap_err(format!(
+            "Failed to read instance directory {}",
+            instance_path.display()
+        ))?
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name().to_string_lossy().starts_with("world") && e.path().is_dir())
+        .map(|e| e.path())
+        .collect_vec();
+
+    if worlds.is_empty() {
+        return Ok(None);
+    }
+
+    let backup_dir = BACKUP_BASE_DIR.join(id);
+    let backup_path = backup_path(&backup_dir, &Utc::now().format("%Y%m%d-%H%M%S").to_string());
+    fs::create_dir_all(&backup_dir).await.wrap_err(format!(
+        "Failed to create backup directory {}",
+        backup_dir.display()
+    ))?;
+
+    // region files are already compressed, so the backup is about as large as the worlds
+    let needed: u64 = worlds.iter().map(|world| path_size(world)).sum();
+    if let Some(available) = available_space(&backup_dir)
+        && available < needed
+    {
+        return Err(eyre!(
+            "Not enough disk space to back up {id}: its worlds take {}, but only {} is free in {}",
+            HumanBytes(needed),
+            HumanBytes(available),
+            backup_dir.display()
+        ));
+    }
+
+    // large worlds take a while to compress
+    let out = backup_path.clone();
+    tokio::task::spawn_blocking(move || zip_dirs(&instance_path, &worlds, &out))
+        .await?
+        .wrap_err("Failed to write backup")?;
+    info!(path = %backup_path.display(), "Created backup");
+
+    let mut meta = META.lock();
+    let instance = meta
+        .instances
+        .get_mut(id)
+        .ok_or_else(|| McdlError::InstanceNotFound(id.to_string()))?;
+    instance.backups.push(backup_path.clone());
+
+    while instance.backups.len() > keep {
+        let old = instance.backups.remove(0);
+        if old.exists() {
+            info!(path = %old.display(), "Removing old backup");
+            std::fs::remove_file(&old)
+                .wrap_err(format!("Failed to remove old backup {}", old.display()))?;
+        }
+    }
+    meta.save()?;
+
+    Ok(Some(backup_path))
+}
+
+/// The space available to unprivileged users on the file system `path` is on
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // the field types differ between platforms
+fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is NUL-terminated, and `stat` is only read if the call succeeds
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: initialized by the successful call above
+    let stat = unsafe { stat.assume_init() };
+    Some(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+}
+
+/// Free space can't be checked on this platform, so backups are always attempted
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// A path for a new backup named after `stamp` in `backup_dir`,
+/// with a counter added if a backup was already made at the same time
+fn backup_path(backup_dir: &Path, stamp: &str) -> PathBuf {
+    let mut path = backup_dir.join(format!("{stamp}.zip"));
+    let mut n = 1;
+    while path.exists() {
+        path = backup_dir.join(format!("{stamp}-{n}.zip"));
+        n += 1;
+    }
+    path
+}
+
+/// Prints the Java versions Adoptium has JREs for, along with the installed JREs for each
+///
+/// Installed versions Adoptium no longer lists are included too.
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn list_jres() -> Result<()> {
+    let releases = get_available_jre_releases().await?;
+
+    let meta = META.lock();
+    let installed_majors = meta
+        .installed_jres
+        .iter()
+        .filter_map(|key| key.split('-').next()?.parse::<u8>().ok());
+    let majors = releases
+        .available_releases
+        .iter()
+        .copied()
+        .chain(installed_majors)
+        .sorted()
+        .dedup()
+        .collect_vec();
+
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
+    table.set_titles(row![b => "Version", "LTS", "Installed"]);
+
+    for major in majors {
+        let lts = if releases.available_lts_releases.contains(&major) {
+            "yes"
+        } else {
+            ""
+        };
+        let installed = meta.jres_for_major(major).join(", ");
+        let installed = if installed.is_empty() {
+            installed
+        } else {
+            installed.green().to_string()
+        };
+        table.add_row(row![major, lts, installed]);
+    }
+    drop(meta);
+
+    table.printstd();
+    Ok(())
+}
+
+/// Installs a JRE without installing a server that uses it
+#[instrument(err, ret(level = "debug"), skip(jre), fields(jre = %jre))]
+pub(crate) async fn install_standalone_jre(jre: &JreSpec) -> Result<()> {
+    let key = jre.key();
+    if META!().jre_installed(&key) {
+        status!("JRE {jre} is already installed");
+    } else {
+        let pb = spinner(format!("JRE {jre}"));
+        install_jre(jre, &pb, false).await?;
+    }
+
+    // keep it around even if no instance uses it
+    if META!().add_standalone_jre(&key) {
+        META!().save()?;
+    }
+    Ok(())
+}
+
+/// Removes an installed JRE
+///
+/// Refuses to remove a JRE an instance runs with, unless `force` is set.
+#[instrument(err, ret(level = "debug"), skip(jre), fields(jre = %jre))]
+pub(crate) async fn uninstall_jre(jre: &JreSpec, force: bool) -> Result<()> {
+    let key = jre.key();
+    if !META!().jre_installed(&key) {
+        return Err(eyre!("JRE {jre} is not installed"));
+    }
+
+    let instances = META!()
+        .instances
+        .iter()
+        .map(|(key, instance)| (key.clone(), instance.jre))
+        .collect_vec();
+    let mut users = vec![];
+    for (instance, installed_jre) in instances {
+        if instance_jre_key(&instance, installed_jre).await == key {
+            users.push(instance);
+        }
+    }
+    if !users.is_empty() && !force {
+        return Err(eyre!(
+            "JRE {jre} is used by {}, use --force to remove it anyway",
+            users.iter().sorted().join(", ")
+        ));
+    }
+
+    let pb = spinner(format!("JRE {jre}"));
+    let path = JRE_BASE_DIR.join(&key);
+    if path.exists() {
+        pb.set_message("Removing files...");
+        info!(?path, "Removing JRE");
+        std::fs::remove_dir_all(&path)
+            .wrap_err(format!("Failed to remove directory {}", path.display()))?;
+    }
+
+    pb.set_message("Updating metadata...");
+    META!().remove_jre(&key);
+    META!().save()?;
+
+    pb.finish_with_message("Done!");
+    Ok(())
+}
+
+/// Writes an instance directory and its settings to a zip archive at `out`,
+/// which [`import_instance`] can install elsewhere
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn export_instance(name: &str, out: &Path) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let instance_path = instance_dir(&META.lock(), &id);
+
+    // the world could change while it is being read
+    if let Some(pid) = running_pid(&instance_path)? {
+        return Err(eyre!(
+            "Instance {id} is running (PID {pid}), stop it before exporting"
+        ));
+    }
+
+    let manifest = {
+        let meta = META.lock();
+        let instance = meta
+            .instances
+            .get(&id)
+            .ok_or_else(|| McdlError::InstanceNotFound(id.clone()))?;
+        ExportManifest {
+            name: id.clone(),
+            version: instance.id.clone(),
+            jre: instance.jre,
+        }
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+
+    let settings_path = settings_path(&META.lock(), &id);
+    let settings = fs::read(&settings_path).await.wrap_err(format!(
+        "Failed to read settings file {}",
+        settings_path.display()
+    ))?;
+
+    let pb = spinner(id.clone());
+    pb.set_message("Writing archive...");
+    let (out_path, id_in_archive) = (out.to_path_buf(), id.clone());
+    tokio::task::spawn_blocking(move || {
+        zip_dir_as(
+            &instance_path,
+            &id_in_archive,
+            &[
+                (ExportManifest::FILE_NAME, &manifest),
+                (ExportManifest::SETTINGS_FILE_NAME, &settings),
+            ],
+            &out_path,
+        )
+    })
+    .await?
+    .wrap_err("Failed to write archive")?;
+    pb.finish_with_message("Done!");
+
+    status!("Exported {id} to {}", out.display());
+    Ok(())
+}
+
+/// Installs an instance from an archive written by [`export_instance`],
+/// as `name` if given or else under the key it was exported from
+///
+/// The JRE it needs is installed if missing.
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn import_instance(archive: &Path, name: Option<String>) -> Result<()> {
+    let path = archive.to_path_buf();
+    let (manifest, settings) = tokio::task::spawn_blocking(move || {
+        Ok::<_, eyre::Report>((
+            read_zip_file(&path, ExportManifest::FILE_NAME)?,
+            read_zip_file(&path, ExportManifest::SETTINGS_FILE_NAME)?,
+        ))
+    })
+    .await?
+    .wrap_err(format!(
+        "{} is not an archive written by `mcdl export`",
+        archive.display()
+    ))?;
+    let mut manifest: ExportManifest = serde_json::from_slice(&manifest)?;
+
+    // the archive may not have been written by mcdl, and the names end up in paths
+    manifest.name = parse_instance_name(&manifest.name).wrap_err(format!(
+        "{} has an invalid instance name",
+        archive.display()
+    ))?;
+    let key = match name {
+        Some(name) => parse_instance_name(&name)?,
+        None => manifest.name.clone(),
+    };
+    let instance_dir = INSTANCE_BASE_DIR.join(&key);
+    if META.lock().instance_installed(&key) || instance_dir.exists() {
+        return Err(eyre!(
+            "Instance {key} already exists, use --name to import it under another name"
+        ));
+    }
+
+    let pb = spinner(key.clone());
+    let settings_path = INSTANCE_SETTINGS_BASE_DIR.join(format!("{key}.toml"));
+    let imported = async {
+        pb.set_message("Extracting archive...");
+        let (path, prefix, dest) = (
+            archive.to_path_buf(),
+            manifest.name.clone(),
+            instance_dir.clone(),
+        );
+        tokio::task::spawn_blocking(move || unzip_dir(&path, &prefix, &dest))
+            .await?
+            .wrap_err(format!("Failed to extract {}", archive.display()))?;
+
+        pb.set_message("Writing settings...");
+        fs::create_dir_all(&*INSTANCE_SETTINGS_BASE_DIR).await?;
+        fs::write(&settings_path, settings).await.wrap_err(format!(
+            "Failed to write settings file {}",
+            settings_path.display()
+        ))?;
+        let settings = InstanceSettings::from_file(&settings_path).await?;
+
+        pb.set_message("Updating metadata...");
+        let mut instance_meta = InstanceMeta::new(manifest.version.clone(), manifest.jre);
+        if key != manifest.version.to_string() {
+            instance_meta.name = Some(key.clone());
+        }
+        instance_meta.add_file(&instance_dir);
+        instance_meta.add_file(&settings_path);
+        let mut meta = META.lock();
+        meta.add_instance(instance_meta);
+        if let Err(e) = meta.save() {
+            meta.remove_instance(&key);
+            return Err(e);
+        }
+        Ok(settings)
+    }
+    .await;
+    // a partial import would keep the same import from being retried
+    let settings = match imported {
+        Ok(settings) => settings,
+        Err(e) => {
+            pb.finish_with_message("Failed");
+            let _ = fs::remove_dir_all(&instance_dir).await;
+            let _ = fs::remove_file(&settings_path).await;
+            return Err(e);
+        }
+    };
+    pb.finish_with_message("Done!");
+
+    ensure_jre(&settings.java.jre_spec(), &key).await?;
+
+    status!("Imported {key} ({})", manifest.version);
+    Ok(())
+}
+
+/// Renames an instance, moving its directory, settings, and backups along with it
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn rename_instance(name: &str, new_name: &str) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let instance_path = instance_dir(&META.lock(), &id);
+    // instances installed with `--output-dir` stay where they are, settings and all
+    let located = META
+        .lock()
+        .instances
+        .get(&id)
+        .is_some_and(|i| i.location.is_some());
+
+    if META!().instance_installed(new_name) {
+        return Err(eyre!("An instance named {new_name} already exists"));
+    }
+    if let Some(pid) = running_pid(&instance_path)? {
+        return Err(eyre!(
+            "Instance {id} is running (PID {pid}), stop it before renaming"
+        ));
+    }
+
+    let mut moves = vec![(BACKUP_BASE_DIR.join(&id), BACKUP_BASE_DIR.join(new_name))];
+    if !located {
+        moves.extend([
+            (instance_path, INSTANCE_BASE_DIR.join(new_name)),
+            (
+                INSTANCE_SETTINGS_BASE_DIR.join(format!("{id}.toml")),
+                INSTANCE_SETTINGS_BASE_DIR.join(format!("{new_name}.toml")),
+            ),
+        ]);
+    }
+    // e.g. the backups left behind by uninstalling an instance of the same name
+    if let Some((_, to)) = moves.iter().find(|(_, to)| to.exists()) {
+        return Err(eyre!(
+            "Cannot rename {id} to {new_name}, {} already exists",
+            to.display()
+        ));
+    }
+    // nothing is recorded until everything has moved, so a failure can be undone
+    let mut done = vec![];
+    for (from, to) in moves {
+        // instances without backups have no backup directory
+        if !from.exists() {
+            continue;
+        }
+        if let Err(e) = fs::rename(&from, &to).await {
+            undo_moves(&done).await;
+            return Err(e).wrap_err(format!(
+                "Failed to move {} to {}",
+                from.display(),
+                to.display()
+            ));
+        }
+        done.push((from, to));
+    }
+
+    let saved = (|| {
+        let mut meta = META.lock();
+        let mut instance = meta
+            .remove_instance(&id)
+            .ok_or_else(|| McdlError::InstanceNotFound(id.clone()))?;
+        let old_name = instance.name.replace(new_name.to_string());
+        for (from, to) in &done {
+            instance.replace_path_prefix(from, to);
+        }
+        meta.add_instance(instance);
+
+        let saved = meta.save();
+        if saved.is_err() {
+            let mut instance = meta.remove_instance(new_name).expect("infallible");
+            instance.name = old_name;
+            for (from, to) in &done {
+                instance.replace_path_prefix(to, from);
+            }
+            meta.add_instance(instance);
+        }
+        saved
+    })();
+    if let Err(e) = saved {
+        undo_moves(&done).await;
+        return Err(e);
+    }
+
+    status!("Renamed {id} to {new_name}");
+    Ok(())
+}
+
+/// Moves renamed files back, most recent first, after a rename failed partway
+async fn undo_moves(moved: &[(PathBuf, PathBuf)]) {
+    for (from, to) in moved.iter().rev() {
+        if let Err(e) = fs::rename(to, from).await {
+            warn!(?e, ?from, ?to, "Failed to move back renamed files");
+        }
+    }
+}
+
+/// Resolves an instance by name or version, see [`AppMeta::resolve_instance`]
+pub(crate) fn resolve_instance(name: &str) -> Result<String> {
+    let meta = META.lock();
+    meta.resolve_instance(name).ok_or_else(|| {
+        if meta.instances.values().any(|i| i.id.to_string() == name) {
+            eyre!("Several instances use version {name}, use the instance name instead")
+        } else {
+            McdlError::InstanceNotFound(name.to_string()).into()
+        }
+    })
+}
+
+/// Hardlinks `dest` to a store entry, falling back to a copy if the
+/// link fails (e.g. the store is on a different filesystem)
+///
+/// A linked `dest` shares its contents with the entry and every other instance linked to it.
+#[instrument(err, ret(level = "debug"))]
+async fn link_or_copy(store_path: &Path, dest: &Path) -> Result<()> {
+    if !store_path.is_file() {
+        return Err(eyre!("Store entry {} is missing", store_path.display()));
+    }
+
+    if dest.exists() {
+        fs::remove_file(dest).await?;
+    }
+
+    if let Err(e) = fs::hard_link(store_path, dest).await {
+        debug!(?e, "Failed to hardlink store entry, copying instead");
+        fs::copy(store_path, dest).await?;
+    }
+
+    Ok(())
+}
+
+/// Starts a server that outlives mcdl, recording its PID in the instance directory
+fn spawn_detached(mut command: Command, instance_path: &Path) -> Result<()> {
+    // it could never be stopped with `mcdl stop`
+    if cfg!(not(any(unix, windows))) {
+        return Err(eyre!(
+            "Running servers in the background is not supported on this platform"
+        ));
+    }
+
+    let log_path = instance_path.join(SERVER_LOG_FILE);
+    let log = std::fs::File::create(&log_path)
+        .wrap_err(format!("Failed to create {}", log_path.display()))?;
+    command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    let child = command.spawn()?;
+    let pid = child
+        .id()
+        .ok_or_else(|| eyre!("Server exited before its PID could be read"))?;
+    // the start time tells the server apart from a process that gets its PID after it exits
+    let started = process_start_time(pid);
+    if started.is_none() {
+        warn!(pid, "Could not read the start time of the server");
+    }
+    std::fs::write(
+        instance_path.join(SERVER_PID_FILE),
+        format!("{pid}\n{}", started.unwrap_or_default()),
+    )
+    .wrap_err("Failed to write PID file")?;
+    info!(pid, "Started server in the background");
+
+    status!("Server started in the background (PID {pid})");
+    status!("Logging to {}", log_path.display());
+    Ok(())
+}
+
+/// The PID of an instance's background server, removing the PID file if the server is gone
+///
+/// The PID file holds the PID and start time of the server, and is stale
+/// if no process with that PID is running, or it started at a different time.
+fn running_pid(instance_path: &Path) -> Result<Option<u32>> {
+    let pid_path = instance_path.join(SERVER_PID_FILE);
+    let Ok(contents) = std::fs::read_to_string(&pid_path) else {
+        return Ok(None);
+    };
+
+    let mut lines = contents.lines();
+    let pid = lines.next().and_then(|pid| pid.trim().parse().ok());
+    let started = lines.next().map(str::trim);
+    match pid {
+        Some(pid) if started.is_some() && process_start_time(pid).as_deref() == started => {
+            Ok(Some(pid))
+        }
+        _ => {
+            debug!(path = ?pid_path, "Removing stale PID file");
+            std::fs::remove_file(&pid_path).wrap_err("Failed to remove stale PID file")?;
+            Ok(None)
+        }
+    }
+}
+
+/// When a running process started, in clock ticks since boot
+#[cfg(target_os = "linux")]
+fn process_start_time(pid: u32) -> Option<String> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the start time is the 22nd field, and the command name in the 2nd may hold spaces
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19).map(str::to_string)
+}
+
+/// When a running process started, as reported by `ps`
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_start_time(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let started = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !started.is_empty()).then_some(started)
+}
+
+/// When a running process was created, as a `FILETIME`
+#[cfg(windows)]
+fn process_start_time(pid: u32) -> Option<String> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    // SAFETY: opening a process handle has no memory safety requirements
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if handle.is_null() {
+        // processes of other users can't be opened, and aren't servers mcdl started
+        return None;
+    }
+
+    // SAFETY: the handle was opened above, and is closed exactly once
+    unsafe {
+        let started = open_process_start_time(handle);
+        CloseHandle(handle);
+        started
+    }
+}
+
+/// When the process behind `handle` was created, as a `FILETIME`, if it is still running
+///
+/// # Safety
+///
+/// `handle` must be an open process handle with at least `PROCESS_QUERY_LIMITED_INFORMATION` access.
+#[cfg(windows)]
+unsafe fn open_process_start_time(
+    handle: windows_sys::Win32::Foundation::HANDLE,
+) -> Option<String> {
+    use windows_sys::Win32::Foundation::{FILETIME, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, GetProcessTimes};
+
+    let mut exit_code = 0;
+    let empty = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut created, mut exited, mut kernel, mut user) = (empty, empty, empty, empty);
+    // SAFETY: the caller guarantees the handle is open
+    let running = unsafe {
+        GetExitCodeProcess(handle, &raw mut exit_code) != 0
+            && exit_code == STILL_ACTIVE.cast_unsigned()
+            && GetProcessTimes(
+                handle,
+                &raw mut created,
+                &raw mut exited,
+                &raw mut kernel,
+                &raw mut user,
+            ) != 0
+    };
+    running.then(|| {
+        ((u64::from(created.dwHighDateTime) << 32) | u64::from(created.dwLowDateTime)).to_string()
+    })
+}
+
+/// Processes can't be identified on this platform, so every PID file is stale
+#[cfg(not(any(unix, windows)))]
+fn process_start_time(_pid: u32) -> Option<String> {
+    None
+}
+
+/// Asks a background server to save and exit with `SIGTERM`, or kills it with `SIGKILL` if `force` is set
+#[cfg(unix)]
+fn signal_server(pid: u32, _started: Option<&str>, force: bool) -> std::io::Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(std::io::Error::other)?;
+    let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+    // SAFETY: sending a signal has no memory safety requirements
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Terminates a background server if it is still the process that started at `started`
+///
+/// Windows has no way to ask a process without a console to exit, so it is always terminated.
+#[cfg(windows)]
+fn signal_server(pid: u32, started: Option<&str>, _force: bool) -> std::io::Result<()> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_TERMINATE, TerminateProcess,
+    };
+
+    // SAFETY: opening a process handle has no memory safety requirements
+    let handle = unsafe {
+        OpenProcess(
+            PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_TERMINATE,
+            0,
+            pid,
+        )
+    };
+    if handle.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // SAFETY: the handle was opened above, and is closed exactly once
+    unsafe {
+        // checked through the same handle, so the PID can't be reused in between
+        let result = if started.is_none() || open_process_start_time(handle).as_deref() != started {
+            Ok(())
+        } else if TerminateProcess(handle, 1) == 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        CloseHandle(handle);
+        result
+    }
+}
+
+/// Stops an instance's background server, asking it to save and exit with `SIGTERM`
+/// and killing it if it does not exit within [`SERVER_STOP_TIMEOUT`]
+///
+/// On Windows the server is terminated right away, see [`signal_server`].
+#[cfg(any(unix, windows))]
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn stop_instance(name: &str) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let instance_path = instance_dir(&META.lock(), &id);
+
+    let had_pid_file = instance_path.join(SERVER_PID_FILE).exists();
+    let Some(pid) = running_pid(&instance_path)? else {
+        if had_pid_file {
+            status!("Instance {id} is no longer running (removed stale PID file)");
+        } else {
+            status!("Instance {id} is not running in the background");
+        }
+        return Ok(());
+    };
+    let started = process_start_time(pid);
+
+    status!("Stopping server (PID {pid})...");
+    signal_server(pid, started.as_deref(), false).wrap_err(format!("Failed to stop PID {pid}"))?;
+
+    let stopped = tokio::time::timeout(SERVER_STOP_TIMEOUT, async {
+        while started.is_some() && process_start_time(pid) == started {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    })
+    .await
+    .is_ok();
+    if !stopped {
+        warn!(pid, "Server did not stop in time, killing it");
+        if let Err(e) = signal_server(pid, started.as_deref(), true) {
+            warn!(?e, pid, "Failed to kill server");
+        }
+    }
+
+    std::fs::remove_file(instance_path.join(SERVER_PID_FILE))
+        .wrap_err("Failed to remove PID file")?;
+
+    if stopped {
+        status!("Server stopped");
+        Ok(())
+    } else {
+        Err(eyre!(
+            "Server did not stop within {SERVER_STOP_TIMEOUT:?} and was killed"
+        ))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn stop_instance(_name: &str) -> Result<()> {
+    Err(eyre!(
+        "Stopping background servers is not supported on this platform"
+    ))
+}
+
+/// How a server run ended
+enum ServerExit {
+    /// The server exited on its own
+    Exited(ExitStatus),
+    /// The server was stopped after running for the maximum runtime
+    MaxRuntime(ExitStatus),
+    /// The server was stopped with Ctrl-C
+    Interrupted(ExitStatus),
+}
+
+/// Waits for a server to exit, stopping it once `max_runtime` has passed or on Ctrl-C
+async fn wait_for_server(
+    child: &mut Child,
+    console: &ServerConsole,
+    max_runtime: Option<Duration>,
+) -> Result<ServerExit> {
+    let deadline = async {
+        match max_runtime {
+            Some(max_runtime) => tokio::time::sleep(max_runtime).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        status = child.wait() => {
+            Ok(ServerExit::Exited(status.wrap_err("Failed to wait for server")?))
+        }
+        () = deadline => {
+            info!(?max_runtime, "Maximum runtime reached, stopping server");
+            Ok(ServerExit::MaxRuntime(stop_server(child, console).await?))
+        }
+        result = tokio::signal::ctrl_c() => {
+            result.wrap_err("Failed to listen for Ctrl-C")?;
+            info!("Interrupted, stopping server");
+            status!("Stopping server, press Ctrl-C again to kill it...");
+            Ok(ServerExit::Interrupted(stop_server(child, console).await?))
+        }
+    }
+}
+
+/// Asks a running server to stop by sending `stop` to its console,
+/// killing it if it does not exit within [`SERVER_STOP_TIMEOUT`] or on Ctrl-C
+#[instrument(err, ret(level = "debug"), skip(child, console))]
+async fn stop_server(child: &mut Child, console: &ServerConsole) -> Result<ExitStatus> {
+    console.send("stop");
+
+    tokio::select! {
+        result = tokio::time::timeout(SERVER_STOP_TIMEOUT, child.wait()) => {
+            if let Ok(status) = result {
+                return status.wrap_err("Failed to wait for server");
+            }
+
+            warn!("Server did not stop in time, killing it");
+            child.kill().await.wrap_err("Failed to kill server")?;
+
+            Err(eyre!(
+                "Server did not stop within {SERVER_STOP_TIMEOUT:?} and was killed"
+            ))
+        }
+        _ = tokio::signal::ctrl_c() => {
+            warn!("Interrupted again, killing server");
+            child.kill().await.wrap_err("Failed to kill server")?;
+
+            Err(eyre!("Server was killed before it could stop"))
+        }
+    }
+}
+
+/// A server's console, fed both by this process's stdin and by mcdl itself
+///
+/// Stdin stops being forwarded once this is dropped.
+struct ServerConsole {
+    sender: mpsc::UnboundedSender<String>,
+    forwarding: Arc<AtomicBool>,
+}
+
+impl ServerConsole {
+    fn attach(mut stdin: ChildStdin) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            while let Some(line) = receiver.recv().await {
+                if let Err(e) = stdin.write_all(line.as_bytes()).await {
+                    // the server may have exited on its own in the meantime
+                    warn!(?e, "Failed to write to server console");
+                    break;
+                }
+            }
+        });
+
+        let forwarding = Arc::new(AtomicBool::new(true));
+        std::thread::spawn({
+            let sender = sender.clone();
+            let forwarding = forwarding.clone();
+            move || forward_stdin(&sender, &forwarding)
+        });
+
+        Self { sender, forwarding }
+    }
+
+    fn send(&self, command: &str) {
+        debug!(command, "Sending console command");
+        if self.sender.send(format!("{command}\n")).is_err() {
+            warn!(command, "Server console is closed");
+        }
+    }
+}
+
+impl Drop for ServerConsole {
+    fn drop(&mut self) {
+        self.forwarding.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Copies the output of a server to stdout, sending `commands` to its console
+/// once it reports that it has started
+///
+/// If `capture` is the instance, each line is also logged with it.
+async fn exec_when_ready(
+    stdout: ChildStdout,
+    console: mpsc::UnboundedSender<String>,
+    commands: Vec<String>,
+    capture: Option<String>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    // with only `--capture-output`, there is nothing to wait for the server to be ready for
+    let mut commands = (!commands.is_empty()).then_some(commands);
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        println!("{line}");
+        if let Some(instance) = &capture {
+            info!(target: SERVER_OUTPUT_TARGET, instance, "{line}");
+        }
+
+        if is_ready_line(&line)
+            && let Some(commands) = commands.take()
+        {
+            info!(count = commands.len(), "Server is ready, sending commands");
+            for command in commands {
+                debug!(command, "Sending console command");
+                if console.send(format!("{command}\n")).is_err() {
+                    warn!(command, "Server console is closed");
+                }
+            }
+        }
+    }
+}
+
+/// Copies the error output of a server to stderr, logging each line as a warning
+async fn log_stderr(stderr: ChildStderr, instance: String) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        eprintln!("{line}");
+        warn!(target: SERVER_OUTPUT_TARGET, instance, "{line}");
+    }
+}
+
+/// Whether a line of server output is the one logged once it has started,
+/// e.g. `[12:00:00] [Server thread/INFO]: Done (3.14s)! For help, type "help"`
+fn is_ready_line(line: &str) -> bool {
+    line.contains("]: Done (")
+}
+
+/// Copies lines from stdin to the server console while `forwarding` is set
+///
+/// Stdin is polled rather than read directly, so that the thread notices when it should stop
+/// instead of blocking on a read and swallowing input meant for a later prompt
+#[cfg(unix)]
+fn forward_stdin(console: &mpsc::UnboundedSender<String>, forwarding: &AtomicBool) {
+    use std::os::fd::AsRawFd;
+
+    let stdin = std::io::stdin();
+    let mut fd = libc::pollfd {
+        fd: stdin.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    while forwarding.load(Ordering::Relaxed) {
+        // SAFETY: `fd` is a single, valid `pollfd`
+        let ready = unsafe { libc::poll(&raw mut fd, 1, 100) };
+        if ready < 0 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return;
+        }
+        if ready <= 0 {
+            continue;
+        }
+
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if console.send(line).is_err() => return,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Copies lines from stdin to the server console while `forwarding` is set
+#[cfg(not(unix))]
+fn forward_stdin(console: &mpsc::UnboundedSender<String>, forwarding: &AtomicBool) {
+    for line in std::io::stdin().lines() {
+        let Ok(line) = line else { return };
+        if !forwarding.load(Ordering::Relaxed) || console.send(format!("{line}\n")).is_err() {
+            return;
+        }
+    }
+}
+
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn gc(dry_run: bool) -> Result<()> {
+    let categories = [
+        ("JREs", unused_jres().await?),
+        ("Cache", expired_cache_files()),
+        ("Logs", old_logs(log_retention())),
+        ("Store", unreferenced_store_entries()),
+    ];
+
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
+    table.set_titles(row![b => "Category", "Items", "Size"]);
+
+    let mut total = 0;
+    for (category, paths) in categories {
+        let mut size = 0;
+        for path in &paths {
+            size += path_size(path);
+
+            if dry_run {
+                continue;
+            }
+
+            info!(?path, category, "Removing");
+            if path.is_dir() {
+                std::fs::remove_dir_all(path)
+                    .wrap_err(format!("Failed to remove directory {}", path.display()))?;
+            } else {
+                std::fs::remove_file(path)
+                    .wrap_err(format!("Failed to remove file {}", path.display()))?;
+            }
+
+            if category == "JREs" {
+                let key = path.file_name().expect("infallible").to_string_lossy();
+                META!().remove_jre(&key);
+                META!().save()?;
+            }
+        }
+
+        table.add_row(row![category, paths.len(), HumanBytes(size)]);
+        total += size;
+    }
+
+    table.printstd();
+    if dry_run {
+        println!("Would reclaim {} (dry run)", HumanBytes(total));
+    } else {
+        status!("Reclaimed {}", HumanBytes(total));
+    }
+
+    Ok(())
+}
+
+/// Brings the metadata in line with the instance and JRE directories on disk
+///
+/// Tracked files that no longer exist are forgotten, as are instances and JREs whose directory
+/// is gone. Untracked instance directories named after a version in `manifest` are registered,
+/// after asking unless `yes` is set.
+#[instrument(err, ret(level = "debug"), skip(manifest))]
+pub(crate) async fn sync(manifest: &GameVersionList, yes: bool) -> Result<()> {
+    let mut changes = forget_missing()?;
+
+    let untracked = std::fs::read_dir(INSTANCE_BASE_DIR.as_path())
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|path| path.is_dir())
+                .collect_vec()
+        })
+        .unwrap_or_default();
+
+    for dir in untracked {
+        let key = dir
+            .file_name()
+            .expect("infallible")
+            .to_string_lossy()
+            .to_string();
+        if META.lock().instance_installed(&key) {
+            continue;
+        }
+
+        let Some(version) = manifest.versions.iter().find(|v| v.id.to_string() == key) else {
+            status!(
+                "Skipping untracked directory {}, its name isn't a version",
+                dir.display()
+            );
+            continue;
+        };
+
+        let register = if yes {
+            true
+        } else if std::io::stdin().is_terminal() {
+            Confirm::new()
+                .with_prompt(format!("Register untracked instance {key}?"))
+                .default(true)
+                .interact()?
+        } else {
+            println!("Found untracked instance {key}, use --yes to register it");
+            false
+        };
+        if register {
+            register_instance(&dir, version).await?;
+            status!("Registered instance {key}");
+            changes += 1;
+        }
+    }
+
+    if changes == 0 {
+        status!("Everything is in sync");
+    }
+    Ok(())
+}
+
+/// Removes the instances, files, and JREs that no longer exist on disk from the metadata,
+/// returning how many were removed
+fn forget_missing() -> Result<usize> {
+    let mut changes = 0;
+    let mut meta = META.lock();
+
+    let gone = meta
+        .instances
+        .keys()
+        .filter(|key| !instance_dir(&meta, key).is_dir())
+        .cloned()
+        .collect_vec();
+    for key in gone {
+        if let Some(instance) = meta.remove_instance(&key) {
+            release_store_entries(&mut meta, &instance.store_entries)?;
+            status!("Removed instance {key}, its directory no longer exists");
+            changes += 1;
+        }
+    }
+
+    for instance in meta.instances.values_mut() {
+        let missing = instance.missing_files().into_iter().cloned().collect_vec();
+        for file in missing {
+            instance.remove_file(&file);
+            status!(
+                "Forgot missing file {} of {}",
+                file.display(),
+                instance.key()
+            );
+            changes += 1;
+        }
+    }
+
+    let gone = meta
+        .installed_jres
+        .iter()
+        .filter(|key| !JRE_BASE_DIR.join(key).is_dir())
+        .cloned()
+        .collect_vec();
+    for key in gone {
+        meta.remove_jre(&key);
+        status!("Removed JRE {key}, its directory no longer exists");
+        changes += 1;
+    }
+
+    meta.save()?;
+    Ok(changes)
+}
+
+/// Adds an instance directory that isn't in the metadata, writing default settings
+/// for its version if it has none
+async fn register_instance(dir: &Path, version: &GameVersion) -> Result<()> {
+    let key = version.id.to_string();
+    let settings_path = INSTANCE_SETTINGS_BASE_DIR.join(format!("{key}.toml"));
+
+    let jre = if settings_path.exists() {
+        InstanceSettings::from_file(&settings_path)
+            .await?
+            .java
+            .version
+    } else {
+        let jre = get_version_metadata(version)
+            .await?
+            .java_version
+            .major_version;
+        InstanceSettings::new(jre).save(&settings_path).await?;
+        jre
+    };
+
+    let mut instance_meta = InstanceMeta::new(version.id.clone(), jre);
+    instance_meta.add_file(dir);
+    instance_meta.add_file(&settings_path);
+
+    let mut meta = META.lock();
+    meta.add_instance(instance_meta);
+    meta.save()
+}
+
+#[instrument(err, ret(level = "debug"))]
+pub(crate) fn clean_cache(all: bool) -> Result<()> {
+    let paths = if all {
+        std::fs::read_dir(CACHE_BASE_DIR.as_path()).map_or(vec![], |entries| {
+            entries.filter_map(Result::ok).map(|e| e.path()).collect()
+        })
+    } else {
+        expired_cache_files()
+    };
+
+    let mut freed = 0;
+    for path in &paths {
+        freed += path_size(path);
+
+        info!(?path, "Removing cache entry");
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)
+                .wrap_err(format!("Failed to remove directory {}", path.display()))?;
+        } else {
+            std::fs::remove_file(path)
+                .wrap_err(format!("Failed to remove file {}", path.display()))?;
+        }
+    }
+
+    status!(
+        "Removed {} cache {}, freeing {}",
+        paths.len(),
+        if paths.len() == 1 { "entry" } else { "entries" },
+        HumanBytes(freed)
+    );
+
+    Ok(())
+}
+
+/// Removes log files older than `max_age`, except `current`, returning how many were removed
+pub(crate) fn prune_logs(max_age: Duration, current: &Path) -> usize {
+    old_logs(max_age)
+        .into_iter()
+        .filter(|path| path != current)
+        .filter(|path| std::fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// How long log files are kept for
+///
+/// `MCDL_LOG_RETENTION_DAYS` takes precedence over the retention saved with
+/// `mcdl clean --set-log-retention`, which takes precedence over the default of a week.
+pub(crate) fn log_retention() -> Duration {
+    const DEFAULT_LOG_RETENTION_DAYS: u64 = 7;
+
+    let days = std::env::var("MCDL_LOG_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .or_else(|| META.lock().log_retention_days)
+        .unwrap_or(DEFAULT_LOG_RETENTION_DAYS);
+    Duration::from_secs(days.saturating_mul(24 * 60 * 60))
+}
+
+/// JRE directories that no installed instance uses and that weren't installed on their own
+async fn unused_jres() -> Result<Vec<PathBuf>> {
+    let instances = META!()
+        .instances
+        .iter()
+        .map(|(key, instance)| (key.clone(), instance.jre))
+        .collect_vec();
+
+    // instances can be configured to use a different JRE than the one they were installed with
+    let mut used = META!().standalone_jres.clone();
+    for (key, jre) in instances {
+        used.insert(instance_jre_key(&key, jre).await);
+    }
+
+    let Ok(entries) = std::fs::read_dir(JRE_BASE_DIR.as_path()) else {
+        return Ok(vec![]);
+    };
+
+    Ok(entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|path| {
+            let key = path.file_name().expect("infallible").to_string_lossy();
+            path.is_dir() && !used.contains(key.as_ref())
+        })
+        .collect())
+}
 
-    pb.set_message("Checking if instance exists...");
-    if let Some(instance) = META!().instances.get(&id.to_string()) {
-        instance_files.extend(instance.files.clone());
-    } else {
-        return Err(eyre!("Instance `{id}` does not exist"));
+/// The key of the JRE an instance runs with
+///
+/// Falls back to the JRE it was installed with if its settings can't be read.
+async fn instance_jre_key(key: &str, installed_jre: u8) -> String {
+    let path = settings_path(&META.lock(), key);
+    match InstanceSettings::from_file(&path).await {
+        Ok(settings) => settings.java.jre_spec().key(),
+        Err(e) => {
+            warn!(
+                ?e,
+                instance = key,
+                "Failed to read settings, assuming installed JRE"
+            );
+            JreSpec::new(installed_jre).key()
+        }
     }
+}
 
-    pb.set_message("Removing files...");
-    for path in &instance_files {
-        if !path.exists() {
-            warn!(?path, "File does not exist");
-            continue;
-        }
+/// Cached responses that have expired
+fn expired_cache_files() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(CACHE_BASE_DIR.as_path()) else {
+        return vec![];
+    };
 
-        if path.is_dir() {
-            info!(?path, "Removing directory");
-            std::fs::remove_dir_all(path)
-                .wrap_err(format!("Failed to remove directory {}", path.display()))?;
-        } else {
-            info!(?path, "Removing file");
-            std::fs::remove_file(path)
-                .wrap_err(format!("Failed to remove file {}", path.display()))?;
-        }
+    entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "mpk"))
+        .filter(|path| CacheHeader::from_file(path).is_ok_and(|header| header.is_expired()))
+        .collect()
+}
 
-        META!()
-            .instances
-            .get_mut(&id.to_string())
-            .unwrap()
-            .remove_file(path);
-        META!().save()?;
-    }
+/// Log files older than `max_age`
+fn old_logs(max_age: Duration) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(LOG_BASE_DIR.as_path()) else {
+        return vec![];
+    };
 
-    pb.set_message("Updating metadata...");
-    META!().remove_instance(&id.to_string());
-    META!().save()?;
+    entries
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "log"))
+        .filter(|e| {
+            e.metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > max_age))
+        })
+        .map(|e| e.path())
+        .collect()
+}
 
-    // bonus: remove jre if it's not used by any other instances
+/// Store entries that no instance links to
+fn unreferenced_store_entries() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(STORE_BASE_DIR.as_path()) else {
+        return vec![];
+    };
 
-    pb.finish_with_message("Done!");
-    Ok(())
+    let meta = META.lock();
+    entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|path| {
+            let hash = path.file_name().expect("infallible").to_string_lossy();
+            !meta.store_refs.contains_key(hash.as_ref())
+        })
+        .collect()
 }
 
-#[instrument(err, ret(level = "debug"), skip(id))]
-pub(crate) async fn run_instance(id: VersionNumber) -> Result<()> {
-    let instance_path = INSTANCE_BASE_DIR.join(id.to_string());
+/// The total size of a file, or of everything in a directory
+pub(crate) fn path_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
 
-    if !META!().instance_installed(&id.to_string()) {
-        return Err(eyre!("Instance `{id}` does not exist"));
+    if !metadata.is_dir() {
+        return metadata.len();
     }
 
-    let settings =
-        InstanceSettings::from_file(INSTANCE_SETTINGS_BASE_DIR.join(format!("{id}.toml"))).await?;
-    debug!(?settings, "Loaded instance settings");
+    std::fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .map(|e| path_size(&e.path()))
+            .sum()
+    })
+}
 
-    // check if the JRE is installed and install it if not
-    let jre_version = settings.java.version;
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn show_config(name: &str, json: bool) -> Result<()> {
+    let id = resolve_instance(name)?;
 
-    if !META!().jre_installed(jre_version) {
-        debug!(jre = jre_version, "Installing JRE due to config change");
-        let pb = ProgressBar::new_spinner()
-            .with_style(PB_STYLE.clone())
-            .with_prefix(format!("JRE {jre_version} for {id}"));
-        pb.enable_steady_tick(Duration::from_millis(100));
+    let settings_path = settings_path(&META.lock(), &id);
+    let settings = InstanceSettings::from_file(settings_path).await?;
 
-        install_jre(jre_version, &pb).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&settings)?);
+    } else {
+        print!("{}", toml::to_string_pretty(&settings)?);
     }
 
-    // make sure JRE version is correct
-    META!()
-        .instances
-        .get_mut(&id.to_string())
-        .ok_or_else(|| eyre!("Instance metadata not found for {id}"))?
-        .jre = jre_version;
-    META!().save()?;
+    Ok(())
+}
 
-    // add all arguments
-    let mut args: Vec<OsString> = vec![];
-    args.extend(settings.java.args.iter().map(Into::into)); // jvm args
-    args.extend(vec!["-jar".into(), settings.server.jar.into()]); // server jar
-    args.extend(settings.server.args.iter().map(Into::into)); // server args
+/// Writes `server-port` to the `server.properties` of an instance, unless it is already set to `port`
+async fn set_server_port(instance_path: &Path, port: u16) -> Result<()> {
+    let path = instance_path.join("server.properties");
+    let mut properties = ServerProperties::from_file(&path).await?;
 
-    let args_string = args
-        .iter()
-        .map(|s| shell_escape::escape(s.to_str().unwrap().into()))
-        .join(" ");
+    let port = port.to_string();
+    if properties.get("server-port").as_deref() != Some(port.as_str()) {
+        debug!(port, "Setting server port");
+        properties.set("server-port", &port);
+        properties.save(&path).await?;
+    }
 
-    let java_path = get_java_path(jre_version);
+    Ok(())
+}
 
-    debug!(
-        "Starting server with command line: {java} {args}",
-        java = java_path.display(),
-        args = args_string
-    );
-    let mut child = Command::new(&java_path)
-        .current_dir(&instance_path)
-        .kill_on_drop(true)
-        .args(&args)
-        .spawn()
-        .wrap_err(format!(
-            "Failed to start server with command line: {java} {args}",
-            java = java_path.display(),
-            args = args_string
-        ))?;
-    info!("Started server");
+/// Prints the value of a key in the `server.properties` of an instance
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn get_property(name: &str, key: &str) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let path = instance_dir(&META.lock(), &id).join("server.properties");
 
-    let status = child.wait().await.wrap_err("Failed to wait for server")?;
-    if !status.success() {
-        error!(?status, "Server exited with an error");
-        let upload = Confirm::new()
-            .with_prompt("Server exited with an error. Would you like to upload the crash report?")
-            .default(false)
-            .interact()?;
+    let value = ServerProperties::from_file(&path)
+        .await?
+        .get(key)
+        .ok_or_else(|| eyre!("`{key}` is not set in {}", path.display()))?;
+    println!("{value}");
 
-        if upload {
-            debug!("Uploading crash report");
-            let crash_reports = instance_path.join("crash-reports");
+    Ok(())
+}
 
-            let latest = std::fs::read_dir(crash_reports)
-                .wrap_err("Failed to read crash reports directory")?
-                .filter_map(Result::ok)
-                .max_by(|a, b| {
-                    let a = a.metadata().unwrap().modified().unwrap();
-                    let b = b.metadata().unwrap().modified().unwrap();
-
-                    a.cmp(&b)
-                })
-                .ok_or_else(|| eyre!("No crash reports found"))?;
-
-            let content =
-                std::fs::read_to_string(latest.path()).wrap_err("Failed to read crash report")?;
-
-            // upload to mclo.gs
-            let response = REQWEST_CLIENT
-                .post("https://api.mclo.gs/1/log")
-                .form(&[("content", content)])
-                .send()
-                .await?;
-
-            // parse json response
-            let response: serde_json::Value = response.json().await?;
-
-            if response["success"].as_bool().unwrap() {
-                println!(
-                    "Crash report uploaded to {}",
-                    response["url"].as_str().unwrap()
-                );
-                debug!(
-                    url = response["url"].as_str().unwrap(),
-                    "Crash report uploaded"
-                );
-            } else {
-                return Err(eyre!(
-                    "Failed to upload crash report: {}",
-                    response["error"].as_str().unwrap()
-                ));
-            }
-        }
+/// Sets a key in the `server.properties` of an instance
+///
+/// If the server hasn't been run yet, the file is created with just that key,
+/// and the server fills in the defaults for the rest when it starts.
+#[instrument(err, ret(level = "debug"))]
+pub(crate) async fn set_property(name: &str, key: &str, value: &str) -> Result<()> {
+    let id = resolve_instance(name)?;
+    let path = instance_dir(&META.lock(), &id).join("server.properties");
 
-        return Err(eyre!(
-            "Server exited with {status}. Command line: {java} {args}",
-            java = java_path.display(),
-            args = args_string
-        ));
+    let mut properties = ServerProperties::from_file(&path).await?;
+    properties.set(key, value);
+    properties.save(&path).await?;
+    info!(key, value, "Set server property");
+
+    status!("Set {key}={value} for {id}");
+    if running_pid(&instance_dir(&META.lock(), &id))?.is_some() {
+        println!("The server is running, restart it for the change to apply");
     }
 
     Ok(())
@@ -408,35 +3268,93 @@ pub(crate) async fn run_instance(id: VersionNumber) -> Result<()> {
 
 #[instrument(err, ret(level = "debug"))]
 pub(crate) fn locate(what: &String) -> Result<()> {
-    match what.to_ascii_lowercase().as_str() {
-        "java" => {
-            println!("JRE base directory: {}", JRE_BASE_DIR.display());
-        }
-        "instance" => {
-            println!("Instance base directory: {}", INSTANCE_BASE_DIR.display());
-        }
-        "config" => {
-            println!(
-                "Instance settings base directory: {}",
-                INSTANCE_SETTINGS_BASE_DIR.display()
-            );
+    let what = what.to_ascii_lowercase();
+    let locations = [
+        ("java", "JRE base directory", JRE_BASE_DIR.as_path()),
+        (
+            "instance",
+            "Instance base directory",
+            INSTANCE_BASE_DIR.as_path(),
+        ),
+        (
+            "config",
+            "Instance settings base directory",
+            INSTANCE_SETTINGS_BASE_DIR.as_path(),
+        ),
+        ("log", "Log base directory", LOG_BASE_DIR.as_path()),
+        ("cache", "Cache base directory", CACHE_BASE_DIR.as_path()),
+    ];
+
+    if what == "all" {
+        let mut table = Table::new();
+        table.set_format(FormatBuilder::new().padding(0, 2).build());
+        for (_, label, path) in locations {
+            table.add_row(row![format!("{}:", label.bold()), path.display()]);
         }
-        "log" => {
-            println!("Log base directory: {}", LOG_BASE_DIR.display());
+        table.printstd();
+        return Ok(());
+    }
+
+    let (_, label, path) = locations
+        .into_iter()
+        .find(|(name, ..)| *name == what)
+        .ok_or_else(|| eyre!("Unknown location: {what}"))?;
+    println!("{label}: {}", path.display());
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"), skip(script))]
+pub(crate) fn install_completions(shell: Shell, script: &[u8]) -> Result<PathBuf> {
+    let base_dirs = BaseDirs::new().ok_or_else(|| eyre!("Failed to get home directory"))?;
+    let home = base_dirs.home_dir();
+    let xdg_dir = |var: &str, fallback: &str| {
+        std::env::var_os(var)
+            .filter(|dir| !dir.is_empty())
+            .map_or_else(|| home.join(fallback), PathBuf::from)
+    };
+
+    let name = env!("CARGO_PKG_NAME");
+    let path = match shell {
+        Shell::Bash => xdg_dir("XDG_DATA_HOME", ".local/share")
+            .join("bash-completion")
+            .join("completions")
+            .join(name),
+        Shell::Zsh => xdg_dir("ZDOTDIR", "")
+            .join(".zfunc")
+            .join(format!("_{name}")),
+        Shell::Fish => xdg_dir("XDG_CONFIG_HOME", ".config")
+            .join("fish")
+            .join("completions")
+            .join(format!("{name}.fish")),
+        shell => {
+            return Err(eyre!(
+                "Installing completions is not supported for {shell}, redirect the output of `{name} completions {shell}` instead"
+            ));
         }
-        _ => {
-            return Err(eyre!("Unknown location: {what}"));
+    };
+
+    let dir = path.parent().expect("infallible");
+    if let Err(e) = std::fs::create_dir_all(dir).and_then(|()| std::fs::write(&path, script)) {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            return Err(eyre!(
+                "Cannot write to {} (permission denied), redirect the output of `{name} completions {shell}` instead",
+                dir.display()
+            ));
         }
+
+        return Err(e).wrap_err(format!("Failed to write completions to {}", path.display()));
     }
 
-    Ok(())
+    info!(path = %path.display(), "Installed completions");
+    Ok(path)
 }
 
 // platform specific stuff
 
 #[cfg(windows)]
 #[instrument(err, ret(level = "debug"), skip_all, fields(path = %jre_dir.as_ref().display()))]
-fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>) -> Result<()> {
+fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>, pb: Option<&ProgressBar>) -> Result<()> {
     use std::io::{BufReader, Cursor};
 
     use zip::ZipArchive;
@@ -452,7 +3370,12 @@ fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>) -> Result<()> {
     let reader: BufReader<Cursor<Vec<u8>>> = BufReader::new(Cursor::new(jre.into()));
     let mut archive = ZipArchive::new(reader)?;
 
+    let pb = pb.cloned().unwrap_or_else(ProgressBar::hidden);
+    pb.set_length(archive.len() as u64);
+    pb.set_position(0);
+
     for i in 0..archive.len() {
+        pb.inc(1);
         let mut entry = archive.by_index(i)?;
         let filepath = entry.enclosed_name().ok_or(eyre!("Invalid file path"))?;
 
@@ -484,17 +3407,24 @@ fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>) -> Result<()> {
     Ok(())
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 #[instrument(err, ret(level = "debug"), skip_all, fields(path = %jre_dir.as_ref().display()))]
-fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>) -> Result<()> {
+fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>, pb: Option<&ProgressBar>) -> Result<()> {
     use std::os::unix::fs::PermissionsExt;
 
     use bytes::Buf;
     use flate2::read::GzDecoder;
     use tar::Archive;
 
-    let mut reader = jre.reader();
-    let mut archive = Archive::new(GzDecoder::new(&mut reader));
+    // tarballs have no index, so the entries are counted by reading through it once first
+    let count = Archive::new(GzDecoder::new(jre.clone().reader()))
+        .entries()?
+        .try_fold(0, |count, entry| entry.map(|_| count + 1))?;
+    let pb = pb.cloned().unwrap_or_else(ProgressBar::hidden);
+    pb.set_length(count);
+    pb.set_position(0);
+
+    let mut archive = Archive::new(GzDecoder::new(jre.reader()));
     let entries = archive.entries()?;
     let jre_dir = jre_dir.as_ref();
 
@@ -503,13 +3433,30 @@ fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>) -> Result<()> {
         path = jre_dir.display()
     ))?;
 
+    // macOS builds are bundles, with the JRE itself under `Contents/Home`
+    let home = if cfg!(target_os = "macos") {
+        Path::new("Contents/Home")
+    } else {
+        Path::new("")
+    };
+
     for entry in entries {
+        pb.inc(1);
         let mut entry = entry?;
-        let filepath = entry.path()?;
 
         // strip the first directory
-        let outpath = jre_dir.join(filepath.components().skip(1).collect::<PathBuf>());
+        let filepath = entry.path()?.components().skip(1).collect::<PathBuf>();
+
+        // skip the rest of the bundle
+        let Ok(filepath) = filepath.strip_prefix(home) else {
+            continue;
+        };
+        let outpath = jre_dir.join(filepath);
 
+        // archives don't always list directories before their contents
+        if let Some(parent) = outpath.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         entry.unpack(outpath)?;
     }
 
@@ -529,57 +3476,646 @@ fn extract_jre(jre: Bytes, jre_dir: impl AsRef<Path>) -> Result<()> {
     Ok(())
 }
 
-#[cfg(not(any(windows, target_os = "linux")))]
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
 #[instrument(err, ret(level = "debug"), skip(_jre))]
-fn extract_jre(_jre: Bytes, _jre_dir: &PathBuf) -> Result<()> {
-    Err(eyre!("Unsupported OS")) // TODO fail gracefully
+fn extract_jre(_jre: Bytes, _jre_dir: &PathBuf, _pb: Option<&ProgressBar>) -> Result<()> {
+    Err(McdlError::UnsupportedOs.into()) // TODO fail gracefully
+}
+
+/// Resolves a command to an executable, searching `PATH` if it isn't a path itself
+fn find_executable(command: &str) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| executable_candidates(&dir, command))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The paths `command` may be at in `dir`, with the platform's executable suffix first
+///
+/// The suffix is appended rather than set as the extension, so names with dots like
+/// `python3.12` are kept whole.
+fn executable_candidates(dir: &Path, command: &str) -> [PathBuf; 2] {
+    [
+        dir.join(format!("{command}{}", std::env::consts::EXE_SUFFIX)),
+        dir.join(command),
+    ]
 }
 
-#[instrument(ret(level = "debug"))]
-fn get_java_path(version: u8) -> PathBuf {
+#[instrument(ret(level = "debug"), skip(jre), fields(jre = %jre))]
+fn get_java_path(jre: &JreSpec) -> PathBuf {
     JRE_BASE_DIR
-        .join(version.to_string())
+        .join(jre.key())
         .join("bin")
         .join(format!("java{}", std::env::consts::EXE_SUFFIX))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::SystemTime;
+
     use super::*;
+    use crate::types::net::CachedResponse;
 
     #[tokio::test]
-    #[cfg(not(target_os = "macos"))]
     async fn test_install_jre() {
         let version = match std::env::consts::OS {
             "macos" => 11, // Adoptium doesn't have JRE 8 for aarch64 macOS
             _ => 8,
         };
+        let jre = JreSpec::new(version);
 
         // remove the jre directory if the test panics
         scopeguard::defer! {
-            let path = JRE_BASE_DIR.join(version.to_string());
+            let path = JRE_BASE_DIR.join(JreSpec::new(version).key());
 
             if path.exists() {
                 std::fs::remove_dir_all(path).unwrap();
             }
 
-            META!().remove_jre(version);
+            META!().remove_jre(&JreSpec::new(version).key());
             META!().save().unwrap();
         }
 
         assert!(
-            !META!().jre_installed(version),
+            !META!().jre_installed(&jre.key()),
             "JRE 8 is already installed"
         );
 
-        install_jre(version, &ProgressBar::hidden()).await.unwrap();
+        install_jre(&jre, &ProgressBar::hidden(), false)
+            .await
+            .unwrap();
 
         assert!(
-            get_java_path(version).exists(),
+            get_java_path(&jre).exists(),
             "{:?} does not exist",
-            get_java_path(version)
+            get_java_path(&jre)
         );
-        assert!(META!().remove_jre(version), "Failed to remove JRE");
+        assert!(META!().remove_jre(&jre.key()), "Failed to remove JRE");
         assert!(META!().save().is_ok(), "Failed to save metadata");
     }
+
+    #[test]
+    fn test_find_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wrapper");
+        std::fs::write(&path, "").unwrap();
+
+        assert_eq!(find_executable(path.to_str().unwrap()), Some(path));
+        assert_eq!(find_executable("mcdl-definitely-not-a-command"), None);
+    }
+
+    #[test]
+    fn test_launch_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let wrapper = dir.path().join("wrapper");
+        std::fs::write(&wrapper, "").unwrap();
+
+        let mut settings = InstanceSettings::new(17);
+        let (program, args) = launch_command(&settings).unwrap();
+        assert_eq!(program, get_java_path(&JreSpec::new(17)));
+        assert_eq!(args, ["-Xms4G", "-Xmx4G", "-jar", "server.jar", "--nogui"]);
+
+        settings.server.launch_wrapper = vec![wrapper.to_str().unwrap().to_string(), "-n".into()];
+        let (program, args) = launch_command(&settings).unwrap();
+        assert_eq!(program, wrapper);
+        assert_eq!(
+            args[..2],
+            [
+                "-n".into(),
+                get_java_path(&JreSpec::new(17)).into_os_string()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_stop_server() {
+        let mut child = Command::new("sh")
+            .args(["-c", r#"read cmd && [ "$cmd" = stop ]"#])
+            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let console = ServerConsole::attach(child.stdin.take().unwrap());
+
+        let status = stop_server(&mut child, &console).await.unwrap();
+        assert!(status.success(), "server exited with {status}");
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_check_java() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let java = dir.path().join("java");
+        let write_java = |script: &str| {
+            std::fs::write(&java, format!("#!/bin/sh\n{script}\n")).unwrap();
+            std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        write_java(r#"echo 'openjdk version "17.0.8" 2023-07-18' >&2"#);
+        assert!(check_java(&java, 17).await.is_ok());
+        assert!(check_java(&java, 21).await.is_err());
+
+        write_java("exit 1");
+        assert!(check_java(&java, 17).await.is_err());
+    }
+
+    #[test]
+    fn test_is_ready_line() {
+        assert!(is_ready_line(
+            r#"[12:00:00] [Server thread/INFO]: Done (3.14s)! For help, type "help""#
+        ));
+        assert!(is_ready_line(
+            "[12:00:00] [Server thread/INFO] [minecraft/DedicatedServer]: Done (3.14s)!"
+        ));
+        assert!(!is_ready_line(
+            "[12:00:00] [Server thread/INFO]: Preparing level \"world\""
+        ));
+        assert!(!is_ready_line("<player> Done (not really)"));
+    }
+
+    #[test]
+    fn test_located_instance_files() {
+        let dir = Path::new("/srv/minecraft");
+        let files = located_instance_files(dir, Loader::Quilt);
+
+        assert!(
+            files
+                .iter()
+                .all(|path| path.starts_with(dir) && path != dir)
+        );
+        assert!(files.contains(&dir.join("server.jar")));
+        assert!(files.contains(&dir.join(OUTPUT_DIR_SETTINGS)));
+        assert!(files.contains(&dir.join(Quilt::LAUNCHER_JAR)));
+        assert!(
+            !located_instance_files(dir, Loader::Vanilla).contains(&dir.join(Quilt::LAUNCHER_JAR))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_import_instance() {
+        let key = "export-roundtrip";
+        let instance_path = INSTANCE_BASE_DIR.join(key);
+        std::fs::create_dir_all(instance_path.join("world")).unwrap();
+        std::fs::write(instance_path.join("world").join("level.dat"), "level").unwrap();
+        let settings_path = INSTANCE_SETTINGS_BASE_DIR.join(format!("{key}.toml"));
+        InstanceSettings::new(17)
+            .save(&settings_path)
+            .await
+            .unwrap();
+
+        let mut instance_meta = InstanceMeta::new("1.20.1".parse().unwrap(), 17);
+        instance_meta.name = Some(key.to_string());
+        instance_meta.add_file(&instance_path);
+        instance_meta.add_file(&settings_path);
+        {
+            let mut meta = META.lock();
+            meta.add_instance(instance_meta);
+            // importing installs the JRE if it's missing
+            let jre = JreSpec::new(17);
+            meta.add_jre(&jre.key(), jre.target_arch());
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("export.zip");
+        export_instance(key, &archive).await.unwrap();
+        import_instance(&archive, Some("export-roundtrip-copy".to_string()))
+            .await
+            .unwrap();
+
+        let imported = INSTANCE_BASE_DIR.join("export-roundtrip-copy");
+        assert_eq!(
+            std::fs::read_to_string(imported.join("world").join("level.dat")).unwrap(),
+            "level"
+        );
+        assert!(
+            INSTANCE_SETTINGS_BASE_DIR
+                .join("export-roundtrip-copy.toml")
+                .exists()
+        );
+        assert!(META.lock().instance_installed("export-roundtrip-copy"));
+    }
+
+    #[test]
+    fn test_executable_candidates() {
+        let [suffixed, bare] = executable_candidates(Path::new("/usr/bin"), "python3.12");
+        assert_eq!(
+            suffixed,
+            PathBuf::from(format!(
+                "/usr/bin/python3.12{}",
+                std::env::consts::EXE_SUFFIX
+            ))
+        );
+        assert_eq!(bare, PathBuf::from("/usr/bin/python3.12"));
+    }
+
+    #[test]
+    fn test_backup_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = backup_path(dir.path(), "20240101-000000");
+        assert_eq!(first, dir.path().join("20240101-000000.zip"));
+
+        std::fs::write(&first, "backup").unwrap();
+        let second = backup_path(dir.path(), "20240101-000000");
+        assert_eq!(second, dir.path().join("20240101-000000-1.zip"));
+    }
+
+    #[test]
+    fn test_prune_logs_keeps_current() {
+        std::fs::create_dir_all(LOG_BASE_DIR.as_path()).unwrap();
+        let old = LOG_BASE_DIR.join("mcdl-old.log");
+        let current = LOG_BASE_DIR.join("mcdl-current.log");
+        std::fs::write(&old, "old").unwrap();
+        std::fs::write(&current, "current").unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert!(prune_logs(Duration::ZERO, &current) >= 1);
+        assert!(!old.exists());
+        assert!(current.exists());
+    }
+
+    #[tokio::test]
+    async fn test_unused_jres_keeps_standalone() {
+        for key in ["98-unused", "99-standalone"] {
+            std::fs::create_dir_all(JRE_BASE_DIR.join(key)).unwrap();
+            META.lock().add_jre(key, "x86_64");
+        }
+        META.lock().add_standalone_jre("99-standalone");
+
+        let unused = unused_jres().await.unwrap();
+        assert!(unused.contains(&JRE_BASE_DIR.join("98-unused")));
+        assert!(!unused.contains(&JRE_BASE_DIR.join("99-standalone")));
+    }
+
+    #[tokio::test]
+    async fn test_update_then_verify() {
+        let key = "update-verify";
+        let instance_path = INSTANCE_BASE_DIR.join(key);
+        std::fs::create_dir_all(&instance_path).unwrap();
+        std::fs::write(instance_path.join("server.jar"), "old jar").unwrap();
+        std::fs::write(instance_path.join(CLIENT_JAR), "old client").unwrap();
+        let settings_path = INSTANCE_SETTINGS_BASE_DIR.join(format!("{key}.toml"));
+        InstanceSettings::new(17)
+            .save(&settings_path)
+            .await
+            .unwrap();
+
+        let mut instance_meta = InstanceMeta::new("1.20.1".parse().unwrap(), 17);
+        instance_meta.name = Some(key.to_string());
+        instance_meta.server_sha1 = Some(sha1_hex(b"old jar"));
+        instance_meta.client_sha1 = Some(sha1_hex(b"old client"));
+        instance_meta.add_file(&instance_path);
+        instance_meta.add_file(&settings_path);
+        META.lock().add_instance(instance_meta);
+
+        // the new jar is already in the store and its metadata cached, so nothing is downloaded
+        let (jar, client) = (b"new jar", b"new client");
+        let (sha1, client_sha1) = (sha1_hex(jar), sha1_hex(client));
+        std::fs::create_dir_all(STORE_BASE_DIR.as_path()).unwrap();
+        std::fs::write(STORE_BASE_DIR.join(&sha1), jar).unwrap();
+        std::fs::write(STORE_BASE_DIR.join(&client_sha1), client).unwrap();
+        let version_meta: VersionMetadata = serde_json::from_value(serde_json::json!({
+            "id": "1.20.2",
+            "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+            "downloads": {
+                "server": {"sha1": sha1, "size": jar.len(), "url": "http://localhost/server.jar"},
+                "client": {"sha1": client_sha1, "size": client.len(), "url": "http://localhost/client.jar"},
+            },
+        }))
+        .unwrap();
+        CachedResponse::new(&version_meta, SystemTime::now() + Duration::from_hours(1))
+            .save(CACHE_BASE_DIR.join("1.20.2.mpk"))
+            .await
+            .unwrap();
+        let target: GameVersion = serde_json::from_value(serde_json::json!({
+            "id": "1.20.2",
+            "type": "release",
+            "url": "http://localhost/1.20.2.json",
+            "time": "2023-09-20T00:00:00+00:00",
+            "releaseTime": "2023-09-20T00:00:00+00:00",
+        }))
+        .unwrap();
+
+        update_instance(key, &target).await.unwrap();
+        {
+            let meta = META.lock();
+            let instance = &meta.instances[key];
+            assert_eq!(instance.server_sha1.as_deref(), Some(sha1.as_str()));
+            assert_eq!(instance.client_sha1.as_deref(), Some(client_sha1.as_str()));
+            assert_eq!(instance.store_entries, [sha1, client_sha1]);
+        }
+        assert_eq!(
+            std::fs::read(instance_path.join(CLIENT_JAR)).unwrap(),
+            client
+        );
+        assert!(verify_instance(key, &target).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_import_invalid_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance_path = dir.path().join("instance");
+        std::fs::create_dir(&instance_path).unwrap();
+
+        for name in ["../escaped", "/tmp/escaped"] {
+            let manifest = serde_json::to_vec(&ExportManifest {
+                name: name.to_string(),
+                version: "1.20.1".parse().unwrap(),
+                jre: 17,
+            })
+            .unwrap();
+            let archive = dir.path().join("export.zip");
+            zip_dir_as(
+                &instance_path,
+                "instance",
+                &[
+                    (ExportManifest::FILE_NAME, &manifest),
+                    (ExportManifest::SETTINGS_FILE_NAME, b""),
+                ],
+                &archive,
+            )
+            .unwrap();
+
+            assert!(import_instance(&archive, None).await.is_err());
+            assert!(!META.lock().instance_installed(name));
+        }
+        assert!(!INSTANCE_BASE_DIR.join("../escaped").exists());
+        assert!(!INSTANCE_SETTINGS_BASE_DIR.join("../escaped.toml").exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_failure_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance_path = dir.path().join("instance");
+        std::fs::create_dir(&instance_path).unwrap();
+
+        let key = "import-invalid-settings";
+        let manifest = serde_json::to_vec(&ExportManifest {
+            name: key.to_string(),
+            version: "1.20.1".parse().unwrap(),
+            jre: 17,
+        })
+        .unwrap();
+        let archive = dir.path().join("export.zip");
+        zip_dir_as(
+            &instance_path,
+            key,
+            &[
+                (ExportManifest::FILE_NAME, &manifest),
+                (ExportManifest::SETTINGS_FILE_NAME, b"not settings"),
+            ],
+            &archive,
+        )
+        .unwrap();
+
+        // the settings are read after the world is extracted, so retrying fails the same way
+        for _ in 0..2 {
+            let err = import_instance(&archive, None).await.unwrap_err();
+            assert!(!err.to_string().contains("already exists"));
+        }
+        assert!(!INSTANCE_BASE_DIR.join(key).exists());
+        assert!(
+            !INSTANCE_SETTINGS_BASE_DIR
+                .join(format!("{key}.toml"))
+                .exists()
+        );
+        assert!(!META.lock().instance_installed(key));
+    }
+
+    #[tokio::test]
+    async fn test_rename_onto_leftover_backups() {
+        let (key, new_name) = ("rename-from", "rename-leftover");
+        let instance_path = INSTANCE_BASE_DIR.join(key);
+        std::fs::create_dir_all(&instance_path).unwrap();
+        let mut instance_meta = InstanceMeta::new("1.20.1".parse().unwrap(), 17);
+        instance_meta.name = Some(key.to_string());
+        instance_meta.add_file(&instance_path);
+        META.lock().add_instance(instance_meta);
+        std::fs::create_dir_all(BACKUP_BASE_DIR.join(new_name)).unwrap();
+
+        let err = rename_instance(key, new_name).await.unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        assert!(instance_path.exists());
+        assert!(META.lock().instance_installed(key));
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_removes_backups() {
+        let options = UninstallOptions {
+            keep_going: false,
+            keep_jre: true,
+            keep_backups: false,
+        };
+        for (key, keep_backups) in [("uninstall-backups", false), ("uninstall-keep", true)] {
+            let instance_path = INSTANCE_BASE_DIR.join(key);
+            std::fs::create_dir_all(&instance_path).unwrap();
+            let backup_path = BACKUP_BASE_DIR.join(key).join("world.zip");
+            std::fs::create_dir_all(backup_path.parent().unwrap()).unwrap();
+            std::fs::write(&backup_path, b"zip").unwrap();
+            let mut instance_meta = InstanceMeta::new("1.20.1".parse().unwrap(), 17);
+            instance_meta.name = Some(key.to_string());
+            instance_meta.add_file(&instance_path);
+            instance_meta.backups.push(backup_path.clone());
+            META.lock().add_instance(instance_meta);
+
+            uninstall_instance(
+                key,
+                UninstallOptions {
+                    keep_backups,
+                    ..options
+                },
+            )
+            .await
+            .unwrap();
+            assert!(!instance_path.exists());
+            assert_eq!(backup_path.exists(), keep_backups);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_crash_report_unexpected_response() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let paste_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 4096]).await.unwrap();
+            let body = r#"{"ok":true}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let report = dir.path().join("crash.txt");
+        std::fs::write(&report, "crash").unwrap();
+        let err = upload_crash_report(&report, &paste_url).await.unwrap_err();
+        assert!(err.to_string().contains("no `success`"), "{err}");
+    }
+
+    #[test]
+    fn test_resolve_missing_instance() {
+        let err = resolve_instance("no such instance").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<McdlError>(),
+            Some(McdlError::InstanceNotFound(name)) if name == "no such instance"
+        ));
+    }
+
+    #[test]
+    fn test_latest_crash_report() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(latest_crash_report(dir.path()).unwrap(), None);
+
+        let crash_reports = dir.path().join("crash-reports");
+        std::fs::create_dir(&crash_reports).unwrap();
+        assert_eq!(latest_crash_report(dir.path()).unwrap(), None);
+
+        let old = crash_reports.join("crash-old.txt");
+        let new = crash_reports.join("crash-new.txt");
+        std::fs::write(&old, "old").unwrap();
+        std::fs::write(&new, "new").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(latest_crash_report(dir.path()).unwrap(), Some(new));
+    }
+
+    #[tokio::test]
+    async fn test_undo_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::create_dir(&a).unwrap();
+        std::fs::write(a.join("server.jar"), b"jar").unwrap();
+
+        fs::rename(&a, &b).await.unwrap();
+        undo_moves(&[(a.clone(), b.clone())]).await;
+        assert!(a.join("server.jar").exists());
+        assert!(!b.exists());
+    }
+
+    #[tokio::test]
+    async fn test_link_missing_store_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let (entry, dest) = (dir.path().join("entry"), dir.path().join("server.jar"));
+        std::fs::write(&dest, b"old jar").unwrap();
+
+        assert!(link_or_copy(&entry, &dest).await.is_err());
+        // the old jar is only replaced once there is something to replace it with
+        assert!(dest.exists());
+
+        std::fs::write(&entry, b"jar").unwrap();
+        link_or_copy(&entry, &dest).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"jar");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_running_pid() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_path = dir.path().join(SERVER_PID_FILE);
+        assert_eq!(running_pid(dir.path()).unwrap(), None);
+
+        let pid = std::process::id();
+        let started = process_start_time(pid).unwrap();
+        std::fs::write(&pid_path, format!("{pid}\n{started}")).unwrap();
+        assert_eq!(running_pid(dir.path()).unwrap(), Some(pid));
+
+        // the PID was reused by another process
+        std::fs::write(&pid_path, format!("{pid}\nnot {started}")).unwrap();
+        assert_eq!(running_pid(dir.path()).unwrap(), None);
+        assert!(!pid_path.exists());
+
+        // without a start time, the process can't be told apart from another one
+        std::fs::write(&pid_path, pid.to_string()).unwrap();
+        assert_eq!(running_pid(dir.path()).unwrap(), None);
+
+        // a reaped child's PID is stale
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        std::fs::write(&pid_path, format!("{}\n{started}", child.id())).unwrap();
+        assert_eq!(running_pid(dir.path()).unwrap(), None);
+        assert!(!pid_path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_signal_server() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let started = process_start_time(child.id());
+        signal_server(child.id(), started.as_deref(), false).unwrap();
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn test_check_eula() {
+        let dir = tempfile::tempdir().unwrap();
+        let eula_path = dir.path().join("eula.txt");
+        assert!(check_eula(dir.path()).is_err());
+
+        std::fs::write(&eula_path, "eula=false").unwrap();
+        assert!(check_eula(dir.path()).is_err());
+
+        // the server writes a comment above the setting
+        std::fs::write(
+            &eula_path,
+            "#By changing the setting below to TRUE...\neula=TRUE\n",
+        )
+        .unwrap();
+        assert!(check_eula(dir.path()).is_ok());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn test_extract_jre() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let root = if cfg!(target_os = "macos") {
+            "jdk-17.0.8+7-jre/Contents/Home"
+        } else {
+            "jdk-17.0.8+7-jre"
+        };
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, contents) in [
+            (format!("{root}/bin/java"), "#!/bin/sh\n"),
+            (format!("{root}/release"), "JAVA_VERSION=\"17.0.8\"\n"),
+            ("jdk-17.0.8+7-jre/Contents/Info.plist".to_string(), ""),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let pb = ProgressBar::hidden();
+        extract_jre(archive.into(), dir.path(), Some(&pb)).unwrap();
+
+        assert_eq!((pb.position(), pb.length()), (3, Some(3)));
+        assert!(dir.path().join("bin").join("java").is_file());
+        assert!(dir.path().join("release").is_file());
+    }
 }
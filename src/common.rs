@@ -1,13 +1,19 @@
-use std::path::PathBuf;
-use std::sync::{Arc, LazyLock};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, OnceLock};
 use std::time::Duration;
 
+use color_eyre::eyre::Result;
 use directories::ProjectDirs;
+use indicatif::MultiProgress;
 use parking_lot::Mutex;
-use reqwest::Client;
 use reqwest::header::{self, HeaderMap};
+use reqwest::{Client, Proxy, Url};
 
 use crate::types::meta::AppMeta;
+use crate::types::net::Mirror;
+use crate::types::stats::TransferStat;
 
 pub static MCDL_VERSION: LazyLock<String> = LazyLock::new(|| {
     format!(
@@ -33,23 +39,167 @@ pub static REQWEST_CLIENT: LazyLock<Client> = LazyLock::new(|| {
         .expect("failed to build user agent header"),
     );
 
-    Client::builder()
+    // without an explicit proxy, reqwest reads HTTP_PROXY, HTTPS_PROXY, ALL_PROXY, and NO_PROXY
+    let mut builder = Client::builder()
         .default_headers(headers)
-        .tcp_keepalive(Some(Duration::from_secs(10)))
-        .build()
-        .expect("failed to build reqwest client")
+        .tcp_keepalive(Some(Duration::from_secs(10)));
+    if let Some(proxy) = PROXY.get() {
+        builder = builder.proxy(proxy.clone());
+    }
+
+    // a read timeout rather than a total one, so large downloads on slow connections still finish
+    let timeout = TIMEOUT.get().copied().unwrap_or(DEFAULT_TIMEOUT);
+    if !timeout.is_zero() {
+        builder = builder.connect_timeout(timeout).read_timeout(timeout);
+    }
+
+    builder.build().expect("failed to build reqwest client")
 });
 
+/// A proxy to use instead of the one from the environment
+///
+/// Must be set before [`REQWEST_CLIENT`] is first used.
+pub static PROXY: OnceLock<Proxy> = OnceLock::new();
+
+/// How long to wait for a connection or for more data before giving up on a request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// A timeout to use instead of [`DEFAULT_TIMEOUT`], where zero disables it
+///
+/// Must be set before [`REQWEST_CLIENT`] is first used.
+pub static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// A mirror of Mojang's metadata API to fetch the version manifest and metadata from
+///
+/// Always ends in a slash, like the URL it replaces.
+pub static MANIFEST_URL: OnceLock<Url> = OnceLock::new();
+
+/// A mirror to download server and client jars from instead of Mojang's servers
+pub static MIRROR: OnceLock<Mirror> = OnceLock::new();
+
+/// Whether to work from the cache only, without touching the network
+pub static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Whether to ignore cached responses, downloading fresh ones (which are still cached)
+pub static NO_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Whether to hide progress bars and decorative output
+pub static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Draws every progress bar, so other output can be printed without garbling them
+///
+/// Nothing may be logged from inside [`MultiProgress::suspend`], which would deadlock with `--verbose`.
+pub static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+#[cfg_attr(test, allow(dead_code))]
 pub static PROJ_DIRS: LazyLock<ProjectDirs> = LazyLock::new(|| {
     ProjectDirs::from("com.github", "ibsamsky", env!("CARGO_PKG_NAME"))
         .expect("failed to get project directories (no valid home dir)")
 });
 
-pub static LOG_BASE_DIR: LazyLock<PathBuf> =
-    LazyLock::new(|| PROJ_DIRS.data_local_dir().join("log"));
+/// A data directory to use instead of the platform default, from `--data-dir`
+///
+/// Must be set before any base directory is first used, like [`CONFIG_DIR`] and [`CACHE_DIR`].
+pub static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// A config directory to use instead of the platform default, from `--config-dir`
+pub static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// A cache directory to use instead of the platform default, from `--cache-dir`
+pub static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// A base directory from [`PROJ_DIRS`]
+#[cfg(not(test))]
+fn platform_dir(dir: fn(&ProjectDirs) -> &Path, _name: &str) -> PathBuf {
+    dir(&PROJ_DIRS).to_path_buf()
+}
+
+/// A directory named `name` in a temporary directory, so tests never touch the user's instances
+#[cfg(test)]
+fn platform_dir(_dir: fn(&ProjectDirs) -> &Path, name: &str) -> PathBuf {
+    static TEST_DIR: LazyLock<tempfile::TempDir> =
+        LazyLock::new(|| tempfile::tempdir().expect("failed to create test directory"));
+    TEST_DIR.path().join(name)
+}
+
+/// Where the metadata and logs are kept, [`DATA_DIR`] if it is set
+pub fn data_local_dir() -> PathBuf {
+    DATA_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| platform_dir(ProjectDirs::data_local_dir, "data"))
+}
+
+/// Where instance settings are kept, [`CONFIG_DIR`] if it is set
+pub fn config_local_dir() -> PathBuf {
+    CONFIG_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| platform_dir(ProjectDirs::config_local_dir, "config"))
+}
+
+/// Where responses and JRE archives are cached, [`CACHE_DIR`] if it is set
+pub fn cache_dir() -> PathBuf {
+    CACHE_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| platform_dir(ProjectDirs::cache_dir, "cache"))
+}
+
+/// The directory instances, JREs, server jars, and backups are stored in
+///
+/// `MCDL_DATA_DIR` overrides the platform default, e.g. to keep instances on a larger disk.
+/// Logs and the metadata stay in the platform default either way.
+/// `--data-dir` takes precedence over both, and moves everything.
+pub static DATA_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+    if let Some(dir) = DATA_DIR.get() {
+        return dir.clone();
+    }
+
+    std::env::var_os("MCDL_DATA_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(data_local_dir, PathBuf::from)
+});
+
+pub static LOG_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| data_local_dir().join("log"));
+
+/// Locks `meta.lock` next to the metadata while this process may change it,
+/// so concurrent runs don't overwrite each other's changes
+///
+/// Taken by [`lock_meta`], and released on exit or by [`release_meta_lock`].
+static META_LOCK: Mutex<Option<File>> = Mutex::new(None);
 
 pub static META: LazyLock<Arc<Mutex<AppMeta>>> = LazyLock::new(|| {
-    Arc::new(Mutex::new(AppMeta::read_or_create(
-        PROJ_DIRS.data_local_dir().join("meta.mpk").as_path(),
-    )))
+    // `main` takes the lock first so it can report errors, this only covers other callers
+    if let Err(e) = lock_meta() {
+        PROGRESS.suspend(|| eprintln!("Error: {e:?}"));
+        std::process::exit(1);
+    }
+
+    let path = data_local_dir().join("meta.mpk");
+    Arc::new(Mutex::new(AppMeta::read_or_create(path.as_path())))
 });
+
+/// Takes the metadata lock unless this process already holds it, waiting for other processes
+///
+/// Must be called before [`META`] is first used for errors to be returned instead of exiting.
+pub fn lock_meta() -> Result<()> {
+    let mut meta_lock = META_LOCK.lock();
+    if meta_lock.is_none() {
+        let lock = AppMeta::lock(data_local_dir().join("meta.lock"), || {
+            if !QUIET.load(Ordering::Relaxed) {
+                PROGRESS.suspend(|| eprintln!("Waiting for another mcdl process to finish..."));
+            }
+        })?;
+        *meta_lock = Some(lock);
+    }
+    Ok(())
+}
+
+/// Lets other processes use the metadata, once this one won't save it again
+pub fn release_meta_lock() {
+    META_LOCK.lock().take();
+}
+
+/// Timings of every download and extraction made by this process
+pub static TRANSFER_STATS: LazyLock<Mutex<Vec<TransferStat>>> = LazyLock::new(Mutex::default);
@@ -0,0 +1,97 @@
+use color_eyre::eyre::Report;
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use crate::types::version::VersionNumber;
+
+/// Errors callers may want to handle, rather than just report
+///
+/// These are still returned as [`eyre::Report`](color_eyre::eyre::Report)s,
+/// and can be recovered with `downcast_ref`
+#[derive(Debug, Error)]
+pub(crate) enum McdlError {
+    #[error("Version {0} not found in the version manifest")]
+    VersionNotFound(VersionNumber),
+    #[error("Instance `{0}` does not exist")]
+    InstanceNotFound(String),
+    #[error("Version {0} has no server jar")]
+    NoServerJar(VersionNumber),
+    #[error("Checksum mismatch for {file} (expected {expected}, got {actual})")]
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+    // only returned on platforms mcdl can't extract JREs on
+    #[cfg_attr(
+        any(windows, target_os = "linux", target_os = "macos"),
+        allow(dead_code)
+    )]
+    #[error("Unsupported OS")]
+    UnsupportedOs,
+    #[error("Adoptium has no JREs for the {0} architecture")]
+    UnsupportedArch(String),
+    #[error("Unexpected error downloading JRE (status code {status}): {url}")]
+    JreDownloadFailed { status: StatusCode, url: String },
+}
+
+impl McdlError {
+    /// The exit code mcdl exits with when this error ends it, see [`exit_code`]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::VersionNotFound(_) => 2,
+            Self::InstanceNotFound(_) => 3,
+            Self::JreDownloadFailed { .. } => 4,
+            Self::ChecksumMismatch { .. } => 5,
+            Self::NoServerJar(_) | Self::UnsupportedOs | Self::UnsupportedArch(_) => 1,
+        }
+    }
+}
+
+/// The exit code for an error that ends mcdl, so scripts can tell common failures apart
+///
+/// - 1: any other error
+/// - 2: a version doesn't exist (also used by clap for usage errors)
+/// - 3: an instance doesn't exist
+/// - 4: a network error, e.g. a timeout or an unexpected status code
+/// - 5: a downloaded file doesn't match its checksum
+///
+/// The outermost error with a known code wins.
+pub(crate) fn exit_code(report: &Report) -> i32 {
+    report
+        .chain()
+        .find_map(|e| {
+            if let Some(e) = e.downcast_ref::<McdlError>() {
+                Some(e.exit_code())
+            } else if e.is::<reqwest::Error>() {
+                Some(4)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::{WrapErr, eyre};
+
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let missing: Result<(), Report> =
+            Err(McdlError::InstanceNotFound("survival".into()).into());
+        let missing = missing.wrap_err("Error while running server").unwrap_err();
+        assert_eq!(exit_code(&missing), 3);
+
+        let mismatch = Report::from(McdlError::ChecksumMismatch {
+            file: "server.jar".into(),
+            expected: "a".into(),
+            actual: "b".into(),
+        });
+        assert_eq!(exit_code(&mismatch), 5);
+
+        assert_eq!(exit_code(&eyre!("Something else")), 1);
+    }
+}
@@ -3,46 +3,163 @@
 
 pub(crate) mod app;
 pub(crate) mod common;
+pub(crate) mod error;
 pub(crate) mod types;
 pub(crate) mod utils;
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::IsTerminal;
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
 
-use chrono::Utc;
-use clap::builder::NonEmptyStringValueParser;
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::builder::{BoolishValueParser, NonEmptyStringValueParser};
 use clap::error::ErrorKind;
 use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
-use color_eyre::eyre::{Result, WrapErr, eyre};
+use clap_complete::Shell;
+use color_eyre::eyre::{Report, Result, WrapErr, eyre};
 use color_eyre::owo_colors::OwoColorize;
 use derive_more::derive::Display;
+use dialoguer::{Confirm, MultiSelect};
+use indicatif::HumanBytes;
 use itertools::Itertools;
 use prettytable::format::FormatBuilder;
 use prettytable::{Cell, Row, Table, row};
-use tracing::{debug, info, instrument};
-
-use crate::common::{LOG_BASE_DIR, MCDL_VERSION, META, PROJ_DIRS};
+use reqwest::{Proxy, Url};
+use serde::Serialize;
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::app::{InstallOptions, RunOptions, UninstallOptions};
+use crate::common::{
+    CACHE_DIR, CONFIG_DIR, DATA_DIR, LOG_BASE_DIR, MANIFEST_URL, MCDL_VERSION, META, MIRROR,
+    NO_CACHE, OFFLINE, PROGRESS, PROXY, QUIET, TIMEOUT, lock_meta,
+};
+use crate::error::{McdlError, exit_code};
+use crate::types::flags::FlagPreset;
+use crate::types::jre::{Arch, HeapSize, ImageType, JreSpec, JvmImpl, Vendor};
+use crate::types::loader::Loader;
 use crate::types::meta::ToArgs;
-use crate::types::version::{GameVersionList, VersionNumber};
-use crate::utils::net::get_version_manifest;
+use crate::types::net::Mirror;
+use crate::types::version::{
+    GameVersion, GameVersionList, VersionDownload, VersionMetadata, VersionNumber,
+};
+use crate::utils::macros::status;
+use crate::utils::net::{
+    get_raw_version_metadata, get_version_manifest, get_version_metadata, read_local_manifest,
+};
+use crate::utils::parse::{
+    parse_duration, parse_instance_name, parse_jre_version, parse_manifest_url, parse_memory,
+    parse_paste_url, parse_proxy, parse_version_file, split_version_list,
+};
 
 static MANIFEST: OnceLock<GameVersionList> = OnceLock::new();
 
 /* cli */
 
+/// The exit codes from [`exit_code`], for `--help`
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  1  Any other error
+  2  A version doesn't exist, or the arguments are invalid
+  3  An instance doesn't exist
+  4  A network error, e.g. a timeout or an unexpected response
+  5  A downloaded file doesn't match its checksum";
+
 #[doc(hidden)]
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[command(author, version = MCDL_VERSION.as_str())]
 #[command(arg_required_else_help = true, subcommand_required = true)]
+#[command(after_long_help = EXIT_CODES_HELP)]
 /// A tool for managing Minecraft server versions
 struct Cli {
+    #[arg(long, global = true, value_name = "SOURCE")]
+    /// Read the version manifest from a local file instead of downloading it
+    ///
+    /// Accepts a path, a `file://` URL, or `-` to read from stdin.
+    /// Local manifests are never cached.
+    manifest: Option<String>,
+    #[arg(long, global = true, env = "MCDL_MANIFEST_URL", value_name = "URL")]
+    #[arg(value_parser = parse_manifest_url, conflicts_with = "manifest")]
+    /// Fetch the version manifest and metadata from a mirror of Mojang's API,
+    /// e.g. `https://bmclapi2.bangbang93.com/`
+    ///
+    /// Must end in a slash. Paths like `mc/game/version_manifest.json` are appended to it.
+    manifest_url: Option<Url>,
+    #[arg(
+        long,
+        global = true,
+        env = "MCDL_MIRROR",
+        value_enum,
+        value_name = "MIRROR"
+    )]
+    /// Download server and client jars from a third-party mirror of Mojang's servers
+    ///
+    /// `bmclapi` is BMCLAPI (`https://bmclapi2.bangbang93.com/`), which isn't run by Mojang.
+    /// Jars are still checked against the checksums in the version metadata, which is
+    /// fetched from Mojang unless `--manifest-url` points at the mirror too.
+    mirror: Option<Mirror>,
+    #[arg(long, global = true, env = "MCDL_OFFLINE", value_parser = BoolishValueParser::new())]
+    /// Never use the network, working from cached data only
+    ///
+    /// Cached data is used even if it has expired.
+    /// Anything that needs a download fails instead.
+    offline: bool,
+    #[arg(long, global = true, conflicts_with = "offline")]
+    /// Download fresh metadata instead of using cached responses, e.g. right after a release
+    ///
+    /// The fresh responses are still cached for later runs. Unlike `clean`,
+    /// this leaves the cache alone and only applies to this run.
+    no_cache: bool,
+    #[arg(long, global = true, env = "MCDL_PROXY", value_name = "URL", value_parser = parse_proxy)]
+    /// Send all requests through a proxy, e.g. `http://proxy:8080` or `socks5://localhost:1080`
+    ///
+    /// Overrides the `HTTP_PROXY`, `HTTPS_PROXY`, and `ALL_PROXY` environment variables.
+    /// Hosts in `NO_PROXY` still bypass it.
+    proxy: Option<Proxy>,
+    #[arg(long, global = true, env = "MCDL_TIMEOUT", value_name = "DURATION", value_parser = parse_duration)]
+    /// How long to wait for a connection or for more data before a request fails (default: 60s)
+    ///
+    /// Accepts seconds or a duration like `30s` or `2m`. 0 disables the timeout.
+    /// Applies to each read, so large downloads on slow connections aren't cut off.
+    timeout: Option<Duration>,
+    #[arg(long, global = true, value_name = "DIR")]
+    /// Keep the metadata, logs, instances, and JREs in this directory instead of the platform default
+    ///
+    /// Takes precedence over `MCDL_DATA_DIR`, which only moves instances, JREs, server jars, and backups.
+    data_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "MCDL_CONFIG_DIR", value_name = "DIR")]
+    /// Keep instance settings in this directory instead of the platform default
+    config_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "MCDL_CACHE_DIR", value_name = "DIR")]
+    /// Cache responses and JRE archives in this directory instead of the platform default
+    cache_dir: Option<PathBuf>,
+    #[arg(short, long, global = true)]
+    /// Hide progress bars and status messages
+    ///
+    /// Errors are still printed to stderr.
+    quiet: bool,
+    #[arg(long, global = true, value_enum, value_name = "LEVEL", default_value_t)]
+    /// How much to write to the log file
+    ///
+    /// `RUST_LOG` takes precedence, for finer-grained filtering.
+    log_level: LogLevel,
+    #[arg(long, global = true)]
+    /// Print logs to stderr as well as to the log file
+    ///
+    /// Uses the same filter as the log file, see `--log-level`.
+    verbose: bool,
     #[command(subcommand)]
     action: Action,
 }
 
 #[doc(hidden)]
+// parsed once, so the size of the largest variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 enum Action {
     /// List available Minecraft versions
@@ -52,38 +169,381 @@ enum Action {
         #[arg(short, long)]
         /// List installed instances and their versions
         installed: bool,
+        #[command(flatten)]
+        view: InstalledView,
+        #[command(flatten)]
+        order: ListOrder,
+        #[arg(long, value_name = "FILTER", value_parser = ListFilter::NAMES)]
+        /// Save the filter to use when none is given
+        set_default: Option<String>,
     },
     /// Get information about a Minecraft version
     Info {
-        #[arg(required = true, value_parser = |s: &str| validate_version_number(s))]
-        #[arg(short, long)]
+        #[arg(value_parser = |s: &str| s.parse::<VersionNumber>())]
+        #[arg(short, long, required_unless_present_any = ["latest", "latest_snapshot"])]
         /// The Minecraft version to get information about
-        version: VersionNumber,
+        ///
+        /// Also accepts `latest`, `latest-release`, or `latest-snapshot`.
+        version: Option<VersionNumber>,
+        #[arg(long, conflicts_with_all = ["version", "latest_snapshot"])]
+        /// Get information about the latest release
+        latest: bool,
+        #[arg(long, conflicts_with = "version")]
+        /// Get information about the latest snapshot
+        latest_snapshot: bool,
+        #[arg(long)]
+        /// Print the version and its server download as JSON
+        json: bool,
+        #[arg(long)]
+        /// List every download of the version with its size, SHA-1, and URL
+        ///
+        /// Includes the client jar and the obfuscation mappings, where available.
+        downloads: bool,
+        #[arg(long, conflicts_with_all = ["json", "downloads"])]
+        /// Print the complete version metadata from Mojang as JSON
+        ///
+        /// Includes everything the other output leaves out, like libraries and logging settings.
+        raw: bool,
+        #[arg(long, value_name = "VERSION", conflicts_with_all = ["json", "downloads", "raw"])]
+        /// Compare the version with another, e.g. before upgrading
+        ///
+        /// Prints their release dates, Java versions, and server jar sizes side by side,
+        /// highlighting what differs. Also accepts the same aliases as the version.
+        compare: Option<VersionNumber>,
     },
     /// Install a server instance
     Install {
-        #[arg(value_delimiter = ',', num_args = 0.., value_parser = |s: &str| validate_version_number(s))]
+        #[arg(num_args = 0..)]
         #[arg(short, long)]
         /// The version(s) to install
         ///
         /// Defaults to latest release version if none is provided.
-        /// Can be specified multiple times, or as a comma or space-separated list.
-        version: Option<Vec<VersionNumber>>,
-        // #[arg(short, long)]
-        // name: Option<String>,
+        /// Can be specified multiple times, or as a comma or space-separated list,
+        /// quoted or not. Empty entries, e.g. from a trailing comma, are ignored.
+        /// Also accepts `latest`, `latest-release`, or `latest-snapshot`.
+        version: Option<Vec<String>>,
+        #[arg(long, value_name = "PATH", conflicts_with = "version")]
+        /// Install the versions listed in a file, one per line
+        ///
+        /// Blank lines and lines starting with `#` are skipped, and duplicates are ignored.
+        /// Versions not in the manifest are reported with their line numbers,
+        /// and the rest are still installed unless `--strict` is given.
+        version_file: Option<PathBuf>,
+        #[arg(long, requires = "version_file")]
+        /// Install nothing if any version in the version file is invalid
+        strict: bool,
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "release")]
+        #[arg(conflicts_with_all = ["version", "version_file"])]
+        /// Pick the version(s) to install from a list of recent versions
+        ///
+        /// Optionally takes the type of versions to list (default: release).
+        /// Requires an interactive terminal.
+        select: Option<SelectFilter>,
+        #[arg(long)]
+        /// Consider pre-releases and release candidates when picking the latest version
+        ///
+        /// Only applies when no version is provided. By default, the latest version
+        /// is the one marked as the latest release in the version manifest.
+        include_prereleases: bool,
+        #[arg(long)]
+        /// Keep installing the remaining versions if one of them fails
+        ///
+        /// Failures are summarized at the end.
+        keep_going: bool,
+        #[arg(long, value_enum, default_value_t)]
+        /// The heap size variant of the JRE to use
+        ///
+        /// `large` builds are tuned for heaps larger than 57GB.
+        jre_heap_size: HeapSize,
+        #[arg(long, value_enum, default_value_t)]
+        /// The JVM implementation of the JRE to use
+        jre_impl: JvmImpl,
+        #[arg(long, value_enum, default_value_t)]
+        /// The image type of the JRE to use
+        ///
+        /// `jdk` includes development tools like `jcmd` and `jfr`.
+        jre_image_type: ImageType,
+        #[arg(long, value_enum, default_value_t)]
+        /// The vendor of the JRE to use
+        jre_vendor: Vendor,
+        #[arg(long, value_enum)]
+        /// Download the JRE for another architecture instead of this machine's
+        ///
+        /// Useful for preparing an instance to export to another machine.
+        /// The instance won't run here.
+        jre_arch: Option<Arch>,
+        #[arg(long, value_name = "VERSION", value_parser = parse_jre_version)]
+        /// Pin the JRE to a full version, e.g. `17.0.9+9` or `8u392-b08`, instead of the latest
+        ///
+        /// The pin is saved in the instance settings, so running it never upgrades the JRE.
+        /// Its major version is used instead of the one the server asks for,
+        /// which must not be newer.
+        jre_version: Option<String>,
+        #[arg(long)]
+        /// Also download the client jar into the instance, as `client.jar`
+        ///
+        /// Useful for mod development. Versions without a client jar are installed without it.
+        client: bool,
+        #[arg(long, visible_alias = "force")]
+        /// Reinstall versions that are already installed
+        ///
+        /// The server jar, eula.txt, and settings are replaced. Worlds are kept.
+        /// Useful for repairing an instance `verify` reports problems with.
+        force_reinstall: bool,
+        #[arg(long, default_value_t = default_metadata_jobs())]
+        /// The maximum number of version metadata requests to make at once
+        ///
+        /// Defaults to twice the number of CPUs, between 4 and 16.
+        metadata_jobs: NonZeroUsize,
+        #[arg(short = 'j', long, visible_alias = "jobs", default_value_t = default_download_jobs())]
+        /// The maximum number of downloads to run at once
+        ///
+        /// Every version is shown as queued up front,
+        /// but only this many download at the same time.
+        /// Defaults to the number of CPUs, between 2 and 8.
+        download_jobs: NonZeroUsize,
+        #[arg(long)]
+        /// Print download and extraction timings once done
+        ///
+        /// Useful for telling a slow mirror apart from a slow disk.
+        stats: bool,
+        #[arg(short, long, value_parser = |s: &str| parse_instance_name(s))]
+        /// The name to install the instance as, instead of its version
+        ///
+        /// Allows installing the same version more than once.
+        /// Only one version can be installed at a time with a name.
+        name: Option<String>,
+        #[arg(long, value_name = "PATH")]
+        /// Install the instance in this directory instead of the instance directory
+        ///
+        /// Its settings are kept in it as `mcdl.toml`, so the directory is self-contained.
+        /// Only one version can be installed at a time with an output directory,
+        /// and it must be empty unless `--force` is given.
+        /// Uninstalling removes only the files mcdl wrote, never the directory.
+        output_dir: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        /// The mod loader to run the server with
+        ///
+        /// `fabric` and `quilt` install the loader's server launcher, which runs the vanilla server jar.
+        loader: Loader,
+        #[arg(long, value_name = "VERSION")]
+        /// The version of the mod loader to install
+        ///
+        /// Defaults to the latest stable version for the Minecraft version.
+        loader_version: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        /// A well-known set of JVM flags to run the server with
+        ///
+        /// Explicit `java.args` in the instance settings take precedence.
+        flags: FlagPreset,
+        #[arg(long)]
+        /// Download JREs again even if their archives are cached
+        ///
+        /// Adoptium's latest release changes over time,
+        /// so this picks up updates to a JRE after uninstalling it.
+        refresh_jre: bool,
+        #[arg(short, long)]
+        /// Don't ask for confirmation before downloading or reinstalling
+        ///
+        /// Prompts are only shown when running in a terminal.
+        yes: bool,
+        #[arg(long, env = "MCDL_ACCEPT_EULA", value_parser = BoolishValueParser::new())]
+        /// Accept the Minecraft EULA (`https://aka.ms/MinecraftEULA`) without asking
+        ///
+        /// Otherwise, you are asked when running in a terminal.
+        /// Servers won't start until the EULA is accepted.
+        accept_eula: bool,
+    },
+    /// Update a server instance to the latest version
+    ///
+    /// Only the server jar is replaced, worlds and settings are kept.
+    Update {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to update
+        version: String,
+        #[arg(long)]
+        /// Update to the latest snapshot instead of the latest release
+        snapshot: bool,
+    },
+    /// Check an installed instance for missing or corrupt files
+    ///
+    /// Exits with an error if anything is wrong.
+    Verify {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to verify
+        version: String,
     },
     /// Uninstall a server instance
     Uninstall {
-        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(num_args = 1.., value_delimiter = ',', value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long, required_unless_present_any = ["all", "release_type"])]
+        /// The name(s) or version(s) of the instance(s) to uninstall
+        ///
+        /// Can be specified multiple times, or as a comma or space-separated list.
+        version: Vec<String>,
+        #[arg(long, conflicts_with_all = ["version", "release_type"])]
+        /// Uninstall every instance
+        all: bool,
+        #[arg(long = "type", value_name = "TYPE", conflicts_with = "version")]
+        #[arg(value_parser = ["release", "pre-release", "snapshot", "other"])]
+        /// Uninstall every instance of a release type
+        release_type: Option<String>,
+        #[arg(long)]
+        /// Keep uninstalling the remaining instances if one of them fails
+        ///
+        /// Failures are summarized at the end. Always on with `--all` or `--type`.
+        keep_going: bool,
+        #[arg(long)]
+        /// Keep the instance's JRE even if no other instance uses it
+        keep_jre: bool,
+        #[arg(long)]
+        /// Keep the instance's world backups
+        ///
+        /// They are removed by default. Kept backups are no longer rotated or cleaned up by mcdl,
+        /// and renaming another instance to the same name fails until they are moved away.
+        keep_backups: bool,
         #[arg(short, long)]
-        version: String, // in the future, `name` will be used instead
+        /// Don't ask for confirmation before uninstalling with `--all` or `--type`
+        yes: bool,
     },
     /// Run a server instance
     Run {
         #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
         #[arg(short, long)]
-        /// The version to run
-        version: String, // in the future, `name` will be used instead
+        /// The name or version of the instance to run
+        ///
+        /// Also accepts `latest`, `latest-release`, or `latest-snapshot`,
+        /// unless an instance has that name.
+        version: String,
+        #[arg(long, value_parser = |s: &str| parse_duration(s))]
+        /// Stop the server after it has been running for this long (e.g. `90s`, `5m`)
+        ///
+        /// The server is asked to stop gracefully, and is killed if it does not
+        /// exit in time. Useful for checking that an instance boots in CI.
+        max_runtime: Option<Duration>,
+        #[arg(short, long, value_parser = |s: &str| parse_memory(s))]
+        /// The maximum heap size for this run (e.g. `4G`, `512M`)
+        ///
+        /// Replaces any `-Xmx` flag in the instance's `java.args`.
+        memory: Option<String>,
+        #[arg(long, value_parser = |s: &str| parse_memory(s))]
+        /// The initial heap size for this run (e.g. `2G`)
+        ///
+        /// Replaces any `-Xms` flag in the instance's `java.args`.
+        min_memory: Option<String>,
+        #[arg(long, value_enum)]
+        /// A well-known set of JVM flags for this run
+        ///
+        /// Explicit `java.args` in the instance settings take precedence.
+        flags: Option<FlagPreset>,
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+        /// The port to run the server on for this run
+        ///
+        /// Written to `server-port` in `server.properties` before the server starts.
+        port: Option<u16>,
+        #[arg(long)]
+        /// Save the heap sizes, flag preset, and port to the instance settings
+        save: bool,
+        #[arg(short, long, conflicts_with = "max_runtime")]
+        /// Start the server in the background and return immediately
+        ///
+        /// Output goes to `server.log` in the instance directory.
+        /// Use `mcdl stop` to stop it.
+        detach: bool,
+        #[arg(long, value_name = "COMMAND", conflicts_with = "detach")]
+        /// A console command to run once the server has started, e.g. `say hello`
+        ///
+        /// Can be given more than once. Stdin is still forwarded to the console.
+        exec: Vec<String>,
+        #[arg(long, value_name = "PATH", conflicts_with = "detach")]
+        /// A file of console commands to run once the server has started, one per line
+        ///
+        /// Blank lines and lines starting with `#` are skipped.
+        /// Runs after the `--exec` commands.
+        exec_file: Option<PathBuf>,
+        #[arg(short, long)]
+        /// Back up the world before starting the server
+        ///
+        /// Backups are always made if `backup.on_run` is set in the instance settings.
+        backup: bool,
+        #[arg(long, value_name = "URL", value_parser = |s: &str| parse_paste_url(s))]
+        /// Upload crash reports to this mclogs-compatible endpoint instead of mclo.gs
+        ///
+        /// A default can be saved with `mcdl config paste-url`.
+        paste_url: Option<Url>,
+        #[arg(long, conflicts_with = "paste_url")]
+        /// Don't offer to upload a crash report if the server crashes
+        ///
+        /// Crash reports are never uploaded with `--offline`.
+        no_upload: bool,
+        #[arg(long, conflicts_with_all = ["max_runtime", "save", "detach", "exec", "exec_file", "backup"])]
+        /// Print the command line that would start the server, without starting it
+        ///
+        /// The command must be run from the instance directory (see `mcdl locate instance`),
+        /// e.g. as the `ExecStart` of a systemd unit. Nothing is written: the port is passed
+        /// with `--port`, the JRE isn't installed if it's missing, and the EULA isn't checked.
+        print_command: bool,
+        #[arg(long, conflicts_with_all = ["detach", "print_command"])]
+        /// Also write the server's output to the mcdl log file
+        ///
+        /// Each line is logged with the instance name, and lines the server writes
+        /// to stderr are logged as warnings. The output is still shown in the terminal.
+        capture_output: bool,
+    },
+    /// Back up the world of a server instance
+    ///
+    /// The `world*` directories are zipped into the backup directory.
+    /// Nothing is done if the server hasn't created a world yet.
+    Backup {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to back up
+        version: String,
+        #[arg(long, value_name = "N")]
+        /// The number of backups to keep, removing the oldest
+        ///
+        /// Defaults to `backup.keep` in the instance settings.
+        keep: Option<NonZeroUsize>,
+    },
+    /// Write an instance and its settings to a zip archive
+    ///
+    /// The archive can be installed on another machine with `import`.
+    /// Server jars are included, so it doesn't need to download anything but the JRE.
+    Export {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to export
+        version: String,
+        /// The path to write the archive to
+        out: PathBuf,
+    },
+    /// Install an instance from an archive written by `export`
+    Import {
+        /// The archive to import
+        archive: PathBuf,
+        #[arg(short, long, value_parser = |s: &str| parse_instance_name(s))]
+        /// The name to install the instance as, instead of the one it was exported with
+        ///
+        /// Required if an instance with that name is already installed.
+        name: Option<String>,
+    },
+    /// Rename an instance, keeping its worlds, settings, and backups
+    Rename {
+        #[arg(value_parser = NonEmptyStringValueParser::new())]
+        /// The name or version of the instance to rename
+        old: String,
+        #[arg(value_parser = |s: &str| parse_instance_name(s))]
+        /// The new name of the instance
+        new: String,
+    },
+    /// Stop a server started with `run --detach`
+    Stop {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to stop
+        version: String,
     },
     /// Print the path to a config file or instance directory
     Locate {
@@ -92,9 +552,140 @@ enum Action {
         /// The file or directory to locate
         what: WhatEnum,
     },
+    /// Remove unused JREs, expired cache files, old logs, and unreferenced server jars
+    Gc {
+        #[arg(long)]
+        /// Only report what would be removed
+        dry_run: bool,
+    },
+    /// Bring the metadata in line with the instances and JREs on disk
+    ///
+    /// Forgets instances, files, and JREs that no longer exist, and offers to register
+    /// instance directories that aren't tracked, e.g. after a crash during an install.
+    /// Untracked directories must be named after their version.
+    Sync {
+        #[arg(short, long)]
+        /// Register untracked instances without asking
+        yes: bool,
+    },
+    /// Remove expired cached responses
+    Clean {
+        #[arg(long)]
+        /// Remove the entire cache, including entries that haven't expired
+        all: bool,
+        #[arg(long, value_name = "SECONDS")]
+        /// Save how long cached responses stay fresh, where 0 means always refetching
+        ///
+        /// Defaults to 600. The `MCDL_CACHE_TTL` environment variable takes
+        /// precedence over the saved value. The cache is only cleaned as well with `--all`.
+        set_ttl: Option<u64>,
+        #[arg(long, value_name = "DAYS")]
+        /// Save how many days log files are kept for before they are removed
+        ///
+        /// Old logs are removed whenever mcdl starts. Defaults to 7.
+        /// The `MCDL_LOG_RETENTION_DAYS` environment variable takes precedence
+        /// over the saved value. The cache is only cleaned as well with `--all`.
+        set_log_retention: Option<u64>,
+    },
+    /// Manage instance configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage Java runtimes
+    Jre {
+        #[command(subcommand)]
+        action: JreAction,
+    },
+    /// Generate shell completions
+    #[command(hide = true)]
+    Completions {
+        #[arg(required = true)]
+        #[arg(value_enum)]
+        /// The shell to generate completions for
+        shell: Shell,
+        #[arg(long)]
+        /// Write the completions to the conventional location for the shell
+        /// instead of printing them
+        ///
+        /// Supported for bash, zsh, and fish.
+        install: bool,
+    },
+}
+
+#[doc(hidden)]
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the settings that will be used when running an instance
+    Show {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to show the settings of
+        version: String,
+        #[arg(long)]
+        /// Print the settings as JSON instead of TOML
+        json: bool,
+    },
+    /// Get or set a key in the `server.properties` of an instance
+    ///
+    /// Comments and the order of keys are kept. Common keys are `server-port`, `motd`,
+    /// `difficulty`, and `max-players`.
+    Property {
+        #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+        #[arg(short, long)]
+        /// The name or version of the instance to configure
+        version: String,
+        #[arg(required_unless_present = "get", requires = "value")]
+        #[arg(value_parser = NonEmptyStringValueParser::new())]
+        /// The key to set
+        key: Option<String>,
+        /// The value to set the key to
+        value: Option<String>,
+        #[arg(long, value_name = "KEY", conflicts_with = "key")]
+        #[arg(value_parser = NonEmptyStringValueParser::new())]
+        /// Print the value of a key instead of setting one
+        get: Option<String>,
+    },
+    /// Save the endpoint crash reports are uploaded to when `run --paste-url` isn't given
+    ///
+    /// Applies to every instance.
+    PasteUrl {
+        #[arg(value_parser = |s: &str| parse_paste_url(s))]
+        /// An mclogs-compatible endpoint, e.g. `https://api.mclo.gs/1/log`
+        url: Url,
+    },
+}
+
+#[doc(hidden)]
+#[derive(Subcommand, Debug)]
+enum JreAction {
+    /// List the Java versions available from Adoptium, marking the installed ones
+    ///
+    /// Installed JREs are listed by key, e.g. `17` or `17-jdk` for a non-default build.
+    List,
+    /// Install a JRE without installing a server
+    ///
+    /// Useful for downloading a JRE ahead of time.
+    Install {
+        #[arg(required = true)]
+        /// The major Java version to install, e.g. `21`
+        major: u8,
+    },
+    /// Remove an installed JRE
+    Uninstall {
+        #[arg(required = true)]
+        /// The major Java version to remove, e.g. `21`
+        major: u8,
+        #[arg(long)]
+        /// Remove the JRE even if an instance runs with it
+        ///
+        /// The JRE is installed again the next time the instance is run.
+        force: bool,
+    },
 }
 
 #[doc(hidden)]
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Args, Debug)]
 #[group(id = "filter", required = false, multiple = false)]
 struct ListFilter {
@@ -115,6 +706,142 @@ struct ListFilter {
     all: bool,
 }
 
+#[doc(hidden)]
+#[derive(Args, Debug)]
+struct InstalledView {
+    #[arg(long, requires = "installed")]
+    /// Flag instances with files that are missing from disk
+    missing_files: bool,
+    #[arg(long)]
+    /// Print versions as JSON, regardless of whether stdout is a terminal
+    ///
+    /// With `--installed`, prints instances instead, always including whether
+    /// each instance is healthy and its missing files.
+    json: bool,
+    #[arg(long, requires = "installed")]
+    /// Show how much disk space each instance uses
+    ///
+    /// This walks every instance directory, which can be slow for large worlds.
+    size: bool,
+}
+
+#[doc(hidden)]
+#[derive(Args, Debug)]
+struct ListOrder {
+    #[arg(long, value_enum, default_value_t)]
+    /// The order to list versions in
+    sort: SortKey,
+    #[arg(long)]
+    /// Reverse the sort order, e.g. to list the newest versions first
+    reverse: bool,
+    #[arg(long, value_name = "N", conflicts_with = "installed")]
+    /// Only list the first N versions, after sorting
+    ///
+    /// Use with `--reverse` to list the newest versions.
+    limit: Option<usize>,
+}
+
+#[doc(hidden)]
+#[derive(Clone, Copy, Default, ValueEnum, Debug)]
+enum SortKey {
+    /// Release date, oldest first
+    #[default]
+    Date,
+    /// Version number
+    Id,
+    /// Release type, then release date
+    Type,
+}
+
+impl SortKey {
+    fn compare(self, a: &GameVersion, b: &GameVersion) -> std::cmp::Ordering {
+        match self {
+            SortKey::Date => a.cmp(b),
+            SortKey::Id => a.id.cmp(&b.id),
+            SortKey::Type => a.release_type.cmp(&b.release_type).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// A version, as printed by `info --json`
+#[derive(Serialize)]
+struct VersionInfo<'a> {
+    #[serde(flatten)]
+    version: &'a GameVersion,
+    #[serde(rename = "javaVersion")]
+    java_version: u8,
+    /// `None` for versions without a server
+    server: Option<&'a VersionDownload>,
+    /// Only included with `--downloads`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    downloads: Option<BTreeMap<&'a str, &'a VersionDownload>>,
+}
+
+/// A version, as printed by `list --json`
+#[derive(Serialize)]
+struct ListedVersion<'a> {
+    id: &'a VersionNumber,
+    release_type: &'a str,
+    release_time: &'a DateTime<FixedOffset>,
+}
+
+/// An installed instance, as printed by `list --installed --json`
+#[derive(Serialize)]
+struct InstalledInstance<'a> {
+    id: &'a str,
+    version: &'a VersionNumber,
+    release_type: &'a str,
+    jre: u8,
+    location: PathBuf,
+    healthy: bool,
+    missing_files: Vec<&'a PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// In bytes, only computed with `--size`
+    size: Option<u64>,
+}
+
+impl ListFilter {
+    const NAMES: [&str; 5] = ["release", "pre-release", "snapshot", "other", "all"];
+
+    fn from_name(name: &str) -> Option<Self> {
+        let mut filter = Self {
+            release: false,
+            pre_release: false,
+            snapshot: false,
+            other: false,
+            all: false,
+        };
+
+        match name {
+            "release" => filter.release = true,
+            "pre-release" => filter.pre_release = true,
+            "snapshot" => filter.snapshot = true,
+            "other" => filter.other = true,
+            "all" => filter.all = true,
+            _ => return None,
+        }
+
+        Some(filter)
+    }
+
+    fn matches(&self, id: &VersionNumber) -> bool {
+        match (
+            self.release,
+            self.pre_release,
+            self.snapshot,
+            self.other,
+            self.all,
+        ) {
+            (true, _, _, _, _) => id.is_release(),
+            (_, true, _, _, _) => id.is_pre_release(),
+            (_, _, true, _, _) => id.is_snapshot(),
+            (_, _, _, true, _) => id.is_other(),
+            (_, _, _, _, true) => true,
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl Default for ListFilter {
     fn default() -> Self {
         Self {
@@ -127,6 +854,54 @@ impl Default for ListFilter {
     }
 }
 
+#[doc(hidden)]
+#[derive(Clone, Copy, ValueEnum, Debug, Display)]
+enum SelectFilter {
+    /// Release versions
+    Release,
+    /// Release and pre-release versions
+    PreRelease,
+    /// Snapshot versions
+    Snapshot,
+    /// All versions
+    All,
+}
+
+impl SelectFilter {
+    fn matches(self, version: &VersionNumber) -> bool {
+        match self {
+            SelectFilter::Release => version.is_release(),
+            SelectFilter::PreRelease => version.is_release() || version.is_pre_release(),
+            SelectFilter::Snapshot => version.is_snapshot(),
+            SelectFilter::All => true,
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone, Copy, ValueEnum, Debug, Default)]
+enum LogLevel {
+    Trace,
+    #[default]
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// The `EnvFilter` directive for mcdl's own logs at this level
+    fn directive(self) -> &'static str {
+        match self {
+            Self::Trace => "mcdl=trace",
+            Self::Debug => "mcdl=debug",
+            Self::Info => "mcdl=info",
+            Self::Warn => "mcdl=warn",
+            Self::Error => "mcdl=error",
+        }
+    }
+}
+
 #[doc(hidden)]
 #[derive(Clone, Copy, ValueEnum, Debug, Display)]
 enum WhatEnum {
@@ -138,23 +913,123 @@ enum WhatEnum {
     Config,
     /// The directory containing logs
     Log,
+    /// The directory containing cached responses and downloads
+    Cache,
+    /// Every directory above
+    All,
 }
 
-#[instrument(level = "debug", err, ret)]
-fn validate_version_number(v: &str) -> Result<VersionNumber> {
-    // lol
-    let version = v.parse()?;
+/// The default `--metadata-jobs`, which can be higher than the number of CPUs
+/// since the requests are small and mostly spent waiting on the network
+fn default_metadata_jobs() -> NonZeroUsize {
+    let cpus = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    NonZeroUsize::new((cpus * 2).clamp(4, 16)).expect("infallible")
+}
 
+/// The default `--download-jobs`, limited by the number of CPUs
+/// since every download is also hashed, and JREs are extracted
+fn default_download_jobs() -> NonZeroUsize {
+    let cpus = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    NonZeroUsize::new(cpus.clamp(2, 8)).expect("infallible")
+}
+
+#[instrument(level = "debug", err, ret)]
+fn validate_version_number(version: &VersionNumber) -> Result<()> {
     MANIFEST
         .get()
         .expect("manifest not set")
         .versions
         .iter()
-        .map(|v| &v.id)
-        .find(|v| v == &&version)
-        .cloned()
-        .map(|_| version)
-        .ok_or(eyre!("Version does not exist"))
+        .any(|v| &v.id == version)
+        .then_some(())
+        .ok_or_else(|| McdlError::VersionNotFound(version.clone()).into())
+}
+
+/// Replaces version aliases like `latest-snapshot` passed on the command line
+/// with the versions they point to in the manifest
+///
+/// Like [`validate_versions`], this has to wait for the manifest to be read
+fn resolve_version_aliases(action: &mut Action) {
+    let manifest = MANIFEST.get().expect("manifest not set");
+    let resolve = |version: &mut VersionNumber| {
+        if let Some(resolved) = manifest.resolve_alias(&version.to_string()) {
+            debug!(%version, %resolved, "Resolved version alias");
+            *version = resolved.clone();
+        }
+    };
+
+    match action {
+        Action::Info {
+            version,
+            latest,
+            latest_snapshot,
+            compare,
+            ..
+        } => {
+            if *latest {
+                *version = Some(manifest.latest.release.clone());
+            } else if *latest_snapshot {
+                *version = Some(manifest.latest.snapshot.clone());
+            }
+            version.iter_mut().for_each(resolve);
+            compare.iter_mut().for_each(resolve);
+        }
+        Action::Install {
+            version: Some(versions),
+            ..
+        } => {
+            for version in versions {
+                if let Some(resolved) = manifest.resolve_alias(version) {
+                    debug!(%version, %resolved, "Resolved version alias");
+                    *version = resolved.to_string();
+                }
+            }
+        }
+        // an instance named like an alias wins, since it was named explicitly
+        Action::Run { version, .. } if !META.lock().instance_installed(version) => {
+            if let Some(resolved) = manifest.resolve_alias(version) {
+                debug!(%version, %resolved, "Resolved version alias");
+                *version = resolved.to_string();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Checks that every version passed on the command line exists in the manifest,
+/// exiting with a usage error otherwise
+///
+/// This can't be done while parsing, since the manifest source is itself an argument
+fn validate_versions(action: &Action) {
+    let versions: Vec<(&str, VersionNumber)> = match action {
+        Action::Info {
+            version, compare, ..
+        } => version
+            .iter()
+            .map(|v| ("--version", v))
+            .chain(compare.iter().map(|v| ("--compare", v)))
+            .map(|(arg, v)| (arg, v.clone()))
+            .collect(),
+        Action::Install {
+            version: Some(versions),
+            ..
+        } => versions
+            .iter()
+            .filter_map(|v| Some(("--version", v.parse().ok()?)))
+            .collect(),
+        _ => vec![],
+    };
+
+    for (arg, version) in versions {
+        if let Err(e) = validate_version_number(&version) {
+            Cli::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("invalid value '{version}' for '{arg} <VERSION>': {e}"),
+                )
+                .exit();
+        }
+    }
 }
 
 /* end cli */
@@ -171,10 +1046,6 @@ async fn main() -> Result<()> {
         .theme(color_eyre::config::Theme::new())
         .install()?;
 
-    MANIFEST
-        .set(get_version_manifest().await?)
-        .map_err(|_| unreachable!("manifest already set"))?;
-
     let args = std::env::args().collect_vec();
 
     let log_name = format!(
@@ -186,32 +1057,289 @@ async fn main() -> Result<()> {
             String::new()
         }
     );
+
+    // lol again
+    let cli = tokio::task::spawn_blocking(Cli::parse).await?;
+    // before the metadata lock, which only reports waiting on it when not quiet
+    OFFLINE.store(cli.offline, Ordering::Relaxed);
+    NO_CACHE.store(cli.no_cache, Ordering::Relaxed);
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+
+    // completions are generated on shell startup, so they skip the log, the metadata, and the manifest
+    if let Action::Completions { shell, install } = cli.action {
+        return completions_impl(shell, install);
+    }
+
+    // before anything uses the base directories, including the log and the metadata
+    set_base_dirs(&cli)?;
     let log_path = LOG_BASE_DIR.join(log_name);
 
-    // set up tracing
-    install_tracing(&log_path)?;
+    // set up tracing, which needs the log level
+    install_tracing(&log_path, cli.log_level, cli.verbose)?;
     info!("Logging to {}", log_path.display());
 
-    info!("Args: {}", args.to_args_string());
+    lock_meta().wrap_err("Failed to lock the metadata")?;
+    let pruned_logs = app::prune_logs(app::log_retention(), &log_path);
+    debug!(pruned_logs, "Removed old log files");
 
-    // lol again
-    let cli = tokio::task::spawn_blocking(Cli::parse).await?;
+    info!("Args: {}", args.to_args_string());
     debug!(?cli);
+    if let Some(proxy) = cli.proxy.clone() {
+        PROXY
+            .set(proxy)
+            .map_err(|_| unreachable!("proxy already set"))?;
+    }
+    if let Some(timeout) = cli.timeout {
+        TIMEOUT
+            .set(timeout)
+            .map_err(|_| unreachable!("timeout already set"))?;
+    }
+    if let Some(url) = cli.manifest_url.clone() {
+        MANIFEST_URL
+            .set(url)
+            .map_err(|_| unreachable!("manifest URL already set"))?;
+    }
+    if let Some(mirror) = cli.mirror {
+        MIRROR
+            .set(mirror)
+            .map_err(|_| unreachable!("mirror already set"))?;
+    }
 
-    match cli.action {
-        Action::List { filter, installed } => list_impl(filter, installed).await?,
-        Action::Info { version } => info_impl(version).await?,
-        Action::Install { version } => install_impl(version).await?,
-        Action::Uninstall { version } => uninstall_impl(version)?,
-        Action::Run { version } => run_impl(version).await?,
+    let manifest = match &cli.manifest {
+        Some(source) => read_local_manifest(source).await,
+        None => get_version_manifest().await,
+    }
+    .map_err(exit_with_code)?;
+    MANIFEST
+        .set(manifest)
+        .map_err(|_| unreachable!("manifest already set"))?;
+    let mut action = cli.action;
+    // the shell may have split a version list, or kept it whole, depending on quoting
+    if let Action::Install {
+        version: Some(versions),
+        ..
+    } = &mut action
+    {
+        *versions = split_version_list(versions);
+    }
+    resolve_version_aliases(&mut action);
+    validate_versions(&action);
+
+    run_action(action).await.map_err(exit_with_code)
+}
+
+/// Exits with the code for a common failure, see [`exit_code`],
+/// or returns the error to be reported as usual, exiting with 1
+fn exit_with_code(e: Report) -> Report {
+    let code = exit_code(&e);
+    if code != 1 {
+        // this is what returning the error from `main` would print
+        error!("{e:?}");
+        eprintln!("Error: {e:?}");
+        std::process::exit(code);
+    }
+    e
+}
+
+/// Dispatches a parsed action to its implementation
+#[allow(clippy::too_many_lines)]
+async fn run_action(action: Action) -> Result<()> {
+    match action {
+        Action::List {
+            filter,
+            installed,
+            view,
+            order,
+            set_default,
+        } => list_impl(filter, installed, view, order, set_default).await?,
+        Action::Info {
+            version,
+            json,
+            downloads,
+            raw,
+            compare,
+            ..
+        } => {
+            // set from `--latest` or `--latest-snapshot` when not given
+            let version = version.expect("version resolved");
+            info_impl(version, json, downloads, raw, compare).await?;
+        }
+        Action::Install {
+            version,
+            version_file,
+            strict,
+            select,
+            include_prereleases,
+            keep_going,
+            jre_heap_size,
+            jre_impl,
+            jre_image_type,
+            jre_vendor,
+            jre_arch,
+            jre_version,
+            client,
+            force_reinstall,
+            metadata_jobs,
+            download_jobs,
+            stats,
+            name,
+            output_dir,
+            loader,
+            loader_version,
+            flags,
+            refresh_jre,
+            yes,
+            accept_eula,
+        } => {
+            // recorded in the metadata, so it must not depend on where mcdl is run from
+            let output_dir = output_dir
+                .map(std::path::absolute)
+                .transpose()
+                .wrap_err("Invalid output directory")?;
+            let options = InstallOptions {
+                keep_going,
+                heap_size: jre_heap_size,
+                jvm_impl: jre_impl,
+                image_type: jre_image_type,
+                vendor: jre_vendor,
+                arch: jre_arch,
+                jre_version,
+                force_reinstall,
+                client,
+                metadata_jobs,
+                download_jobs,
+                stats,
+                name,
+                output_dir,
+                loader,
+                loader_version,
+                flag_preset: flags,
+                refresh_jre,
+                yes,
+                accept_eula,
+            };
+            let version = match version_file {
+                Some(path) => Some(read_version_file(&path, strict)?),
+                None => version,
+            };
+            install_impl(version, select, include_prereleases, &options).await?;
+        }
+        Action::Update { version, snapshot } => update_impl(version, snapshot).await?,
+        Action::Verify { version } => verify_impl(version).await?,
+        Action::Uninstall {
+            version,
+            all,
+            release_type,
+            keep_going,
+            keep_jre,
+            keep_backups,
+            yes,
+        } => {
+            let filter = if all {
+                Some("all")
+            } else {
+                release_type.as_deref()
+            };
+            let options = UninstallOptions {
+                keep_going,
+                keep_jre,
+                keep_backups,
+            };
+            uninstall_impl(version, filter, options, yes).await?;
+        }
+        Action::Run {
+            version,
+            max_runtime,
+            memory,
+            min_memory,
+            flags,
+            port,
+            save,
+            detach,
+            exec,
+            exec_file,
+            backup,
+            paste_url,
+            no_upload,
+            print_command,
+            capture_output,
+        } => {
+            let mut exec = exec;
+            if let Some(path) = exec_file {
+                exec.extend(read_commands(&path)?);
+            }
+
+            let options = RunOptions {
+                max_runtime,
+                min_memory,
+                max_memory: memory,
+                flag_preset: flags,
+                port,
+                save,
+                detach,
+                backup,
+                paste_url,
+                no_upload,
+                exec,
+                print_command,
+                capture_output,
+            };
+            run_impl(version, options).await?;
+        }
+        Action::Backup { version, keep } => backup_impl(version, keep).await?,
+        Action::Export { version, out } => export_impl(version, out).await?,
+        Action::Import { archive, name } => import_impl(archive, name).await?,
+        Action::Rename { old, new } => rename_impl(old, new).await?,
+        Action::Stop { version } => stop_impl(version).await?,
         Action::Locate { what } => locate_impl(what)?,
+        Action::Gc { dry_run } => gc_impl(dry_run).await?,
+        Action::Sync { yes } => sync_impl(yes).await?,
+        Action::Clean {
+            all,
+            set_ttl,
+            set_log_retention,
+        } => clean_impl(all, set_ttl, set_log_retention)?,
+        Action::Config { action } => config_impl(action).await?,
+        Action::Jre { action } => jre_impl(action).await?,
+        Action::Completions { .. } => unreachable!("completions are generated before the manifest"),
     }
 
     Ok(())
 }
 
-fn install_tracing(path: &PathBuf) -> Result<()> {
+/// Overrides the platform's base directories with `--data-dir`, `--config-dir`, and `--cache-dir`
+fn set_base_dirs(cli: &Cli) -> Result<()> {
+    // recorded in the metadata, so they must not depend on where mcdl is run from
+    let absolute = |dir: &Option<PathBuf>| {
+        dir.as_deref()
+            .map(std::path::absolute)
+            .transpose()
+            .wrap_err("Invalid base directory")
+    };
+
+    if let Some(dir) = absolute(&cli.data_dir)? {
+        DATA_DIR
+            .set(dir)
+            .map_err(|_| unreachable!("data directory already set"))?;
+    }
+    if let Some(dir) = absolute(&cli.config_dir)? {
+        CONFIG_DIR
+            .set(dir)
+            .map_err(|_| unreachable!("config directory already set"))?;
+    }
+    if let Some(dir) = absolute(&cli.cache_dir)? {
+        CACHE_DIR
+            .set(dir)
+            .map_err(|_| unreachable!("cache directory already set"))?;
+    }
+    Ok(())
+}
+
+/// Logs to `path`, and to stderr if `verbose` is set,
+/// filtered by `RUST_LOG` if it is set and by `level` otherwise
+fn install_tracing(path: &PathBuf, level: LogLevel, verbose: bool) -> Result<()> {
     use tracing_error::ErrorLayer;
+    use tracing_subscriber::filter::filter_fn;
     use tracing_subscriber::prelude::*;
     use tracing_subscriber::{EnvFilter, fmt};
 
@@ -223,139 +1351,324 @@ fn install_tracing(path: &PathBuf) -> Result<()> {
         // .with_timer(fmt::time::uptime())
         .with_thread_ids(true)
         .with_writer(Mutex::new(file));
+    // stderr is shared with the progress bars, which are cleared while a line is written
+    let stderr_layer = verbose.then(|| {
+        fmt::layer()
+            .with_ansi(std::io::stderr().is_terminal())
+            .with_writer(|| SuspendingWriter(std::io::stderr()))
+            .with_filter(filter_fn(|metadata| {
+                metadata.target() != app::SERVER_OUTPUT_TARGET
+            }))
+    });
     let filter_layer =
-        EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("mcdl=debug"))?;
+        EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(level.directive()))?;
 
     tracing_subscriber::registry()
         .with(filter_layer)
         .with(fmt_layer)
+        .with(stderr_layer)
         .with(ErrorLayer::default())
         .init();
 
     Ok(())
 }
 
+/// Writes to the inner writer with the progress bars cleared, so they aren't garbled
+struct SuspendingWriter<W>(W);
+
+impl<W: std::io::Write> std::io::Write for SuspendingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        PROGRESS.suspend(|| self.0.write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
 /* end main */
 
 /* impls */
 
 #[instrument(err, ret(level = "debug"), skip(filter))]
-async fn list_impl(filter: Option<ListFilter>, installed: bool) -> Result<()> {
-    let filter = filter.unwrap_or_default();
+async fn list_impl(
+    filter: Option<ListFilter>,
+    installed: bool,
+    view: InstalledView,
+    order: ListOrder,
+    set_default: Option<String>,
+) -> Result<()> {
+    if let Some(name) = set_default {
+        let mut meta = META.lock();
+        meta.default_list_filter = Some(name.clone());
+        meta.save()?;
+        eprintln!("Default filter set to {name}");
+    }
+
+    let filter = filter
+        .or_else(|| {
+            let name = META.lock().default_list_filter.clone()?;
+            ListFilter::from_name(&name).or_else(|| {
+                warn!(name, "Ignoring unknown default filter");
+                None
+            })
+        })
+        .unwrap_or_default();
     debug!(?filter);
 
-    let versions = MANIFEST
+    let mut versions = MANIFEST
         .get()
         .expect("manifest not set")
         .versions
         .iter()
-        .filter(|v| {
-            match (
-                filter.release,
-                filter.pre_release,
-                filter.snapshot,
-                filter.other,
-                filter.all,
-            ) {
-                (true, _, _, _, _) => v.id.is_release(),
-                (_, true, _, _, _) => v.id.is_pre_release(),
-                (_, _, true, _, _) => v.id.is_snapshot(),
-                (_, _, _, true, _) => v.id.is_other(),
-                (_, _, _, _, true) => true,
-                _ => unreachable!(),
+        .filter(|v| filter.matches(&v.id))
+        .sorted_by(|a, b| {
+            let ordering = order.sort.compare(a, b);
+            if order.reverse {
+                ordering.reverse()
+            } else {
+                ordering
             }
         })
-        .sorted()
         .collect_vec();
 
     info!("Found {} matching versions", versions.len());
 
     if installed {
-        // installed versions only, more info
-        info!("Filtering for installed versions");
+        return list_installed(&versions, &view);
+    }
+
+    let total = versions.len();
+    if let Some(limit) = order.limit {
+        versions.truncate(limit);
+    }
+    let footer =
+        (versions.len() < total).then(|| format!("Showing {} of {total} versions", versions.len()));
 
-        let installed_instances = &META.lock().instances;
-        let filtered_instances = installed_instances
+    // short info for all versions
+    info!("Filtering for all versions");
+    print_versions(&versions, view.json, footer.as_deref())
+}
+
+/// Prints the given versions as JSON, bare ids when piped, or a table,
+/// followed by `footer` if the list was truncated
+fn print_versions(versions: &[&GameVersion], json: bool, footer: Option<&str>) -> Result<()> {
+    if json {
+        let listed = versions
             .iter()
-            .filter(|(_, i)| versions.iter().any(|v| v.id == i.id))
+            .map(|v| ListedVersion {
+                id: &v.id,
+                release_type: &v.release_type,
+                release_time: &v.release_time,
+            })
             .collect_vec();
-
-        info!("Found {} installed versions", filtered_instances.len());
-        if filtered_instances.is_empty() {
-            println!("No matching versions installed");
-            return Ok(());
+        println!("{}", serde_json::to_string_pretty(&listed)?);
+        // on stderr, so the output stays valid JSON
+        if let Some(footer) = footer {
+            eprintln!("{footer}");
         }
+        return Ok(());
+    }
 
-        let mut table = Table::new();
-        table.set_format(
-            FormatBuilder::new()
-                .column_separator(' ')
-                .borders(' ')
-                .padding(1, 1)
-                .build(),
-        );
+    if !std::io::stdout().is_terminal() {
+        for v in versions {
+            println!("{}", v.id);
+        }
+        if let Some(footer) = footer {
+            eprintln!("{footer}");
+        }
+        return Ok(());
+    }
 
-        table.set_titles(row![b => "ID", "Version", "Type", "JRE"]);
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
 
-        for (id, instance) in filtered_instances {
-            let version = versions.iter().find(|v| v.id == instance.id).unwrap();
-            let location = PROJ_DIRS.data_local_dir().join("instance").join(id);
+    table.set_titles(row![b => "Version", "Type", "Release Date"]);
+    for version in versions {
+        table.add_row(Row::new(vec![
+            Cell::new(&version.id.to_string()),
+            Cell::new(&version.release_type.clone()).style_spec(
+                match version.release_type.as_str() {
+                    "release" => "Fgb",
+                    _ => "",
+                },
+            ),
+            Cell::new(&version.release_time.to_string()),
+        ]));
+    }
 
-            table.add_row(row![id, version.id, version.release_type, instance.jre]);
-            table.add_row(row![H4->format!("{} {}", "Location:".bold(), location.display())]);
-            table.add_empty_row();
-        }
+    table.printstd();
+    if let Some(footer) = footer {
+        println!("{footer}");
+    }
 
-        table.printstd();
-    } else {
-        // short info for all versions
-        info!("Filtering for all versions");
+    Ok(())
+}
 
-        if !std::io::stdout().is_terminal() {
-            for v in versions {
-                println!("{}", v.id);
-            }
-            return Ok(());
-        }
+/// Prints installed instances of the given versions
+#[instrument(err, ret(level = "debug"), skip(versions))]
+fn list_installed(versions: &[&GameVersion], view: &InstalledView) -> Result<()> {
+    // installed versions only, more info
+    info!("Filtering for installed versions");
+
+    let meta = META.lock();
+    let installed_instances = &meta.instances;
+    // in the same order as the versions
+    let filtered_instances = installed_instances
+        .iter()
+        .filter_map(|(key, i)| {
+            let position = versions.iter().position(|v| v.id == i.id)?;
+            Some((position, key, i))
+        })
+        .sorted_by_key(|(position, key, _)| (*position, *key))
+        .map(|(_, key, i)| (key, i))
+        .collect_vec();
 
-        let mut table = Table::new();
-        table.set_format(
-            FormatBuilder::new()
-                .column_separator(' ')
-                .borders(' ')
-                .padding(1, 1)
-                .build(),
-        );
+    info!("Found {} installed versions", filtered_instances.len());
+
+    if view.json {
+        let instances = filtered_instances
+            .iter()
+            .map(|(id, instance)| {
+                let version = versions.iter().find(|v| v.id == instance.id).unwrap();
+                let missing_files = instance.missing_files();
+                let location = app::instance_dir(&meta, id);
+                InstalledInstance {
+                    id,
+                    version: &version.id,
+                    release_type: &version.release_type,
+                    jre: instance.jre,
+                    size: view.size.then(|| app::path_size(&location)),
+                    location,
+                    healthy: missing_files.is_empty(),
+                    missing_files,
+                }
+            })
+            .collect_vec();
+        println!("{}", serde_json::to_string_pretty(&instances)?);
+        return Ok(());
+    }
 
-        table.set_titles(row![b => "Version", "Type", "Release Date"]);
-        for version in versions {
-            table.add_row(Row::new(vec![
-                Cell::new(&version.id.to_string()),
-                Cell::new(&version.release_type.clone()).style_spec(
-                    match version.release_type.as_str() {
-                        "release" => "Fgb",
-                        _ => "",
-                    },
-                ),
-                Cell::new(&version.release_time.to_string()),
-            ]));
+    if filtered_instances.is_empty() {
+        println!("No matching versions installed");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
+
+    let mut titles = row![b => "ID", "Version", "Type", "JRE"];
+    if view.size {
+        titles.add_cell(Cell::new("Size").style_spec("b"));
+    }
+    if view.missing_files {
+        titles.add_cell(Cell::new("Files").style_spec("b"));
+    }
+    let columns = titles.len();
+    table.set_titles(titles);
+
+    let detail = |label: &str, value: &dyn std::fmt::Display| {
+        Row::new(vec![
+            Cell::new(&format!("{} {value}", label.bold())).with_hspan(columns),
+        ])
+    };
+
+    for (id, instance) in filtered_instances {
+        let version = versions.iter().find(|v| v.id == instance.id).unwrap();
+        let location = app::instance_dir(&meta, id);
+
+        let mut row = row![id, version.id, version.release_type, instance.jre];
+        if view.size {
+            row.add_cell(Cell::new(
+                &HumanBytes(app::path_size(&location)).to_string(),
+            ));
         }
+        let missing_files = if view.missing_files {
+            let missing_files = instance.missing_files();
+            let status = if missing_files.is_empty() {
+                "ok".green().to_string()
+            } else {
+                format!("⚠ {} missing", missing_files.len())
+                    .yellow()
+                    .to_string()
+            };
+            row.add_cell(Cell::new(&status));
+            missing_files
+        } else {
+            Vec::new()
+        };
 
-        table.printstd();
+        table.add_row(row);
+        table.add_row(detail("Location:", &location.display()));
+        for file in missing_files {
+            table.add_row(detail("Missing:", &file.display()));
+        }
+        table.add_empty_row();
     }
 
+    table.printstd();
+
     Ok(())
 }
 
 #[instrument(err, ret(level = "debug"))]
-async fn info_impl(version: VersionNumber) -> Result<()> {
-    let version = MANIFEST
-        .get()
-        .expect("manifest not set")
-        .versions
-        .iter()
-        .find(|v| v.id == version)
-        .expect("infallible");
+async fn info_impl(
+    version: VersionNumber,
+    json: bool,
+    downloads: bool,
+    raw: bool,
+    compare: Option<VersionNumber>,
+) -> Result<()> {
+    let find = |id: VersionNumber| {
+        MANIFEST
+            .get()
+            .expect("manifest not set")
+            .versions
+            .iter()
+            .find(|v| v.id == id)
+            .expect("infallible")
+    };
+    let version = find(version);
+
+    if let Some(other) = compare {
+        return compare_versions(version, find(other)).await;
+    }
+
+    if raw {
+        let metadata = get_raw_version_metadata(version).await?;
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+        return Ok(());
+    }
+
+    if json {
+        let metadata = get_version_metadata(version).await?;
+        let info = VersionInfo {
+            version,
+            java_version: metadata.java_version.major_version,
+            server: metadata.downloads.get("server"),
+            downloads: downloads.then(|| {
+                metadata
+                    .downloads
+                    .iter()
+                    .map(|(name, download)| (name.as_str(), download))
+                    .collect()
+            }),
+        };
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
 
     let time_format = "%-d %B %Y at %-I:%M:%S%P UTC";
     let message = format!(
@@ -368,69 +1681,539 @@ async fn info_impl(version: VersionNumber) -> Result<()> {
 
     println!("{message}");
 
+    if downloads {
+        let metadata = get_version_metadata(version).await?;
+
+        let mut table = Table::new();
+        table.set_format(
+            FormatBuilder::new()
+                .column_separator(' ')
+                .borders(' ')
+                .padding(1, 1)
+                .build(),
+        );
+        table.set_titles(row![b => "Download", "Size", "SHA-1", "URL"]);
+        for (name, download) in metadata.downloads.iter().sorted_by_key(|(name, _)| *name) {
+            table.add_row(row![
+                name,
+                HumanBytes(download.size),
+                download.sha1,
+                download.url
+            ]);
+        }
+
+        println!();
+        table.printstd();
+    }
+
     Ok(())
 }
 
-#[instrument(err, ret(level = "debug"), skip(versions))]
-async fn install_impl(versions: Option<Vec<VersionNumber>>) -> Result<()> {
-    let manifest = MANIFEST.get().expect("manifest not set");
-    let game_versions = &manifest.versions;
-    let latest = &manifest.latest;
+/// Prints the metadata of two versions side by side, highlighting the values that differ
+async fn compare_versions(version: &GameVersion, other: &GameVersion) -> Result<()> {
+    let (metadata, other_metadata) =
+        tokio::try_join!(get_version_metadata(version), get_version_metadata(other))?;
+
+    let date_format = "%-d %B %Y";
+    let server_jar = |metadata: &VersionMetadata| {
+        metadata
+            .downloads
+            .get("server")
+            .map_or_else(|| "none".to_string(), |d| HumanBytes(d.size).to_string())
+    };
+    let rows = [
+        (
+            "Type",
+            version.release_type.clone(),
+            other.release_type.clone(),
+        ),
+        (
+            "Released",
+            version.release_time.format(date_format).to_string(),
+            other.release_time.format(date_format).to_string(),
+        ),
+        (
+            "Java",
+            metadata.java_version.major_version.to_string(),
+            other_metadata.java_version.major_version.to_string(),
+        ),
+        (
+            "Server jar",
+            server_jar(&metadata),
+            server_jar(&other_metadata),
+        ),
+    ];
+
+    let mut table = Table::new();
+    table.set_format(
+        FormatBuilder::new()
+            .column_separator(' ')
+            .borders(' ')
+            .padding(1, 1)
+            .build(),
+    );
+    table.set_titles(row![b => "", version.id, other.id]);
+    for (label, left, right) in rows {
+        let right = if left == right {
+            right
+        } else {
+            right.yellow().bold().to_string()
+        };
+        table.add_row(row![b -> label, left, right]);
+    }
 
-    if versions.is_none() {
-        println!("Installing latest release version\n");
-        let latest = game_versions
-            .iter()
-            .find(|v| v.id == latest.release)
-            .ok_or_else(|| eyre!("No latest release version found"))?;
-        app::install_versions(vec![latest])
-            .await
-            .wrap_err("Error while installing latest version")?;
+    table.printstd();
+    Ok(())
+}
 
+/// Installs the latest release, or pre-release if `include_prereleases` is set
+async fn install_latest(include_prereleases: bool, options: &InstallOptions) -> Result<()> {
+    let manifest = MANIFEST.get().expect("manifest not set");
+    if include_prereleases {
+        status!("Installing latest release or pre-release version\n");
+    } else {
+        status!("Installing latest release version\n");
+    }
+    let latest = manifest
+        .latest_release(include_prereleases)
+        .ok_or_else(|| eyre!("No latest release version found"))?;
+
+    let id = options
+        .name
+        .clone()
+        .unwrap_or_else(|| latest.id.to_string());
+    if options.force_reinstall
+        && !options.yes
+        && META.lock().instance_installed(&id)
+        && !confirm_reinstall(&[id])?
+    {
+        println!("Cancelled");
         return Ok(());
     }
 
-    let versions = versions.unwrap();
+    app::install_versions(vec![latest], options)
+        .await
+        .wrap_err("Error while installing latest version")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"), skip(versions))]
+async fn install_impl(
+    versions: Option<Vec<String>>,
+    select: Option<SelectFilter>,
+    include_prereleases: bool,
+    options: &InstallOptions,
+) -> Result<()> {
+    let versions = match select {
+        Some(filter) => {
+            let selected = select_versions(filter)?;
+            if selected.is_empty() {
+                println!("No versions selected");
+                return Ok(());
+            }
+            Some(selected)
+        }
+        None => versions
+            .map(|versions| {
+                versions
+                    .iter()
+                    .map(|v| v.parse())
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?,
+    };
+
+    let Some(versions) = versions else {
+        return install_latest(include_prereleases, options).await;
+    };
+
+    // duplicates would otherwise only be caught by the already-installed check, mid-install
+    let count = versions.len();
+    let versions = versions
+        .into_iter()
+        .unique_by(ToString::to_string)
+        .collect_vec();
+    if versions.len() < count {
+        info!(
+            duplicates = count - versions.len(),
+            "Ignoring duplicate versions"
+        );
+    }
     if versions.is_empty() {
         Cli::command()
             .error(ErrorKind::ValueValidation, "No version provided")
             .exit();
     }
+    if options.name.is_some() && versions.len() > 1 {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--name can only be used when installing a single version",
+            )
+            .exit();
+    }
+    if options.output_dir.is_some() && versions.len() > 1 {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--output-dir can only be used when installing a single version",
+            )
+            .exit();
+    }
+    if options.loader == Loader::Vanilla && options.loader_version.is_some() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--loader-version can only be used with a mod loader",
+            )
+            .exit();
+    }
 
-    println!(
+    status!(
         "Installing {} version{}: {}\n",
         versions.len(),
         if versions.len() == 1 { "" } else { "s" },
         versions.iter().map(ToString::to_string).join(", ")
     );
 
-    let to_install_versions = game_versions
+    if options.force_reinstall && !options.yes {
+        let reinstalled = versions
+            .iter()
+            .map(|v| options.name.clone().unwrap_or_else(|| v.to_string()))
+            .filter(|id| META.lock().instance_installed(id))
+            .collect_vec();
+
+        if !reinstalled.is_empty() && !confirm_reinstall(&reinstalled)? {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    let to_install_versions = MANIFEST
+        .get()
+        .expect("manifest not set")
+        .versions
         .iter()
         .filter(|v| versions.contains(&v.id))
         .collect_vec();
-    app::install_versions(to_install_versions)
+    app::install_versions(to_install_versions, options)
         .await
         .wrap_err("Error while installing versions")?;
 
     Ok(())
 }
 
+/// The number of versions offered by `install --select`
+const SELECT_MAX_VERSIONS: usize = 30;
+
+/// Asks the user to pick versions to install from the most recent versions matching `filter`
+fn select_versions(filter: SelectFilter) -> Result<Vec<VersionNumber>> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--select requires an interactive terminal, pass versions with --version instead",
+            )
+            .exit();
+    }
+
+    let versions = MANIFEST
+        .get()
+        .expect("manifest not set")
+        .versions
+        .iter()
+        .filter(|v| filter.matches(&v.id))
+        .sorted()
+        .rev()
+        .take(SELECT_MAX_VERSIONS)
+        .collect_vec();
+
+    let items = {
+        let meta = META.lock();
+        versions
+            .iter()
+            .map(|v| {
+                if meta.instances.values().any(|i| i.id == v.id) {
+                    format!("{} (installed)", v.id)
+                } else {
+                    v.id.to_string()
+                }
+            })
+            .collect_vec()
+    };
+
+    let selected = MultiSelect::new()
+        .with_prompt("Select versions to install (space to toggle, enter to confirm)")
+        .items(&items)
+        .interact()?;
+
+    Ok(selected
+        .into_iter()
+        .map(|i| versions[i].id.clone())
+        .collect())
+}
+
+/// Warns that existing instances will be replaced, asking for
+/// confirmation if running interactively
+fn confirm_reinstall(instances: &[String]) -> Result<bool> {
+    let message = format!(
+        "This will replace the server jar and settings of {}",
+        instances.join(", ")
+    );
+
+    if !std::io::stdin().is_terminal() {
+        warn!("{message}");
+        eprintln!("Warning: {message}");
+        return Ok(true);
+    }
+
+    Ok(Confirm::new()
+        .with_prompt(format!("{message}. Worlds are kept. Continue?"))
+        .default(false)
+        .interact()?)
+}
+
 #[instrument(err, ret(level = "debug"))]
-fn uninstall_impl(version: String) -> Result<()> {
-    app::uninstall_instance(version.parse()?).wrap_err("Error while uninstalling instance")?;
+async fn update_impl(instance: String, snapshot: bool) -> Result<()> {
+    let manifest = MANIFEST.get().expect("manifest not set");
+    let latest = if snapshot {
+        &manifest.latest.snapshot
+    } else {
+        &manifest.latest.release
+    };
+    let target = manifest
+        .versions
+        .iter()
+        .find(|v| &v.id == latest)
+        .ok_or_else(|| McdlError::VersionNotFound(latest.clone()))?;
+
+    let key = app::resolve_instance(&instance)?;
+    let current = META.lock().instances[&key].id.clone();
+
+    // anything released after the target is up to date, e.g. a snapshot newer than the latest release
+    let up_to_date = manifest
+        .versions
+        .iter()
+        .find(|v| v.id == current)
+        .map_or(current == target.id, |current| current >= target);
+    if up_to_date {
+        status!("Already up to date ({current})");
+        return Ok(());
+    }
+
+    status!("Updating {instance} from {current} to {}\n", target.id);
+    app::update_instance(&instance, target)
+        .await
+        .wrap_err("Error while updating instance")?;
 
     Ok(())
 }
 
 #[instrument(err, ret(level = "debug"))]
-async fn run_impl(version: String) -> Result<()> {
-    app::run_instance(version.parse()?)
+async fn verify_impl(instance: String) -> Result<()> {
+    let manifest = MANIFEST.get().expect("manifest not set");
+    let key = app::resolve_instance(&instance)?;
+    let id = META.lock().instances[&key].id.clone();
+    let version = manifest
+        .versions
+        .iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| McdlError::VersionNotFound(id.clone()))?;
+
+    let healthy = app::verify_instance(&key, version)
+        .await
+        .wrap_err("Error while verifying instance")?;
+    if !healthy {
+        return Err(eyre!("Instance {instance} has missing or corrupt files"));
+    }
+
+    status!("Instance {instance} is intact");
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn uninstall_impl(
+    versions: Vec<String>,
+    filter: Option<&str>,
+    options: UninstallOptions,
+    yes: bool,
+) -> Result<()> {
+    let Some(filter) = filter else {
+        app::uninstall_instances(versions, options)
+            .await
+            .wrap_err("Error while uninstalling instances")?;
+        return Ok(());
+    };
+
+    let filter = ListFilter::from_name(filter).expect("validated by clap");
+    let instances = META
+        .lock()
+        .instances
+        .iter()
+        .filter(|(_, instance)| filter.matches(&instance.id))
+        .map(|(key, _)| key.clone())
+        .sorted()
+        .collect_vec();
+
+    if instances.is_empty() {
+        println!("No matching instances installed");
+        return Ok(());
+    }
+    if !yes && !confirm_bulk_uninstall(&instances)? {
+        return Ok(());
+    }
+
+    // one failure shouldn't keep the rest installed
+    app::uninstall_instances(
+        instances.clone(),
+        UninstallOptions {
+            keep_going: true,
+            ..options
+        },
+    )
+    .await
+    .wrap_err("Error while uninstalling instances")?;
+    status!(
+        "Uninstalled {} instance{}: {}",
+        instances.len(),
+        if instances.len() == 1 { "" } else { "s" },
+        instances.join(", ")
+    );
+
+    Ok(())
+}
+
+/// Asks for confirmation before uninstalling many instances at once,
+/// refusing to go ahead without `--yes` when not running interactively
+fn confirm_bulk_uninstall(instances: &[String]) -> Result<bool> {
+    let message = format!(
+        "This will uninstall {}, including their worlds",
+        instances.join(", ")
+    );
+
+    if !std::io::stdin().is_terminal() {
+        return Err(eyre!("{message}, pass --yes to go ahead"));
+    }
+
+    Ok(Confirm::new()
+        .with_prompt(format!("{message}. Continue?"))
+        .default(false)
+        .interact()?)
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn run_impl(version: String, options: RunOptions) -> Result<()> {
+    app::run_instance(&version, options)
         .await
         .wrap_err("Error while running server")?;
 
     Ok(())
 }
 
+/// Reads console commands from a file, skipping blank lines and `#` comments
+fn read_commands(path: &Path) -> Result<Vec<String>> {
+    let commands = std::fs::read_to_string(path)
+        .wrap_err(format!("Failed to read commands from {}", path.display()))?;
+
+    Ok(commands
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Reads the versions to install from a file, resolving aliases and reporting
+/// every line that isn't a version in the manifest
+///
+/// With `strict`, any invalid line is an error. Otherwise only the valid versions are returned.
+fn read_version_file(path: &Path, strict: bool) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err(format!("Failed to read versions from {}", path.display()))?;
+    let manifest = MANIFEST.get().expect("manifest not set");
+
+    let mut versions = vec![];
+    let mut invalid = vec![];
+    for (line, entry) in parse_version_file(&contents) {
+        let version = match manifest.resolve_alias(entry) {
+            Some(resolved) => Ok(resolved.clone()),
+            None => entry.parse::<VersionNumber>(),
+        }
+        .and_then(|version| validate_version_number(&version).map(|()| version));
+
+        match version {
+            Ok(version) => versions.push(version.to_string()),
+            Err(e) => invalid.push(format!("  line {line}: `{entry}`: {e}")),
+        }
+    }
+
+    if invalid.is_empty() {
+        return Ok(versions);
+    }
+
+    let message = format!(
+        "{} invalid version{} in {}",
+        invalid.len(),
+        if invalid.len() == 1 { "" } else { "s" },
+        path.display()
+    );
+    if strict {
+        return Err(eyre!("{message}:\n{}", invalid.join("\n")));
+    }
+    warn!(
+        invalid = invalid.len(),
+        "Skipping invalid versions in version file"
+    );
+    eprintln!("Warning: {message}, skipping them:\n{}", invalid.join("\n"));
+    Ok(versions)
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn backup_impl(version: String, keep: Option<NonZeroUsize>) -> Result<()> {
+    app::backup_world(&version, keep)
+        .await
+        .wrap_err("Error while backing up world")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn export_impl(version: String, out: PathBuf) -> Result<()> {
+    app::export_instance(&version, &out)
+        .await
+        .wrap_err("Error while exporting instance")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn rename_impl(old: String, new: String) -> Result<()> {
+    app::rename_instance(&old, &new)
+        .await
+        .wrap_err("Error while renaming instance")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn import_impl(archive: PathBuf, name: Option<String>) -> Result<()> {
+    app::import_instance(&archive, name)
+        .await
+        .wrap_err("Error while importing instance")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn stop_impl(version: String) -> Result<()> {
+    app::stop_instance(&version)
+        .await
+        .wrap_err("Error while stopping server")?;
+
+    Ok(())
+}
+
 #[instrument(err, ret(level = "debug"))]
 fn locate_impl(what: WhatEnum) -> Result<()> {
     // TODO: pass directly
@@ -439,4 +2222,119 @@ fn locate_impl(what: WhatEnum) -> Result<()> {
     Ok(())
 }
 
+#[instrument(err, ret(level = "debug"))]
+async fn gc_impl(dry_run: bool) -> Result<()> {
+    app::gc(dry_run).await.wrap_err("Error while cleaning up")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn sync_impl(yes: bool) -> Result<()> {
+    let manifest = MANIFEST.get().expect("manifest not set");
+    app::sync(manifest, yes)
+        .await
+        .wrap_err("Error while syncing metadata")?;
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+fn clean_impl(all: bool, set_ttl: Option<u64>, set_log_retention: Option<u64>) -> Result<()> {
+    if let Some(ttl) = set_ttl {
+        let mut meta = META.lock();
+        meta.cache_ttl = Some(ttl);
+        meta.save()?;
+        eprintln!("Cache TTL set to {ttl}s");
+    }
+    if let Some(days) = set_log_retention {
+        let mut meta = META.lock();
+        meta.log_retention_days = Some(days);
+        meta.save()?;
+        eprintln!("Log files are now kept for {days} days");
+    }
+
+    // changing a setting shouldn't also empty the cache
+    if all || (set_ttl.is_none() && set_log_retention.is_none()) {
+        app::clean_cache(all).wrap_err("Error while cleaning the cache")?;
+    }
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn config_impl(action: ConfigAction) -> Result<()> {
+    match action {
+        ConfigAction::Show { version, json } => app::show_config(&version, json)
+            .await
+            .wrap_err("Error while showing instance settings")?,
+        ConfigAction::Property {
+            version,
+            key,
+            value,
+            get,
+        } => match (get, key, value) {
+            (Some(key), _, _) => app::get_property(&version, &key)
+                .await
+                .wrap_err("Error while reading server properties")?,
+            (None, Some(key), Some(value)) => app::set_property(&version, &key, &value)
+                .await
+                .wrap_err("Error while writing server properties")?,
+            _ => unreachable!("clap requires a key and value or --get"),
+        },
+        ConfigAction::PasteUrl { url } => {
+            let mut meta = META.lock();
+            meta.paste_url = Some(url.to_string());
+            meta.save()?;
+            eprintln!("Crash reports will be uploaded to {url}");
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+async fn jre_impl(action: JreAction) -> Result<()> {
+    match action {
+        JreAction::List => app::list_jres()
+            .await
+            .wrap_err("Error while listing JREs")?,
+        JreAction::Install { major } => app::install_standalone_jre(&JreSpec::new(major))
+            .await
+            .wrap_err("Error while installing JRE")?,
+        JreAction::Uninstall { major, force } => app::uninstall_jre(&JreSpec::new(major), force)
+            .await
+            .wrap_err("Error while uninstalling JRE")?,
+    }
+
+    Ok(())
+}
+
+#[instrument(err, ret(level = "debug"))]
+fn completions_impl(shell: Shell, install: bool) -> Result<()> {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+
+    if !install {
+        clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, name, &mut script);
+
+    let path = app::install_completions(shell, &script)
+        .wrap_err(format!("Error while installing {shell} completions"))?;
+    status!("Installed {shell} completions to {}", path.display());
+
+    if shell == Shell::Zsh {
+        println!(
+            "Make sure {} is in your `fpath` before `compinit` is called",
+            path.parent().expect("infallible").display()
+        );
+    }
+
+    Ok(())
+}
+
 /* end impls */
@@ -0,0 +1,85 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::parse::parse_memory_bytes;
+
+/// Heaps larger than this get Aikar's flags for large heaps
+const AIKAR_LARGE_HEAP: u64 = 12 << 30;
+
+/// A well-known set of JVM flags
+///
+/// The flags go before `java.args`, so explicit arguments take precedence
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FlagPreset {
+    /// No extra flags
+    #[default]
+    None,
+    /// Aikar's G1GC tuning for Minecraft servers
+    Aikar,
+}
+
+impl FlagPreset {
+    /// The preset's flags, some of which are scaled to the maximum heap size
+    pub fn args(self, max_memory: Option<&str>) -> Vec<String> {
+        match self {
+            FlagPreset::None => vec![],
+            FlagPreset::Aikar => aikar_flags(max_memory),
+        }
+    }
+}
+
+/// See <https://docs.papermc.io/paper/aikars-flags>
+fn aikar_flags(max_memory: Option<&str>) -> Vec<String> {
+    let large = max_memory
+        .and_then(|memory| parse_memory_bytes(memory).ok())
+        .is_some_and(|bytes| bytes > AIKAR_LARGE_HEAP);
+    let (new_size, max_new_size, region_size, reserve, occupancy) = if large {
+        (40, 50, "16M", 15, 20)
+    } else {
+        (30, 40, "8M", 20, 15)
+    };
+
+    [
+        "-XX:+UseG1GC",
+        "-XX:+ParallelRefProcEnabled",
+        "-XX:MaxGCPauseMillis=200",
+        "-XX:+UnlockExperimentalVMOptions",
+        "-XX:+DisableExplicitGC",
+        "-XX:+AlwaysPreTouch",
+        &format!("-XX:G1NewSizePercent={new_size}"),
+        &format!("-XX:G1MaxNewSizePercent={max_new_size}"),
+        &format!("-XX:G1HeapRegionSize={region_size}"),
+        &format!("-XX:G1ReservePercent={reserve}"),
+        "-XX:G1HeapWastePercent=5",
+        "-XX:G1MixedGCCountTarget=4",
+        &format!("-XX:InitiatingHeapOccupancyPercent={occupancy}"),
+        "-XX:G1MixedGCLiveThresholdPercent=90",
+        "-XX:G1RSetUpdatingPauseTimePercent=5",
+        "-XX:SurvivorRatio=32",
+        "-XX:+PerfDisableSharedMem",
+        "-XX:MaxTenuringThreshold=1",
+        "-Dusing.aikars.flags=https://mcflags.emc.gs",
+        "-Daikars.new.flags=true",
+    ]
+    .map(ToString::to_string)
+    .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aikar_flags_scale_with_heap() {
+        assert!(FlagPreset::None.args(Some("4G")).is_empty());
+
+        let small = FlagPreset::Aikar.args(Some("10G"));
+        assert!(small.contains(&"-XX:G1HeapRegionSize=8M".to_string()));
+        assert_eq!(FlagPreset::Aikar.args(None), small);
+
+        let large = FlagPreset::Aikar.args(Some("16384M"));
+        assert!(large.contains(&"-XX:G1HeapRegionSize=16M".to_string()));
+        assert!(large.contains(&"-XX:G1NewSizePercent=40".to_string()));
+    }
+}
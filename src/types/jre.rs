@@ -0,0 +1,408 @@
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// The oldest Java version Adoptium has builds for
+pub(crate) const MIN_JRE_MAJOR: u8 = 8;
+
+/// The Java versions Adoptium has builds for, as returned by `/v3/info/available_releases`
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct AvailableReleases {
+    /// Every feature version with a GA build, oldest first
+    pub available_releases: Vec<u8>,
+    /// The feature versions that are LTS releases, oldest first
+    pub available_lts_releases: Vec<u8>,
+}
+
+/// A release from Adoptium's `/v3/assets` endpoints, with the builds that matched the query
+#[derive(Debug, Deserialize)]
+pub(crate) struct JreAssets {
+    pub binaries: Vec<JreBinary>,
+}
+
+/// A build in [`JreAssets`]
+#[derive(Debug, Deserialize)]
+pub(crate) struct JreBinary {
+    pub package: JrePackage,
+}
+
+/// The archive of a [`JreBinary`]
+#[derive(Debug, Deserialize)]
+pub(crate) struct JrePackage {
+    /// The file name of the archive
+    pub name: String,
+    /// The SHA-256 of the archive, as a hex string
+    pub checksum: String,
+}
+
+/// The heap size variant of an Adoptium JVM build
+///
+/// `Large` builds are tuned for heaps larger than 57GB
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HeapSize {
+    #[default]
+    Normal,
+    Large,
+}
+
+impl HeapSize {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HeapSize::Normal => "normal",
+            HeapSize::Large => "large",
+        }
+    }
+}
+
+impl Display for HeapSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The JVM implementation of an Adoptium build
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JvmImpl {
+    #[default]
+    Hotspot,
+    Openj9,
+}
+
+impl JvmImpl {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JvmImpl::Hotspot => "hotspot",
+            JvmImpl::Openj9 => "openj9",
+        }
+    }
+}
+
+impl Display for JvmImpl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The image type of an Adoptium build
+///
+/// A `Jdk` also includes development tools like `jcmd` and `jfr`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ImageType {
+    #[default]
+    Jre,
+    Jdk,
+}
+
+impl ImageType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageType::Jre => "jre",
+            ImageType::Jdk => "jdk",
+        }
+    }
+}
+
+impl Display for ImageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The vendor of an Adoptium build
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Vendor {
+    #[default]
+    Eclipse,
+    Alibaba,
+    Ibm,
+}
+
+impl Vendor {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Vendor::Eclipse => "eclipse",
+            Vendor::Alibaba => "alibaba",
+            Vendor::Ibm => "ibm",
+        }
+    }
+}
+
+impl Display for Vendor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A CPU architecture to download a JRE for, named like [`std::env::consts::ARCH`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+pub(crate) enum Arch {
+    #[serde(rename = "x86_64")]
+    #[value(name = "x86_64")]
+    X86_64,
+    #[serde(rename = "x86")]
+    #[value(name = "x86")]
+    X86,
+    #[serde(rename = "aarch64")]
+    #[value(name = "aarch64")]
+    Aarch64,
+    #[serde(rename = "arm")]
+    #[value(name = "arm")]
+    Arm,
+    #[serde(rename = "powerpc64")]
+    #[value(name = "powerpc64")]
+    Powerpc64,
+    #[serde(rename = "s390x")]
+    #[value(name = "s390x")]
+    S390x,
+    #[serde(rename = "riscv64")]
+    #[value(name = "riscv64")]
+    Riscv64,
+}
+
+impl Arch {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::X86 => "x86",
+            Arch::Aarch64 => "aarch64",
+            Arch::Arm => "arm",
+            Arch::Powerpc64 => "powerpc64",
+            Arch::S390x => "s390x",
+            Arch::Riscv64 => "riscv64",
+        }
+    }
+}
+
+impl Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Identifies a JRE build to download from Adoptium
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JreSpec {
+    pub major: u8,
+    pub heap_size: HeapSize,
+    pub jvm_impl: JvmImpl,
+    pub image_type: ImageType,
+    pub vendor: Vendor,
+    /// The architecture to download the build for, or this machine's if `None`
+    pub arch: Option<Arch>,
+    /// A full version to pin, e.g. `17.0.9+9`, or the latest GA release if `None`
+    pub release: Option<String>,
+}
+
+impl JreSpec {
+    pub fn new(major: u8) -> Self {
+        Self {
+            major,
+            heap_size: HeapSize::default(),
+            jvm_impl: JvmImpl::default(),
+            image_type: ImageType::default(),
+            vendor: Vendor::default(),
+            arch: None,
+            release: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_heap_size(mut self, heap_size: HeapSize) -> Self {
+        self.heap_size = heap_size;
+        self
+    }
+
+    #[must_use]
+    pub fn with_jvm_impl(mut self, jvm_impl: JvmImpl) -> Self {
+        self.jvm_impl = jvm_impl;
+        self
+    }
+
+    #[must_use]
+    pub fn with_image_type(mut self, image_type: ImageType) -> Self {
+        self.image_type = image_type;
+        self
+    }
+
+    #[must_use]
+    pub fn with_vendor(mut self, vendor: Vendor) -> Self {
+        self.vendor = vendor;
+        self
+    }
+
+    #[must_use]
+    pub fn with_arch(mut self, arch: Option<Arch>) -> Self {
+        self.arch = arch;
+        self
+    }
+
+    #[must_use]
+    pub fn with_release(mut self, release: Option<String>) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// The name of the pinned release on Adoptium, e.g. `jdk-17.0.9+9` or `jdk8u392-b08`
+    pub fn release_name(&self) -> Option<String> {
+        self.release.as_ref().map(|release| {
+            if release.contains('u') {
+                format!("jdk{release}")
+            } else {
+                format!("jdk-{release}")
+            }
+        })
+    }
+
+    /// The architecture the build is for, which is this machine's unless overridden
+    pub fn target_arch(&self) -> &'static str {
+        self.arch.map_or(std::env::consts::ARCH, Arch::as_str)
+    }
+
+    /// The parameters that differ from the default build, as `(name, value)` pairs
+    pub fn non_default_params(&self) -> Vec<(&'static str, &str)> {
+        let mut params = vec![];
+        if self.heap_size != HeapSize::default() {
+            params.push(("heap_size", self.heap_size.as_str()));
+        }
+        if self.jvm_impl != JvmImpl::default() {
+            params.push(("jvm_impl", self.jvm_impl.as_str()));
+        }
+        if self.image_type != ImageType::default() {
+            params.push(("image_type", self.image_type.as_str()));
+        }
+        if self.vendor != Vendor::default() {
+            params.push(("vendor", self.vendor.as_str()));
+        }
+        if self.target_arch() != std::env::consts::ARCH {
+            params.push(("arch", self.target_arch()));
+        }
+        if let Some(release) = &self.release {
+            params.push(("release", release));
+        }
+        params
+    }
+
+    /// The name of the JRE's directory and metadata entry
+    ///
+    /// Default builds are keyed by their major version alone,
+    /// which keeps JREs installed by older versions of mcdl usable
+    pub fn key(&self) -> String {
+        let mut parts = vec![self.major.to_string()];
+        parts.extend(
+            self.non_default_params()
+                .into_iter()
+                .map(|(_, value)| value.to_string()),
+        );
+        parts.join("-")
+    }
+}
+
+/// The major version of a full JRE version, e.g. 17 for `17.0.9+9`
+pub(crate) fn release_major(release: &str) -> Option<u8> {
+    let end = release
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(release.len());
+    release[..end].parse().ok()
+}
+
+impl Display for JreSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.major)?;
+
+        let params = self.non_default_params();
+        if !params.is_empty() {
+            let qualifiers = params
+                .into_iter()
+                .map(|(name, value)| match name {
+                    "heap_size" => format!("{value} heap"),
+                    "release" => format!("pinned to {value}"),
+                    _ => value.to_string(),
+                })
+                .collect::<Vec<_>>();
+            write!(f, " ({})", qualifiers.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jre_spec_key() {
+        assert_eq!(JreSpec::new(17).key(), "17");
+        assert_eq!(
+            JreSpec::new(17).with_heap_size(HeapSize::Large).key(),
+            "17-large"
+        );
+        assert_eq!(
+            JreSpec::new(21)
+                .with_heap_size(HeapSize::Large)
+                .with_jvm_impl(JvmImpl::Openj9)
+                .with_image_type(ImageType::Jdk)
+                .with_vendor(Vendor::Ibm)
+                .key(),
+            "21-large-openj9-jdk-ibm"
+        );
+
+        let foreign = if std::env::consts::ARCH == "s390x" {
+            Arch::Riscv64
+        } else {
+            Arch::S390x
+        };
+        assert_eq!(
+            JreSpec::new(21).with_arch(Some(foreign)).key(),
+            format!("21-{foreign}")
+        );
+        assert_eq!(
+            JreSpec::new(21)
+                .with_arch(Some(Arch::X86_64))
+                .with_arch(None)
+                .key(),
+            "21"
+        );
+    }
+
+    #[test]
+    fn jre_spec_display() {
+        assert_eq!(JreSpec::new(17).to_string(), "17");
+        assert_eq!(
+            JreSpec::new(17)
+                .with_heap_size(HeapSize::Large)
+                .with_jvm_impl(JvmImpl::Openj9)
+                .to_string(),
+            "17 (large heap, openj9)"
+        );
+        assert_eq!(
+            JreSpec::new(17)
+                .with_release(Some("17.0.9+9".to_string()))
+                .to_string(),
+            "17 (pinned to 17.0.9+9)"
+        );
+    }
+
+    #[test]
+    fn jre_release_names() {
+        let pinned = |release: &str| JreSpec::new(0).with_release(Some(release.to_string()));
+        assert_eq!(JreSpec::new(17).release_name(), None);
+        assert_eq!(
+            pinned("17.0.9+9").release_name().as_deref(),
+            Some("jdk-17.0.9+9")
+        );
+        assert_eq!(
+            pinned("8u392-b08").release_name().as_deref(),
+            Some("jdk8u392-b08")
+        );
+        assert_eq!(pinned("17.0.9+9").key(), "0-17.0.9+9");
+
+        assert_eq!(release_major("17.0.9+9"), Some(17));
+        assert_eq!(release_major("21+35"), Some(21));
+        assert_eq!(release_major("8u392-b08"), Some(8));
+    }
+}
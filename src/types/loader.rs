@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// The mod loader to run a server with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Loader {
+    #[default]
+    Vanilla,
+    Fabric,
+    Quilt,
+}
+
+impl Loader {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Loader::Vanilla => "vanilla",
+            Loader::Fabric => "fabric",
+            Loader::Quilt => "quilt",
+        }
+    }
+}
+
+impl Display for Loader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A loader or installer version from a loader's meta API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct LoaderVersion {
+    pub version: String,
+    pub stable: bool,
+}
+
+/// A loader version from the Quilt meta API, which doesn't say whether it's stable
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct QuiltVersion {
+    pub version: String,
+}
+
+impl From<QuiltVersion> for LoaderVersion {
+    fn from(v: QuiltVersion) -> Self {
+        // pre-releases are versioned like `0.26.0-beta.1`
+        let stable = !v.version.contains('-');
+        Self {
+            version: v.version,
+            stable,
+        }
+    }
+}
+
+/// A loader compatible with a game version, from `versions/loader/<game_version>`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct LoaderEntry<T> {
+    pub loader: T,
+}
+
+/// The launch profile of a loader for a server, from `versions/loader/<game>/<loader>/server/json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ServerProfile {
+    /// The class the launcher hands over to once the libraries are loaded
+    pub main_class: String,
+    pub libraries: Vec<ProfileLibrary>,
+}
+
+/// A library of a [`ServerProfile`], named by its Maven coordinates
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ProfileLibrary {
+    /// The coordinates, e.g. `org.quiltmc:quilt-loader:0.26.0`
+    pub name: String,
+    /// The Maven repository the library is in
+    pub url: String,
+}
+
+impl ProfileLibrary {
+    /// The path of the library in its repository, e.g. `org/quiltmc/quilt-loader/0.26.0/quilt-loader-0.26.0.jar`
+    pub fn path(&self) -> Option<String> {
+        let mut parts = self.name.split(':');
+        let (group, artifact, version) = (parts.next()?, parts.next()?, parts.next()?);
+        let classifier = parts.next().map(|c| format!("-{c}")).unwrap_or_default();
+
+        Some(format!(
+            "{}/{artifact}/{version}/{artifact}-{version}{classifier}.jar",
+            group.replace('.', "/")
+        ))
+    }
+
+    /// The URL to download the library from
+    pub fn download_url(&self) -> Option<String> {
+        Some(format!(
+            "{}/{}",
+            self.url.trim_end_matches('/'),
+            self.path()?
+        ))
+    }
+}
+
+/// The newest stable version, relying on the meta API listing versions newest first
+pub(crate) fn latest_stable(versions: &[LoaderVersion]) -> Option<&LoaderVersion> {
+    versions.iter().find(|v| v.stable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_stable_loader() {
+        let json = r#"[
+            {"loader": {"separator": ".", "build": 4, "maven": "net.fabricmc:fabric-loader:0.16.0-beta.1", "version": "0.16.0-beta.1", "stable": false}},
+            {"loader": {"separator": ".", "build": 3, "maven": "net.fabricmc:fabric-loader:0.15.11", "version": "0.15.11", "stable": true}},
+            {"loader": {"separator": ".", "build": 2, "maven": "net.fabricmc:fabric-loader:0.15.10", "version": "0.15.10", "stable": true}}
+        ]"#;
+        let entries: Vec<LoaderEntry<LoaderVersion>> = serde_json::from_str(json).unwrap();
+        let loaders = entries.into_iter().map(|e| e.loader).collect::<Vec<_>>();
+
+        assert_eq!(latest_stable(&loaders).unwrap().version, "0.15.11");
+        assert!(latest_stable(&loaders[..1]).is_none());
+    }
+
+    #[test]
+    fn latest_stable_quilt_loader() {
+        let json = r#"[
+            {"loader": {"separator": ".", "build": 2, "maven": "org.quiltmc:quilt-loader:0.26.0-beta.1", "version": "0.26.0-beta.1"}},
+            {"loader": {"separator": ".", "build": 1, "maven": "org.quiltmc:quilt-loader:0.25.0", "version": "0.25.0"}}
+        ]"#;
+        let entries: Vec<LoaderEntry<QuiltVersion>> = serde_json::from_str(json).unwrap();
+        let loaders = entries
+            .into_iter()
+            .map(|e| e.loader.into())
+            .collect::<Vec<LoaderVersion>>();
+
+        assert_eq!(latest_stable(&loaders).unwrap().version, "0.25.0");
+    }
+
+    #[test]
+    fn library_path() {
+        let library = ProfileLibrary {
+            name: "org.quiltmc:quilt-loader:0.26.0".to_string(),
+            url: "https://maven.quiltmc.org/repository/release/".to_string(),
+        };
+        assert_eq!(
+            library.download_url().unwrap(),
+            "https://maven.quiltmc.org/repository/release/org/quiltmc/quilt-loader/0.26.0/quilt-loader-0.26.0.jar"
+        );
+
+        let library = ProfileLibrary {
+            name: "org.ow2.asm:asm:9.6:sources".to_string(),
+            url: String::new(),
+        };
+        assert_eq!(
+            library.path().unwrap(),
+            "org/ow2/asm/asm/9.6/asm-9.6-sources.jar"
+        );
+        assert!(
+            ProfileLibrary {
+                name: "invalid".to_string(),
+                url: String::new()
+            }
+            .path()
+            .is_none()
+        );
+    }
+}
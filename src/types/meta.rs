@@ -2,17 +2,28 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 
-use color_eyre::eyre::{Result, WrapErr};
+use color_eyre::eyre::{Report, Result, WrapErr, eyre};
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use tokio::fs;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::AsyncReadExt;
 use tracing::{debug, instrument};
 
+use crate::common::PROGRESS;
+use crate::types::flags::FlagPreset;
+use crate::types::jre::{
+    Arch, HeapSize, ImageType, JreSpec, JvmImpl, MIN_JRE_MAJOR, Vendor, release_major,
+};
+use crate::types::loader::Loader;
 use crate::types::version::VersionNumber;
+use crate::utils::atomic::{write_atomic, write_atomic_async};
+use crate::utils::loader::{Fabric, LoaderApi, Quilt};
+use crate::utils::parse::{parse_jre_version, parse_memory_bytes};
 
-const DEFAULT_JVM_ARGS: &[&str] = &["-Xms4G", "-Xmx4G"];
+const DEFAULT_JVM_ARGS: &[&str] = &[];
+const DEFAULT_MEMORY: &str = "4G";
 const DEFAULT_SERVER_ARGS: &[&str] = &["--nogui"];
+const DEFAULT_BACKUPS_KEPT: usize = 5;
 
 pub(crate) trait ToArgs: Sized {
     fn to_args(self) -> Vec<String>;
@@ -45,6 +56,33 @@ pub(crate) struct InstanceJavaSettings {
     pub version: u8,
     /// The arguments to pass to the JVM
     pub args: Vec<String>,
+    /// The heap size variant of the JVM build, either `normal` or `large`
+    #[serde(default)]
+    pub heap_size: HeapSize,
+    /// The initial heap size (`-Xms`), e.g. `2G` or `512M`
+    #[serde(default)]
+    pub min_memory: Option<String>,
+    /// The maximum heap size (`-Xmx`), e.g. `4G`
+    #[serde(default)]
+    pub max_memory: Option<String>,
+    /// The JVM implementation, either `hotspot` or `openj9`
+    #[serde(default)]
+    pub jvm_impl: JvmImpl,
+    /// The image type of the JVM build, either `jre` or `jdk`
+    #[serde(default)]
+    pub image_type: ImageType,
+    /// The vendor of the JVM build, one of `eclipse`, `alibaba`, or `ibm`
+    #[serde(default)]
+    pub vendor: Vendor,
+    /// A well-known set of flags to pass to the JVM before [`Self::args`], `none` or `aikar`
+    #[serde(default)]
+    pub flag_preset: FlagPreset,
+    /// The architecture to download the JRE for, if not this machine's
+    #[serde(default)]
+    pub arch: Option<Arch>,
+    /// A full JRE version to use, e.g. `17.0.9+9`, instead of the latest GA release
+    #[serde(default)]
+    pub release: Option<String>,
 }
 
 impl InstanceJavaSettings {
@@ -52,8 +90,49 @@ impl InstanceJavaSettings {
         Self {
             version,
             args: DEFAULT_JVM_ARGS.to_args(),
+            heap_size: HeapSize::default(),
+            min_memory: Some(DEFAULT_MEMORY.to_string()),
+            max_memory: Some(DEFAULT_MEMORY.to_string()),
+            jvm_impl: JvmImpl::default(),
+            image_type: ImageType::default(),
+            vendor: Vendor::default(),
+            flag_preset: FlagPreset::default(),
+            arch: None,
+            release: None,
         }
     }
+
+    /// The `-Xms` and `-Xmx` arguments for the memory settings, which go before [`Self::args`]
+    pub fn memory_args(&self) -> Vec<String> {
+        let min = self.min_memory.iter().map(|m| format!("-Xms{m}"));
+        let max = self.max_memory.iter().map(|m| format!("-Xmx{m}"));
+        min.chain(max).collect()
+    }
+
+    /// Removes the arguments in [`Self::args`] starting with `prefix`, e.g. `-Xmx`, returning them
+    pub fn remove_args(&mut self, prefix: &str) -> Vec<String> {
+        let (removed, kept) = std::mem::take(&mut self.args)
+            .into_iter()
+            .partition(|arg| arg.starts_with(prefix));
+        self.args = kept;
+        removed
+    }
+
+    /// The flags of the flag preset, which go between the memory arguments and [`Self::args`]
+    pub fn preset_args(&self) -> Vec<String> {
+        self.flag_preset.args(self.max_memory.as_deref())
+    }
+
+    /// The JRE build these settings require
+    pub fn jre_spec(&self) -> JreSpec {
+        JreSpec::new(self.version)
+            .with_heap_size(self.heap_size)
+            .with_jvm_impl(self.jvm_impl)
+            .with_image_type(self.image_type)
+            .with_vendor(self.vendor)
+            .with_arch(self.arch)
+            .with_release(self.release.clone())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -62,6 +141,22 @@ pub(crate) struct InstanceServerSettings {
     pub jar: PathBuf,
     /// The arguments to pass to the server
     pub args: Vec<String>,
+    /// A command and arguments to launch Java with, e.g. `["nice", "-n", "10"]`
+    ///
+    /// Empty by default, which runs Java directly
+    #[serde(default)]
+    pub launch_wrapper: Vec<String>,
+    /// The port to run the server on, written to `server.properties` before it starts
+    ///
+    /// Unset by default, which leaves `server.properties` alone
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// The mod loader the jar launches, `vanilla`, `fabric`, or `quilt`
+    #[serde(default)]
+    pub loader: Loader,
+    /// The version of the mod loader, unset for `vanilla`
+    #[serde(default)]
+    pub loader_version: Option<String>,
 }
 
 impl Default for InstanceServerSettings {
@@ -69,16 +164,97 @@ impl Default for InstanceServerSettings {
         Self {
             jar: PathBuf::from("server.jar"),
             args: DEFAULT_SERVER_ARGS.to_args(),
+            launch_wrapper: Vec::new(),
+            port: None,
+            loader: Loader::default(),
+            loader_version: None,
+        }
+    }
+}
+
+impl InstanceServerSettings {
+    /// The mod loader whose launcher [`jar`](Self::jar) is, or vanilla if it's not a launcher
+    fn launched_loader(&self) -> Loader {
+        let jar = self.jar.file_name().unwrap_or_default();
+        if jar == Fabric::LAUNCHER_JAR {
+            Loader::Fabric
+        } else if jar == Quilt::LAUNCHER_JAR {
+            Loader::Quilt
+        } else {
+            Loader::Vanilla
+        }
+    }
+
+    /// The vanilla server jar, which is [`jar`](Self::jar) unless that's the launcher of a mod loader
+    pub fn vanilla_jar(&self) -> &Path {
+        match self.loader {
+            Loader::Vanilla => &self.jar,
+            // the launchers run `server.jar` next to them
+            Loader::Fabric | Loader::Quilt => Path::new("server.jar"),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct InstanceBackupSettings {
+    /// Whether to back up the world every time the server is started
+    pub on_run: bool,
+    /// The number of backups to keep, the oldest are removed first
+    pub keep: usize,
+}
+
+impl Default for InstanceBackupSettings {
+    fn default() -> Self {
+        Self {
+            on_run: false,
+            keep: DEFAULT_BACKUPS_KEPT,
+        }
+    }
+}
+
+/// Describes a settings parse error by the key it's in and its line,
+/// rather than the byte offsets `toml` reports
+fn describe_toml_error(contents: &str, error: &toml::de::Error) -> String {
+    let message = error.message().trim_end();
+    let Some(span) = error.span() else {
+        return message.to_string();
+    };
+
+    let start = span.start.min(contents.len());
+    let line = contents[..start].matches('\n').count() + 1;
+    let line_start = contents[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = contents[start..]
+        .find('\n')
+        .map_or(contents.len(), |i| start + i);
+    let table = contents[..line_end]
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| l.starts_with('[') && l.ends_with(']'))
+        .map(|l| l.trim_matches(['[', ']']).trim());
+    let key = contents[line_start..]
+        .split_once('=')
+        .map(|(key, _)| key.trim())
+        .filter(|key| !key.is_empty() && !key.contains('\n'));
+
+    match (table, key) {
+        (Some(table), Some(key)) => format!("`{table}.{key}` on line {line}: {message}"),
+        (None, Some(key)) => format!("`{key}` on line {line}: {message}"),
+        (Some(table), None) => format!("[{table}] on line {line}: {message}"),
+        (None, None) => format!("Line {line}: {message}"),
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct InstanceSettings {
     /// The settings for the JVM
     pub java: InstanceJavaSettings,
     /// The settings relating to the server
     pub server: InstanceServerSettings,
+    /// The settings for world backups
+    #[serde(default)]
+    pub backup: InstanceBackupSettings,
 }
 
 impl InstanceSettings {
@@ -86,6 +262,7 @@ impl InstanceSettings {
         Self {
             java: InstanceJavaSettings::new(version),
             server: InstanceServerSettings::default(),
+            backup: InstanceBackupSettings::default(),
         }
     }
 
@@ -103,29 +280,124 @@ impl InstanceSettings {
             .await
             .wrap_err(format!("Error reading settings at {}", path.display()))?;
 
-        let settings: Self = toml::from_str(&contents)
+        let mut settings: Self = toml::from_str(&contents)
+            .map_err(|e| {
+                let summary = describe_toml_error(&contents, &e);
+                Report::new(e).wrap_err(summary)
+            })
             .wrap_err(format!("Error parsing settings at {}", path.display()))?;
+        settings.infer_missing_loader(&contents);
+
+        for key in settings.unknown_keys(&contents) {
+            PROGRESS.suspend(|| {
+                eprintln!(
+                    "Warning: unknown setting `{key}` in {}, ignoring it",
+                    path.display()
+                );
+            });
+        }
+        settings
+            .validate()
+            .wrap_err(format!("Invalid settings at {}", path.display()))?;
 
         Ok(settings)
     }
 
+    /// Checks the constraints the types of the settings can't express
+    pub fn validate(&self) -> Result<()> {
+        let java = &self.java;
+        if java.version < MIN_JRE_MAJOR {
+            return Err(eyre!(
+                "`java.version` must be {MIN_JRE_MAJOR} or newer, the oldest Java with Adoptium builds (got: {})",
+                java.version
+            ));
+        }
+
+        let memory = |key: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|m| parse_memory_bytes(m).wrap_err(format!("`java.{key}` is invalid")))
+                .transpose()
+        };
+        let min = memory("min_memory", &java.min_memory)?;
+        let max = memory("max_memory", &java.max_memory)?;
+        if let (Some(min), Some(max)) = (min, max)
+            && max < min
+        {
+            return Err(eyre!(
+                "`java.max_memory` ({}) must be at least `java.min_memory` ({})",
+                java.max_memory.as_deref().unwrap_or_default(),
+                java.min_memory.as_deref().unwrap_or_default()
+            ));
+        }
+
+        if let Some(release) = &java.release {
+            let release = parse_jre_version(release).wrap_err("`java.release` is invalid")?;
+            if release_major(&release) != Some(java.version) {
+                return Err(eyre!(
+                    "`java.release` ({release}) is not a release of Java {}, set by `java.version`",
+                    java.version
+                ));
+            }
+        }
+
+        // the backup made before a run would be removed right away
+        if self.backup.keep == 0 {
+            return Err(eyre!("`backup.keep` must be at least 1"));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the mod loader from the server jar if `contents` doesn't have `server.loader`,
+    /// like settings written before the loader was recorded
+    fn infer_missing_loader(&mut self, contents: &str) {
+        let has_loader = toml::from_str::<toml::Table>(contents)
+            .is_ok_and(|raw| raw.get("server").and_then(|s| s.get("loader")).is_some());
+        if !has_loader {
+            self.server.loader = self.server.launched_loader();
+        }
+    }
+
+    /// The dotted keys in `contents` that don't match any setting, e.g. misspelled ones
+    fn unknown_keys(&self, contents: &str) -> Vec<String> {
+        fn diff(raw: &toml::Table, known: &toml::Table, prefix: &str, out: &mut Vec<String>) {
+            for (key, value) in raw {
+                let path = format!("{prefix}{key}");
+                match (value, known.get(key)) {
+                    (_, None) => out.push(path),
+                    (toml::Value::Table(raw), Some(toml::Value::Table(known))) => {
+                        diff(raw, known, &format!("{path}."), out);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // every key that was read is written back, so anything else was ignored
+        let (Ok(raw), Ok(known)) = (
+            toml::from_str::<toml::Table>(contents),
+            toml::Table::try_from(self),
+        ) else {
+            return Vec::new();
+        };
+        let mut unknown = Vec::new();
+        diff(&raw, &known, "", &mut unknown);
+        unknown
+    }
+
     #[instrument(err, ret(level = "debug"), skip(self))]
     pub async fn save(&self, path: impl AsRef<Path> + Debug) -> Result<()> {
         debug!("Saving instance settings");
 
         let path = path.as_ref();
         fs::create_dir_all(path.parent().expect("infallible")).await?;
-        let mut file = fs::File::create(path).await.wrap_err(format!(
-            "Error creating settings file at {}",
-            path.display()
-        ))?;
-
         let mut contents =
             "# This file is automatically generated\n# Only edit if you know what you're doing!\n\n"
                 .to_string();
         contents.push_str(&toml::to_string(self)?);
 
-        file.write_all(contents.as_bytes()).await.wrap_err(format!(
+        write_atomic_async(path, contents).await.wrap_err(format!(
             "Error writing settings to file at {}",
             path.display()
         ))?;
@@ -140,6 +412,27 @@ pub(crate) struct InstanceMeta {
     pub id: VersionNumber,
     pub files: Vec<PathBuf>,
     pub jre: u8, // String?
+    /// Hashes of the store entries this instance links to
+    #[serde(default)]
+    pub store_entries: Vec<String>,
+    /// Paths to world backups, oldest first
+    #[serde(default)]
+    pub backups: Vec<PathBuf>,
+    /// The name the instance was installed with, if any
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The URL the server jar was downloaded from, if it was installed from Mojang
+    #[serde(default)]
+    pub server_url: Option<String>,
+    /// The SHA-1 of the server jar, if it was installed from Mojang
+    #[serde(default)]
+    pub server_sha1: Option<String>,
+    /// The SHA-1 of the client jar, if it was installed with `--client`
+    #[serde(default)]
+    pub client_sha1: Option<String>,
+    /// The directory the instance was installed in with `--output-dir`, if any
+    #[serde(default)]
+    pub location: Option<PathBuf>,
 }
 
 impl InstanceMeta {
@@ -148,9 +441,21 @@ impl InstanceMeta {
             id,
             files: Vec::new(),
             jre,
+            store_entries: Vec::new(),
+            backups: Vec::new(),
+            name: None,
+            server_url: None,
+            server_sha1: None,
+            client_sha1: None,
+            location: None,
         }
     }
 
+    /// The key of the instance in [`AppMeta::instances`], its name or else its version
+    pub fn key(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.id.to_string())
+    }
+
     #[instrument(skip(self, file), fields(id = %self.id))]
     pub fn add_file(&mut self, file: &Path) {
         debug!(?file, "Adding file");
@@ -162,14 +467,83 @@ impl InstanceMeta {
         debug!(?file, "Removing file");
         self.files.retain(|f| f != file);
     }
+
+    /// The tracked files that no longer exist on disk
+    pub fn missing_files(&self) -> Vec<&PathBuf> {
+        self.files.iter().filter(|f| !f.exists()).collect()
+    }
+
+    /// Points the tracked files and backups under `from` to the same paths under `to`,
+    /// after they were moved there
+    pub fn replace_path_prefix(&mut self, from: &Path, to: &Path) {
+        for path in self.files.iter_mut().chain(&mut self.backups) {
+            // joining an empty path would add a trailing separator
+            match path.strip_prefix(from) {
+                Ok(rest) if rest.as_os_str().is_empty() => *path = to.to_path_buf(),
+                Ok(rest) => *path = to.join(rest),
+                Err(_) => {}
+            }
+        }
+    }
+
+    #[instrument(skip(self), fields(id = %self.id))]
+    pub fn add_store_entry(&mut self, hash: &str) {
+        debug!("Adding store entry");
+        self.store_entries.push(hash.to_string());
+    }
+}
+
+/// Describes an instance exported with `mcdl export`,
+/// stored alongside it in the archive as [`ExportManifest::FILE_NAME`]
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ExportManifest {
+    /// The key the instance was exported from, which its files are stored under
+    pub name: String,
+    pub version: VersionNumber,
+    /// The major version of the JRE the instance needs
+    pub jre: u8,
+}
+
+impl ExportManifest {
+    pub const FILE_NAME: &str = "mcdl-export.json";
+    pub const SETTINGS_FILE_NAME: &str = "settings.toml";
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub(crate) struct AppMeta {
     // keyed by id for now, possibly changed later to allow for multiple instances with the same version
     pub instances: HashMap<String, InstanceMeta>,
-    pub installed_jres: HashSet<u8>, // String?
+    /// Keys of the installed JREs (see [`JreSpec::key`])
+    #[serde(deserialize_with = "deserialize_jre_keys")]
+    pub installed_jres: HashSet<String>,
     path: PathBuf,
+    /// Number of instances referencing each store entry, keyed by hash
+    #[serde(default)]
+    pub store_refs: HashMap<String, u32>,
+    /// The filter `list` uses when none is given, e.g. `snapshot`
+    #[serde(default)]
+    pub default_list_filter: Option<String>,
+    /// How long cached responses stay fresh, in seconds
+    ///
+    /// Overridden by `MCDL_CACHE_TTL`
+    #[serde(default)]
+    pub cache_ttl: Option<u64>,
+    /// The architecture each installed JRE was downloaded for, keyed like `installed_jres`
+    ///
+    /// JREs installed before this was recorded have no entry.
+    #[serde(default)]
+    pub jre_arches: HashMap<String, String>,
+    /// The mclogs-compatible endpoint crash reports are uploaded to, instead of mclo.gs
+    #[serde(default)]
+    pub paste_url: Option<String>,
+    /// How many days log files are kept for
+    ///
+    /// Overridden by `MCDL_LOG_RETENTION_DAYS`
+    #[serde(default)]
+    pub log_retention_days: Option<u64>,
+    /// Keys of the JREs installed with `jre install`, which are kept even if no instance uses them
+    #[serde(default)]
+    pub standalone_jres: HashSet<String>,
 }
 
 impl AppMeta {
@@ -178,6 +552,13 @@ impl AppMeta {
             instances: HashMap::new(),
             installed_jres: HashSet::new(),
             path,
+            store_refs: HashMap::new(),
+            default_list_filter: None,
+            cache_ttl: None,
+            jre_arches: HashMap::new(),
+            paste_url: None,
+            log_retention_days: None,
+            standalone_jres: HashSet::new(),
         }
     }
 
@@ -208,11 +589,39 @@ impl AppMeta {
         let data = rmp_serde::to_vec(self)
             .wrap_err(format!("Error serializing meta at {}", path.display()))?;
 
-        std::fs::write(path, data).wrap_err(format!("Error writing meta at {}", path.display()))?;
+        write_atomic(path, data).wrap_err(format!("Error writing meta at {}", path.display()))?;
 
         Ok(())
     }
 
+    /// Takes an exclusive lock on the file at `path`, which is held until the returned file is dropped
+    ///
+    /// Waits for the lock if another process holds it, calling `on_wait` first.
+    pub fn lock(path: impl AsRef<Path>, on_wait: impl FnOnce()) -> Result<std::fs::File> {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path.parent().expect("infallible"))?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .wrap_err(format!("Error opening lockfile at {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                on_wait();
+                file.lock()
+                    .wrap_err(format!("Error locking {}", path.display()))?;
+            }
+            Err(std::fs::TryLockError::Error(e)) => {
+                return Err(e).wrap_err(format!("Error locking {}", path.display()));
+            }
+        }
+
+        Ok(file)
+    }
+
     #[instrument(skip(path))]
     pub fn read_or_create(path: impl AsRef<Path> + Debug) -> Self {
         let path = path.as_ref();
@@ -231,42 +640,143 @@ impl AppMeta {
     #[instrument(skip(self, instance), fields(id = %instance.id))]
     pub fn add_instance(&mut self, instance: InstanceMeta) {
         debug!("Adding instance {:?}", instance);
-        self.instances.insert(instance.id.to_string(), instance);
+        self.instances.insert(instance.key(), instance);
     }
 
     #[instrument(ret(level = "debug"), skip(self))]
-    pub fn remove_instance(&mut self, id: &String) -> Option<InstanceMeta> {
+    pub fn remove_instance(&mut self, id: &str) -> Option<InstanceMeta> {
         debug!("Removing instance");
         self.instances.remove(id)
     }
 
     #[instrument(skip(self))]
-    pub fn instance_installed(&self, id: &String) -> bool {
+    pub fn instance_installed(&self, id: &str) -> bool {
         self.instances.contains_key(id)
     }
 
+    /// Finds the key of an instance by name, falling back to its version
+    /// if no instance has that name and exactly one instance has that version
+    #[instrument(ret(level = "debug"), skip(self))]
+    pub fn resolve_instance(&self, name: &str) -> Option<String> {
+        if self.instances.contains_key(name) {
+            return Some(name.to_string());
+        }
+
+        self.instances
+            .iter()
+            .filter(|(_, instance)| instance.id.to_string() == name)
+            .map(|(key, _)| key.clone())
+            .exactly_one()
+            .ok()
+    }
+
     #[instrument(skip(self))]
-    pub fn add_jre(&mut self, jre: u8) -> bool {
+    pub fn add_jre(&mut self, jre: &str, arch: &str) -> bool {
         debug!("Adding JRE");
-        self.installed_jres.insert(jre)
+        self.jre_arches.insert(jre.to_string(), arch.to_string());
+        self.installed_jres.insert(jre.to_string())
     }
 
     #[instrument(skip(self))]
-    pub fn remove_jre(&mut self, jre: u8) -> bool {
+    pub fn remove_jre(&mut self, jre: &str) -> bool {
         debug!("Removing JRE");
-        self.installed_jres.remove(&jre)
+        self.jre_arches.remove(jre);
+        self.standalone_jres.remove(jre);
+        self.installed_jres.remove(jre)
+    }
+
+    /// Marks an installed JRE as installed on its own, so it isn't removed when unused
+    #[instrument(skip(self))]
+    pub fn add_standalone_jre(&mut self, jre: &str) -> bool {
+        debug!("Marking JRE as standalone");
+        self.standalone_jres.insert(jre.to_string())
     }
 
     #[instrument(skip(self))]
-    pub fn jre_installed(&self, jre: u8) -> bool {
-        self.installed_jres.contains(&jre)
+    pub fn jre_installed(&self, jre: &str) -> bool {
+        self.installed_jres.contains(jre)
+    }
+
+    /// The keys of the installed JREs with a major version, sorted
+    pub fn jres_for_major(&self, major: u8) -> Vec<&str> {
+        let major = major.to_string();
+        self.installed_jres
+            .iter()
+            .filter(|key| key.split('-').next() == Some(major.as_str()))
+            .map(String::as_str)
+            .sorted()
+            .collect()
+    }
+
+    /// The architecture an installed JRE was downloaded for, if it is known
+    pub fn jre_arch(&self, jre: &str) -> Option<&str> {
+        self.jre_arches.get(jre).map(String::as_str)
+    }
+
+    #[instrument(skip(self))]
+    pub fn add_store_ref(&mut self, hash: &str) {
+        debug!("Adding store reference");
+        *self.store_refs.entry(hash.to_string()).or_default() += 1;
+    }
+
+    /// Removes a reference to a store entry, returning `true` if
+    /// it is no longer referenced by any instance
+    #[instrument(ret(level = "debug"), skip(self))]
+    pub fn remove_store_ref(&mut self, hash: &str) -> bool {
+        debug!("Removing store reference");
+        match self.store_refs.get_mut(hash) {
+            Some(refs) if *refs > 1 => {
+                *refs -= 1;
+                false
+            }
+            _ => {
+                self.store_refs.remove(hash);
+                true
+            }
+        }
     }
 }
 
+/// Deserializes installed JRE keys, accepting the bare major
+/// versions written by older versions of mcdl
+fn deserialize_jre_keys<'de, D>(deserializer: D) -> std::result::Result<HashSet<String>, D::Error>
+where D: Deserializer<'de> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum JreKey {
+        Major(u8),
+        Key(String),
+    }
+
+    Ok(Vec::<JreKey>::deserialize(deserializer)?
+        .into_iter()
+        .map(|key| match key {
+            JreKey::Major(major) => major.to_string(),
+            JreKey::Key(key) => key,
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn lock_meta() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meta.lock");
+
+        let lock = AppMeta::lock(&path, || panic!("lock should be free")).unwrap();
+        let other = std::fs::File::open(&path).unwrap();
+        assert!(matches!(
+            other.try_lock(),
+            Err(std::fs::TryLockError::WouldBlock)
+        ));
+
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+    }
+
     #[tokio::test]
     async fn read_write_settings() {
         let dir = tempfile::tempdir().unwrap();
@@ -285,6 +795,75 @@ mod tests {
         );
         assert_eq!(settings.server.jar, PathBuf::from("server.jar"));
         assert_eq!(settings.server.args, vec!["--nogui".to_string()]);
+        assert!(!settings.backup.on_run);
+    }
+
+    #[test]
+    fn loader_vanilla_jar() {
+        let mut server = InstanceServerSettings {
+            jar: PathBuf::from("custom.jar"),
+            ..Default::default()
+        };
+        assert_eq!(server.vanilla_jar(), Path::new("custom.jar"));
+
+        server.loader = Loader::Fabric;
+        server.jar = PathBuf::from("fabric-server-launch.jar");
+        assert_eq!(server.vanilla_jar(), Path::new("server.jar"));
+    }
+
+    #[test]
+    fn java_memory_args() {
+        let mut java = InstanceJavaSettings::new(17);
+        assert_eq!(java.memory_args(), ["-Xms4G", "-Xmx4G"]);
+
+        java.min_memory = None;
+        java.max_memory = Some("512M".to_string());
+        assert_eq!(java.memory_args(), ["-Xmx512M"]);
+    }
+
+    #[test]
+    fn java_remove_args() {
+        let mut java = InstanceJavaSettings::new(17);
+        java.args = vec![
+            "-Xms1G".to_string(),
+            "-Xmx2G".to_string(),
+            "-Dfoo".to_string(),
+        ];
+
+        assert_eq!(java.remove_args("-Xmx"), ["-Xmx2G"]);
+        assert_eq!(java.args, ["-Xms1G", "-Dfoo"]);
+        assert!(java.remove_args("-Xmx").is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_settings_without_backup_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+
+        fs::write(
+            &path,
+            "[java]\nversion = 17\nargs = []\n\n[server]\njar = \"server.jar\"\nargs = []\n",
+        )
+        .await
+        .unwrap();
+        let settings = InstanceSettings::from_file(&path).await.unwrap();
+        assert!(!settings.backup.on_run);
+        assert_eq!(settings.backup.keep, DEFAULT_BACKUPS_KEPT);
+    }
+
+    #[tokio::test]
+    async fn read_settings_with_launch_wrapper() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+
+        fs::write(
+            &path,
+            "[java]\nversion = 17\nargs = []\n\n[server]\njar = \"server.jar\"\nargs = []\nlaunch_wrapper = [\"taskset\", \"-c\", \"0-3\"]\n",
+        )
+        .await
+        .unwrap();
+        let settings = InstanceSettings::from_file(&path).await.unwrap();
+        assert_eq!(settings.server.launch_wrapper, ["taskset", "-c", "0-3"]);
     }
 
     #[tokio::test]
@@ -305,4 +884,257 @@ mod tests {
         fs::write(&path, "invalid").await.unwrap();
         let _settings = InstanceSettings::from_file(&path).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn read_settings_wrong_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+
+        fs::write(
+            &path,
+            "[java]\nversion = \"17\"\nargs = []\n\n[server]\njar = \"server.jar\"\nargs = []\n",
+        )
+        .await
+        .unwrap();
+        let err = InstanceSettings::from_file(&path).await.unwrap_err();
+        assert!(
+            format!("{err:?}").contains("`java.version` on line 2"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn validate_settings() {
+        let mut settings = InstanceSettings::new(17);
+        assert!(settings.validate().is_ok());
+
+        settings.java.min_memory = Some("8G".to_string());
+        settings.java.max_memory = Some("2048M".to_string());
+        let err = settings.validate().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("must be at least `java.min_memory` (8G)")
+        );
+
+        settings.java.max_memory = None;
+        settings.java.release = Some("21.0.1+12".to_string());
+        let err = settings.validate().unwrap_err();
+        assert!(err.to_string().contains("is not a release of Java 17"));
+
+        settings.java.release = None;
+        settings.java.version = 6;
+        assert!(settings.validate().is_err());
+
+        settings.java.version = 17;
+        settings.backup.keep = 0;
+        let err = settings.validate().unwrap_err();
+        assert!(err.to_string().contains("`backup.keep` must be at least 1"));
+    }
+
+    #[test]
+    fn settings_unknown_keys() {
+        let contents = "[java]\nversion = 17\nargs = []\nmax_memroy = \"4G\"\n\n\
+            [server]\njar = \"server.jar\"\nargs = []\n\n[extra]\nfoo = 1\n";
+        let settings: InstanceSettings = toml::from_str(contents).unwrap();
+        assert_eq!(
+            settings.unknown_keys(contents),
+            ["java.max_memroy", "extra"]
+        );
+    }
+
+    #[test]
+    fn settings_infer_missing_loader() {
+        let contents = "[java]\nversion = 17\nargs = []\n\n\
+            [server]\njar = \"fabric-server-launch.jar\"\nargs = []\n";
+        let mut settings: InstanceSettings = toml::from_str(contents).unwrap();
+        settings.infer_missing_loader(contents);
+        assert_eq!(settings.server.loader, Loader::Fabric);
+        assert_eq!(settings.server.vanilla_jar(), Path::new("server.jar"));
+
+        // a recorded loader is kept, even if the jar was renamed
+        let contents = "[java]\nversion = 17\nargs = []\n\n\
+            [server]\njar = \"server.jar\"\nargs = []\nloader = \"quilt\"\n";
+        let mut settings: InstanceSettings = toml::from_str(contents).unwrap();
+        settings.infer_missing_loader(contents);
+        assert_eq!(settings.server.loader, Loader::Quilt);
+    }
+
+    #[test]
+    fn instance_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("server.jar");
+        let missing = dir.path().join("eula.txt");
+        std::fs::write(&present, "").unwrap();
+
+        let mut meta = InstanceMeta::new("1.20.1".parse().unwrap(), 17);
+        meta.add_file(&present);
+        meta.add_file(&missing);
+
+        assert_eq!(meta.missing_files(), [&missing]);
+    }
+
+    #[test]
+    fn read_legacy_instance_meta() {
+        #[derive(Serialize)]
+        struct LegacyInstanceMeta {
+            id: VersionNumber,
+            files: Vec<PathBuf>,
+            jre: u8,
+        }
+
+        let legacy = LegacyInstanceMeta {
+            id: "1.20.1".parse().unwrap(),
+            files: vec![PathBuf::from("1.20.1")],
+            jre: 17,
+        };
+        let data = rmp_serde::to_vec(&legacy).unwrap();
+        let meta: InstanceMeta = rmp_serde::from_slice(&data).unwrap();
+
+        assert_eq!(meta.jre, 17);
+        assert!(meta.server_url.is_none());
+        assert!(meta.server_sha1.is_none());
+    }
+
+    #[test]
+    fn replace_instance_path_prefix() {
+        let mut meta = InstanceMeta::new("1.20.1".parse().unwrap(), 17);
+        meta.add_file(Path::new("/data/instance/1.20.1"));
+        meta.add_file(Path::new("/data/instance/1.20.1/client.jar"));
+        meta.add_file(Path::new("/config/instance/1.20.1.toml"));
+        meta.add_file(Path::new("/data/instance/1.20.10"));
+        meta.backups
+            .push(PathBuf::from("/data/backup/1.20.1/20240101-000000.zip"));
+
+        meta.replace_path_prefix(
+            Path::new("/data/instance/1.20.1"),
+            Path::new("/data/instance/survival"),
+        );
+        meta.replace_path_prefix(
+            Path::new("/config/instance/1.20.1.toml"),
+            Path::new("/config/instance/survival.toml"),
+        );
+        meta.replace_path_prefix(
+            Path::new("/data/backup/1.20.1"),
+            Path::new("/data/backup/survival"),
+        );
+
+        assert_eq!(
+            meta.files,
+            [
+                PathBuf::from("/data/instance/survival"),
+                PathBuf::from("/data/instance/survival/client.jar"),
+                PathBuf::from("/config/instance/survival.toml"),
+                PathBuf::from("/data/instance/1.20.10"),
+            ]
+        );
+        assert_eq!(
+            meta.backups,
+            [PathBuf::from("/data/backup/survival/20240101-000000.zip")]
+        );
+        assert!(!meta.files[2].to_string_lossy().ends_with('/'));
+    }
+
+    #[test]
+    fn resolve_named_instances() {
+        let mut meta = AppMeta::default();
+        meta.add_instance(InstanceMeta::new("1.20.1".parse().unwrap(), 17));
+        for name in ["survival", "creative"] {
+            let mut instance = InstanceMeta::new("1.19.4".parse().unwrap(), 17);
+            instance.name = Some(name.to_string());
+            meta.add_instance(instance);
+        }
+
+        assert_eq!(
+            meta.resolve_instance("survival").as_deref(),
+            Some("survival")
+        );
+        assert_eq!(meta.resolve_instance("1.20.1").as_deref(), Some("1.20.1"));
+        // ambiguous, so the name must be used
+        assert_eq!(meta.resolve_instance("1.19.4"), None);
+        assert_eq!(meta.resolve_instance("hardcore"), None);
+    }
+
+    #[test]
+    fn store_refcount() {
+        let mut meta = AppMeta::default();
+        meta.add_store_ref("abc");
+        meta.add_store_ref("abc");
+
+        assert!(!meta.remove_store_ref("abc"));
+        assert!(meta.remove_store_ref("abc"));
+        assert!(!meta.store_refs.contains_key("abc"));
+    }
+
+    #[test]
+    fn jre_arch() {
+        let mut meta = AppMeta::default();
+        meta.add_jre("21", "x86_64");
+        meta.add_jre("21-aarch64", "aarch64");
+        assert_eq!(meta.jre_arch("21"), Some("x86_64"));
+        assert_eq!(meta.jre_arch("21-aarch64"), Some("aarch64"));
+
+        meta.remove_jre("21");
+        assert_eq!(meta.jre_arch("21"), None);
+    }
+
+    #[test]
+    fn remove_standalone_jre() {
+        let mut meta = AppMeta::default();
+        meta.add_jre("21", "x86_64");
+        assert!(meta.add_standalone_jre("21"));
+
+        meta.remove_jre("21");
+        assert!(meta.standalone_jres.is_empty());
+    }
+
+    #[test]
+    fn jres_for_major() {
+        let mut meta = AppMeta::default();
+        meta.add_jre("17", "x86_64");
+        meta.add_jre("17-jdk", "x86_64");
+        meta.add_jre("171", "x86_64");
+        meta.add_jre("21", "x86_64");
+
+        assert_eq!(meta.jres_for_major(17), ["17", "17-jdk"]);
+        assert!(meta.jres_for_major(8).is_empty());
+    }
+
+    #[test]
+    fn read_legacy_jre_keys() {
+        #[derive(Serialize)]
+        struct LegacyAppMeta {
+            instances: HashMap<String, InstanceMeta>,
+            installed_jres: HashSet<u8>,
+            path: PathBuf,
+        }
+
+        let legacy = LegacyAppMeta {
+            instances: HashMap::new(),
+            installed_jres: HashSet::from([8, 17]),
+            path: PathBuf::from("meta.mpk"),
+        };
+        let data = rmp_serde::to_vec(&legacy).unwrap();
+        let meta: AppMeta = rmp_serde::from_slice(&data).unwrap();
+
+        assert!(meta.jre_installed("8"));
+        assert!(meta.jre_installed("17"));
+        assert!(meta.store_refs.is_empty());
+        assert!(meta.default_list_filter.is_none());
+        assert!(meta.cache_ttl.is_none());
+        assert!(meta.log_retention_days.is_none());
+        assert!(meta.standalone_jres.is_empty());
+        assert_eq!(meta.jre_arch("17"), None);
+    }
+
+    #[test]
+    fn roundtrip_default_list_filter() {
+        let meta = AppMeta {
+            default_list_filter: Some("snapshot".to_string()),
+            ..Default::default()
+        };
+
+        let data = rmp_serde::to_vec(&meta).unwrap();
+        let meta: AppMeta = rmp_serde::from_slice(&data).unwrap();
+        assert_eq!(meta.default_list_filter.as_deref(), Some("snapshot"));
+    }
 }
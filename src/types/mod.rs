@@ -1,3 +1,7 @@
+pub(crate) mod flags;
+pub(crate) mod jre;
+pub(crate) mod loader;
 pub(crate) mod meta;
 pub(crate) mod net;
+pub(crate) mod stats;
 pub(crate) mod version;
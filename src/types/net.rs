@@ -1,11 +1,15 @@
 use std::path::Path;
 use std::time::SystemTime;
 
+use clap::ValueEnum;
 use color_eyre::eyre::Result;
 use derive_more::Constructor;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 
+use crate::utils::atomic::write_atomic_async;
+
 #[derive(Serialize, Deserialize, Constructor)]
 pub(crate) struct CachedResponse<T> {
     pub data: T,
@@ -29,7 +33,100 @@ impl<T> CachedResponse<T> {
     where Self: Serialize {
         let data = rmp_serde::to_vec(self)?;
         fs::create_dir_all(path.as_ref().parent().expect("infallible")).await?;
-        fs::write(path, data).await?;
+        write_atomic_async(path, data).await?;
         Ok(())
     }
 }
+
+/// The expiry of a [`CachedResponse`], which can be read
+/// without knowing the type of the cached data
+#[derive(Deserialize)]
+pub(crate) struct CacheHeader {
+    #[serde(rename = "data")]
+    _data: IgnoredAny,
+    pub expires: SystemTime,
+}
+
+impl CacheHeader {
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() > self.expires
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        Ok(rmp_serde::from_slice(&data)?)
+    }
+}
+
+/// A third-party mirror of Mojang's download servers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Mirror {
+    /// BMCLAPI (`https://bmclapi2.bangbang93.com/`), run by bangbang93
+    Bmclapi,
+}
+
+impl Mirror {
+    /// The hosts of Mojang's download servers, which the mirror serves the same paths for
+    const MOJANG_HOSTS: [&str; 4] = [
+        "piston-data.mojang.com",
+        "piston-meta.mojang.com",
+        "launcher.mojang.com",
+        "launchermeta.mojang.com",
+    ];
+
+    pub fn base_url(self) -> &'static str {
+        match self {
+            Mirror::Bmclapi => "https://bmclapi2.bangbang93.com/",
+        }
+    }
+
+    /// Points a download URL on Mojang's servers at the mirror, leaving other URLs alone
+    pub fn rewrite(self, url: &str) -> String {
+        Self::MOJANG_HOSTS
+            .iter()
+            .find_map(|host| url.strip_prefix(&format!("https://{host}/")))
+            .map_or_else(
+                || url.to_string(),
+                |path| format!("{}{path}", self.base_url()),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_cache_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cached.mpk");
+
+        let expires = SystemTime::now() - Duration::from_secs(1);
+        CachedResponse::new(vec!["a".to_string(), "b".to_string()], expires)
+            .save(&path)
+            .await
+            .unwrap();
+
+        let header = CacheHeader::from_file(&path).unwrap();
+        assert_eq!(header.expires, expires);
+        assert!(header.is_expired());
+    }
+
+    #[test]
+    fn rewrite_mirror_url() {
+        assert_eq!(
+            Mirror::Bmclapi.rewrite("https://piston-data.mojang.com/v1/objects/abc/server.jar"),
+            "https://bmclapi2.bangbang93.com/v1/objects/abc/server.jar"
+        );
+        assert_eq!(
+            Mirror::Bmclapi.rewrite("https://launcher.mojang.com/v1/objects/abc/server.jar"),
+            "https://bmclapi2.bangbang93.com/v1/objects/abc/server.jar"
+        );
+        assert_eq!(
+            Mirror::Bmclapi.rewrite("https://maven.fabricmc.net/net/fabricmc/a.jar"),
+            "https://maven.fabricmc.net/net/fabricmc/a.jar"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use derive_more::Display;
+
+/// What a [`TransferStat`] measures
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display)]
+pub(crate) enum TransferKind {
+    Download,
+    Extract,
+}
+
+/// Timings for a single download or extraction, reported by `install --stats`
+#[derive(Clone, Debug)]
+pub(crate) struct TransferStat {
+    pub label: String,
+    pub kind: TransferKind,
+    pub bytes: u64,
+    /// Time until the response headers arrived, including DNS lookup,
+    /// connecting, and the TLS handshake. Only set for downloads
+    pub response_time: Option<Duration>,
+    pub elapsed: Duration,
+}
+
+impl TransferStat {
+    /// Average bytes per second over the whole transfer
+    pub fn throughput(&self) -> u64 {
+        let millis = self.elapsed.as_millis().max(1);
+        u64::try_from(u128::from(self.bytes) * 1000 / millis).unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throughput() {
+        let stat = TransferStat {
+            label: "1.20.1".to_string(),
+            kind: TransferKind::Download,
+            bytes: 50_000_000,
+            response_time: Some(Duration::from_millis(120)),
+            elapsed: Duration::from_millis(2500),
+        };
+        assert_eq!(stat.throughput(), 20_000_000);
+
+        let instant = TransferStat {
+            elapsed: Duration::ZERO,
+            ..stat
+        };
+        assert_eq!(instant.throughput(), 50_000_000_000);
+    }
+}
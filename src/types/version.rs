@@ -171,7 +171,7 @@ parse_variants!(VersionNumber {
 ///
 /// Consists of an ID, a release type, the meta URL, and a release
 /// timestamp
-#[derive(Debug, Serialize, Deserialize, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Eq)]
 pub(crate) struct GameVersion {
     pub id: VersionNumber,
     #[serde(rename = "type")]
@@ -218,6 +218,36 @@ pub(crate) struct GameVersionList {
     pub versions: Vec<GameVersion>,
 }
 
+impl GameVersionList {
+    /// Resolves the latest release version
+    ///
+    /// By default, this is whatever the manifest marks as the latest release.
+    /// If `include_prereleases` is set, the newest release, pre-release, or
+    /// release candidate (by release time) is picked instead
+    pub fn latest_release(&self, include_prereleases: bool) -> Option<&GameVersion> {
+        if include_prereleases {
+            self.versions
+                .iter()
+                .filter(|v| v.id.is_release() || v.id.is_pre_release())
+                .max()
+        } else {
+            self.versions.iter().find(|v| v.id == self.latest.release)
+        }
+    }
+
+    /// Resolves a version alias to the version it currently points to
+    ///
+    /// `latest` and `latest-release` are the latest release, `latest-snapshot` is the latest
+    /// snapshot, as marked in the manifest. Returns `None` if `alias` is not an alias
+    pub fn resolve_alias(&self, alias: &str) -> Option<&VersionNumber> {
+        match alias {
+            "latest" | "latest-release" => Some(&self.latest.release),
+            "latest-snapshot" => Some(&self.latest.snapshot),
+            _ => None,
+        }
+    }
+}
+
 impl Iterator for GameVersionList {
     type Item = GameVersion;
 
@@ -228,8 +258,8 @@ impl Iterator for GameVersionList {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct VersionDownload {
-    sha1: String,
-    size: u64,
+    pub sha1: String,
+    pub size: u64,
     pub url: String,
 }
 
@@ -240,11 +270,26 @@ pub(crate) struct JavaVersionInfo {
     pub major_version: u8,
 }
 
+/// Versions that predate `javaVersion` in their metadata all run on Java 8
+impl Default for JavaVersionInfo {
+    fn default() -> Self {
+        Self {
+            component: "jre-legacy".to_string(),
+            major_version: 8,
+        }
+    }
+}
+
+/// Metadata for a single version, as returned by the Mojang API
+///
+/// Old and experimental (e.g. April Fools) versions don't always have the usual shape,
+/// so everything but the ID is optional
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct VersionMetadata {
+    #[serde(default)]
     pub downloads: HashMap<String, VersionDownload>, // client, server, windows_server (legacy) + mappings
     pub id: VersionNumber,
-    #[serde(rename = "javaVersion")]
+    #[serde(rename = "javaVersion", default)]
     pub java_version: JavaVersionInfo,
     // the rest of the fields are not used
 
@@ -390,4 +435,60 @@ mod tests {
         let v: VersionNumber = serde_json::from_str(r#""3D Shareware v1.34""#).unwrap();
         assert_eq!(v, VersionNumber::Other("3D Shareware v1.34".to_string()));
     }
+
+    #[test]
+    fn version_metadata_without_server_or_java_version() {
+        // trimmed down from 2.0_blue, with an unusual download key and no javaVersion
+        let meta: VersionMetadata = serde_json::from_str(
+            r#"{
+                "id": "2.0_blue",
+                "type": "old_alpha",
+                "assets": "pre-1.6",
+                "downloads": {
+                    "client": { "sha1": "abc", "size": 1, "url": "https://example.com/client.jar" },
+                    "client_mappings_unofficial": { "sha1": "def", "size": 2, "url": "https://example.com/m.txt" }
+                },
+                "mainClass": "net.minecraft.client.Minecraft"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(meta.id, VersionNumber::Other("2.0_blue".to_string()));
+        assert!(!meta.downloads.contains_key("server"));
+        assert_eq!(meta.java_version.major_version, 8);
+
+        let meta: VersionMetadata = serde_json::from_str(r#"{ "id": "1.RV-Pre1" }"#).unwrap();
+        assert!(meta.downloads.is_empty());
+    }
+
+    #[test]
+    fn latest_release_with_prereleases() {
+        let list: GameVersionList = serde_json::from_str(
+            r#"{
+                "latest": { "release": "1.20.1", "snapshot": "23w31a" },
+                "versions": [
+                    { "id": "23w31a", "type": "snapshot", "url": "", "time": "2023-08-01T00:00:00+00:00", "releaseTime": "2023-08-01T00:00:00+00:00" },
+                    { "id": "1.20.2-rc1", "type": "snapshot", "url": "", "time": "2023-07-01T00:00:00+00:00", "releaseTime": "2023-07-01T00:00:00+00:00" },
+                    { "id": "1.20.1", "type": "release", "url": "", "time": "2023-06-12T00:00:00+00:00", "releaseTime": "2023-06-12T00:00:00+00:00" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(list.latest_release(false).unwrap().id.to_string(), "1.20.1");
+        assert_eq!(
+            list.latest_release(true).unwrap().id.to_string(),
+            "1.20.2-rc1"
+        );
+
+        assert_eq!(list.resolve_alias("latest").unwrap().to_string(), "1.20.1");
+        assert_eq!(
+            list.resolve_alias("latest-release").unwrap().to_string(),
+            "1.20.1"
+        );
+        assert_eq!(
+            list.resolve_alias("latest-snapshot").unwrap().to_string(),
+            "23w31a"
+        );
+        assert!(list.resolve_alias("1.20.1").is_none());
+    }
 }
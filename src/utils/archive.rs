@@ -0,0 +1,203 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use tracing::{debug, instrument};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Writes `dirs` (and everything in them) to a new zip archive at `out`
+///
+/// Entries are named relative to `base`, which must be an ancestor of every directory
+#[instrument(err, ret(level = "debug"), skip(dirs))]
+pub(crate) fn zip_dirs(base: &Path, dirs: &[PathBuf], out: &Path) -> Result<()> {
+    zip_dirs_and_files(base, dirs, &[], out)
+}
+
+/// Like [`zip_dirs`], but also writes `files` at the root of the archive, by name
+#[instrument(err, ret(level = "debug"), skip(dirs, files))]
+pub(crate) fn zip_dirs_and_files(
+    base: &Path,
+    dirs: &[PathBuf],
+    files: &[(&str, &[u8])],
+    out: &Path,
+) -> Result<()> {
+    write_zip(dirs, files, out, |path| {
+        Ok(path
+            .strip_prefix(base)?
+            .to_string_lossy()
+            .replace('\\', "/"))
+    })
+}
+
+/// Like [`zip_dirs_and_files`] with a single directory, but stores it as `name`
+/// whatever the directory is called
+#[instrument(err, ret(level = "debug"), skip(files))]
+pub(crate) fn zip_dir_as(
+    dir: &Path,
+    name: &str,
+    files: &[(&str, &[u8])],
+    out: &Path,
+) -> Result<()> {
+    write_zip(&[dir.to_path_buf()], files, out, |path| {
+        let relative = path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+        Ok(if relative.is_empty() {
+            name.to_string()
+        } else {
+            format!("{name}/{relative}")
+        })
+    })
+}
+
+/// Writes `files` and the contents of `dirs` to a new zip archive at `out`,
+/// naming the directory entries with `entry_name`
+fn write_zip(
+    dirs: &[PathBuf],
+    files: &[(&str, &[u8])],
+    out: &Path,
+    entry_name: impl Fn(&Path) -> Result<String>,
+) -> Result<()> {
+    let file = File::create(out).wrap_err(format!("Failed to create {}", out.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, data) in files {
+        zip.start_file(*name, options)?;
+        std::io::Write::write_all(&mut zip, data)?;
+    }
+
+    let mut stack = dirs.to_vec();
+    while let Some(dir) = stack.pop() {
+        zip.add_directory(entry_name(&dir)?, options)?;
+
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+
+            debug!(path = %path.display(), "Adding file to archive");
+            zip.start_file(entry_name(&path)?, options)?;
+            std::io::copy(&mut File::open(&path)?, &mut zip)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Reads a single file from the zip archive at `path`
+#[instrument(err, skip(path), fields(path = %path.display()))]
+pub(crate) fn read_zip_file(path: &Path, name: &str) -> Result<Vec<u8>> {
+    let mut archive =
+        ZipArchive::new(File::open(path).wrap_err(format!("Failed to open {}", path.display()))?)?;
+    let mut entry = archive
+        .by_name(name)
+        .wrap_err(format!("{} has no {name}", path.display()))?;
+
+    let mut data = Vec::new();
+    std::io::copy(&mut entry, &mut data)?;
+    Ok(data)
+}
+
+/// Extracts the entries under `prefix` in the zip archive at `path` into `dest`,
+/// without the prefix
+///
+/// Entries that would be written outside of `dest` are rejected.
+#[instrument(err, ret(level = "debug"))]
+pub(crate) fn unzip_dir(path: &Path, prefix: &str, dest: &Path) -> Result<()> {
+    let mut archive =
+        ZipArchive::new(File::open(path).wrap_err(format!("Failed to open {}", path.display()))?)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry
+            .enclosed_name()
+            .ok_or_else(|| eyre!("Invalid file path {} in archive", entry.name()))?;
+        let Ok(relative) = name.strip_prefix(prefix) else {
+            continue;
+        };
+
+        let outpath = dest.join(relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&outpath)?;
+            continue;
+        }
+
+        debug!(path = %outpath.display(), "Extracting file from archive");
+        if let Some(parent) = outpath.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut entry, &mut File::create(&outpath)?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn zip_world_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = dir.path().join("world");
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::write(world.join("level.dat"), "level").unwrap();
+        std::fs::write(world.join("region").join("r.0.0.mca"), "region").unwrap();
+
+        let out = dir.path().join("backup.zip");
+        zip_dirs(dir.path(), &[world], &out).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&out).unwrap()).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("world/region/r.0.0.mca")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "region");
+        assert!(archive.by_name("world/level.dat").is_ok());
+    }
+
+    #[test]
+    fn unzip_with_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = dir.path().join("1.20.1");
+        std::fs::create_dir_all(instance.join("world")).unwrap();
+        std::fs::write(instance.join("server.jar"), "jar").unwrap();
+        std::fs::write(instance.join("world").join("level.dat"), "level").unwrap();
+
+        let out = dir.path().join("export.zip");
+        zip_dirs_and_files(dir.path(), &[instance], &[("manifest", b"{}")], &out).unwrap();
+        assert_eq!(read_zip_file(&out, "manifest").unwrap(), b"{}");
+        assert!(read_zip_file(&out, "missing").is_err());
+
+        let dest = dir.path().join("imported");
+        unzip_dir(&out, "1.20.1", &dest).unwrap();
+        assert_eq!(std::fs::read(dest.join("server.jar")).unwrap(), b"jar");
+        assert_eq!(
+            std::fs::read(dest.join("world").join("level.dat")).unwrap(),
+            b"level"
+        );
+        assert!(!dest.join("manifest").exists());
+    }
+
+    #[test]
+    fn zip_dir_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = dir.path().join("my-server");
+        std::fs::create_dir_all(&server).unwrap();
+        std::fs::write(server.join("server.jar"), "jar").unwrap();
+
+        let out = dir.path().join("export.zip");
+        zip_dir_as(&server, "1.20.1", &[], &out).unwrap();
+
+        let dest = dir.path().join("imported");
+        unzip_dir(&out, "1.20.1", &dest).unwrap();
+        assert_eq!(std::fs::read(dest.join("server.jar")).unwrap(), b"jar");
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// The file `data` is written to before it replaces `path`,
+/// in the same directory so the rename can't cross filesystems
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().expect("infallible").to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Writes `data` to `path` so it either has the old contents or all of the new ones,
+/// even if the process dies or the disk fills up mid-write
+pub(crate) fn write_atomic(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path);
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(data.as_ref())?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Async version of [`write_atomic`]
+pub(crate) async fn write_atomic_async(
+    path: impl AsRef<Path>,
+    data: impl AsRef<[u8]>,
+) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path);
+
+    let result = async {
+        let mut file = fs::File::create(&temp).await?;
+        file.write_all(data.as_ref()).await?;
+        file.sync_all().await?;
+        fs::rename(&temp, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn write_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meta.mpk");
+
+        write_atomic(&path, b"old").unwrap();
+        write_atomic_async(&path, b"new").await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        // the temporary file is renamed, not left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_write_keeps_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meta.mpk");
+        write_atomic(&path, b"old").unwrap();
+
+        // a directory in the way of the temporary file makes the write fail
+        std::fs::create_dir(temp_path(&path)).unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+    }
+}
@@ -0,0 +1,34 @@
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+/// Computes the SHA1 digest of `data` as a lowercase hex string
+pub(crate) fn sha1_hex(data: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+/// Computes the SHA-256 digest of `data` as a lowercase hex string, which Adoptium publishes for JREs
+pub(crate) fn sha256_hex(data: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_hex_digest() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            sha1_hex(b"mcdl"),
+            "3d56ecf7a9ee2055d01d75aed42169cb0f1c893e"
+        );
+    }
+
+    #[test]
+    fn sha256_hex_digest() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
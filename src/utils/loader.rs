@@ -0,0 +1,311 @@
+use std::path::Path;
+
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use indicatif::ProgressBar;
+use tokio::fs;
+use tracing::{debug, instrument};
+
+use crate::common::REQWEST_CLIENT;
+use crate::error::McdlError;
+use crate::types::loader::{
+    LoaderEntry, LoaderVersion, QuiltVersion, ServerProfile, latest_stable,
+};
+use crate::types::version::VersionNumber;
+use crate::utils::archive::zip_dirs_and_files;
+use crate::utils::hash::sha1_hex;
+use crate::utils::net::{
+    CACHE_BASE_DIR, MAX_ATTEMPTS, get_maybe_cached, read_with_progress, retry_request,
+};
+
+const FABRIC_API_URL: &str = "https://meta.fabricmc.net/";
+const QUILT_API_URL: &str = "https://meta.quiltmc.org/";
+
+/// The class of the Quilt launcher, which loads the server jar named in [`QUILT_LAUNCHER_PROPERTIES`]
+/// and then starts the `mainClass` of the profile
+const QUILT_LAUNCHER_CLASS: &str = "org.quiltmc.loader.impl.launch.server.QuiltServerLauncher";
+const QUILT_LAUNCHER_PROPERTIES: &str = "quilt-server-launcher.properties";
+
+/// The meta API of a mod loader, which its server launcher is installed from
+///
+/// Adding a loader means implementing this and adding it to [`Loader`](crate::types::loader::Loader).
+pub(crate) trait LoaderApi {
+    /// The name of the loader in messages
+    const NAME: &'static str;
+    /// The file name of the launcher, which runs the vanilla `server.jar` next to it
+    const LAUNCHER_JAR: &'static str;
+    /// Everything [`install_launcher`](Self::install_launcher) writes, relative to the instance directory
+    const FILES: &'static [&'static str];
+
+    /// Loader versions compatible with a game version, newest first
+    async fn loader_versions(game_version: &VersionNumber) -> Result<Vec<LoaderVersion>>;
+
+    /// Writes the launcher for a game and loader version, and anything it needs, into `instance_dir`
+    async fn install_launcher(
+        game_version: &VersionNumber,
+        loader_version: &str,
+        instance_dir: &Path,
+        pb: &ProgressBar,
+    ) -> Result<()>;
+}
+
+pub(crate) struct Fabric;
+
+impl LoaderApi for Fabric {
+    const NAME: &'static str = "Fabric";
+    const LAUNCHER_JAR: &'static str = "fabric-server-launch.jar";
+    const FILES: &'static [&'static str] = &[Self::LAUNCHER_JAR];
+
+    #[instrument(err)]
+    async fn loader_versions(game_version: &VersionNumber) -> Result<Vec<LoaderVersion>> {
+        let cache_file = CACHE_BASE_DIR.join(format!("fabric-loader-{game_version}.mpk"));
+
+        let entries: Vec<LoaderEntry<LoaderVersion>> = get_maybe_cached(
+            &format!("{FABRIC_API_URL}v2/versions/loader/{game_version}"),
+            &cache_file,
+        )
+        .await?;
+        Ok(entries.into_iter().map(|entry| entry.loader).collect())
+    }
+
+    /// Downloads the launcher Fabric bundles for the latest stable installer
+    #[instrument(err, skip(instance_dir, pb))]
+    async fn install_launcher(
+        game_version: &VersionNumber,
+        loader_version: &str,
+        instance_dir: &Path,
+        pb: &ProgressBar,
+    ) -> Result<()> {
+        let cache_file = CACHE_BASE_DIR.join("fabric-installer.mpk");
+        let installers: Vec<LoaderVersion> = get_maybe_cached(
+            &format!("{FABRIC_API_URL}v2/versions/installer"),
+            &cache_file,
+        )
+        .await?;
+        let installer =
+            latest_stable(&installers).ok_or_else(|| eyre!("No stable Fabric installer found"))?;
+
+        let url = format!(
+            "{FABRIC_API_URL}v2/versions/loader/{game_version}/{loader_version}/{}/server/jar",
+            installer.version
+        );
+        let launcher = download(&url, pb)
+            .await
+            .wrap_err("Failed to download Fabric launcher")?;
+
+        fs::write(instance_dir.join(Self::LAUNCHER_JAR), launcher)
+            .await
+            .wrap_err(format!(
+                "Failed to write Fabric launcher for {game_version}"
+            ))
+    }
+}
+
+pub(crate) struct Quilt;
+
+impl LoaderApi for Quilt {
+    const NAME: &'static str = "Quilt";
+    const LAUNCHER_JAR: &'static str = "quilt-server-launch.jar";
+    const FILES: &'static [&'static str] =
+        &[Self::LAUNCHER_JAR, QUILT_LAUNCHER_PROPERTIES, "libraries"];
+
+    #[instrument(err)]
+    async fn loader_versions(game_version: &VersionNumber) -> Result<Vec<LoaderVersion>> {
+        let cache_file = CACHE_BASE_DIR.join(format!("quilt-loader-{game_version}.mpk"));
+
+        let entries: Vec<LoaderEntry<QuiltVersion>> = get_maybe_cached(
+            &format!("{QUILT_API_URL}v3/versions/loader/{game_version}"),
+            &cache_file,
+        )
+        .await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| entry.loader.into())
+            .collect())
+    }
+
+    /// Downloads the libraries of the server profile into `libraries/`
+    /// and writes a launcher for them, like the Quilt installer does
+    #[instrument(err, skip(instance_dir, pb))]
+    async fn install_launcher(
+        game_version: &VersionNumber,
+        loader_version: &str,
+        instance_dir: &Path,
+        pb: &ProgressBar,
+    ) -> Result<()> {
+        let cache_file =
+            CACHE_BASE_DIR.join(format!("quilt-server-{game_version}-{loader_version}.mpk"));
+        let profile: ServerProfile = get_maybe_cached(
+            &format!(
+                "{QUILT_API_URL}v3/versions/loader/{game_version}/{loader_version}/server/json"
+            ),
+            &cache_file,
+        )
+        .await?;
+
+        let mut class_path = Vec::with_capacity(profile.libraries.len());
+        for library in &profile.libraries {
+            let (Some(path), Some(url)) = (library.path(), library.download_url()) else {
+                return Err(eyre!("Invalid Quilt library name: {}", library.name));
+            };
+            let path = format!("libraries/{path}");
+            let dest = instance_dir.join(&path);
+
+            if dest.exists() {
+                debug!(library = library.name, "Library already downloaded");
+            } else {
+                pb.set_message(format!("Downloading {}...", library.name));
+                let data = download(&url, pb)
+                    .await
+                    .wrap_err(format!("Failed to download {}", library.name))?;
+                verify_library(&library.name, &url, &data, pb).await?;
+                fs::create_dir_all(dest.parent().expect("infallible")).await?;
+                fs::write(&dest, data)
+                    .await
+                    .wrap_err(format!("Failed to write {}", dest.display()))?;
+            }
+            class_path.push(path);
+        }
+
+        fs::write(
+            instance_dir.join(QUILT_LAUNCHER_PROPERTIES),
+            "serverJar=server.jar\n",
+        )
+        .await
+        .wrap_err(format!("Failed to write {QUILT_LAUNCHER_PROPERTIES}"))?;
+
+        let manifest = [
+            manifest_line("Manifest-Version", "1.0"),
+            manifest_line("Main-Class", QUILT_LAUNCHER_CLASS),
+            manifest_line("Launcher-Main-Class", &profile.main_class),
+            manifest_line("Class-Path", &class_path.join(" ")),
+        ]
+        .concat();
+        let launcher = instance_dir.join(Self::LAUNCHER_JAR);
+        zip_dirs_and_files(
+            instance_dir,
+            &[],
+            &[("META-INF/MANIFEST.MF", manifest.as_bytes())],
+            &launcher,
+        )
+        .wrap_err(format!("Failed to write Quilt launcher for {game_version}"))
+    }
+}
+
+/// Downloads a small file from a loader's API or Maven repository
+async fn download(url: &str, pb: &ProgressBar) -> Result<bytes::Bytes> {
+    debug!(url, "Downloading");
+    let response = retry_request(REQWEST_CLIENT.get(url), MAX_ATTEMPTS, |n| {
+        pb.set_message(format!("Retrying ({n}/{MAX_ATTEMPTS})..."));
+    })
+    .await?
+    .error_for_status()?;
+
+    read_with_progress(response, pb).await
+}
+
+/// Checks a downloaded library against the SHA1 its Maven repository publishes next to it
+async fn verify_library(name: &str, url: &str, data: &[u8], pb: &ProgressBar) -> Result<()> {
+    let published = download(&format!("{url}.sha1"), pb)
+        .await
+        .wrap_err(format!("Failed to download the checksum of {name}"))?;
+    // some repositories follow the hash with the file name
+    let expected = String::from_utf8_lossy(&published)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let actual = sha1_hex(data);
+    if actual != expected {
+        return Err(McdlError::ChecksumMismatch {
+            file: name.to_string(),
+            expected,
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// A jar manifest header, wrapped since manifest lines can't be longer than 72 bytes
+fn manifest_line(key: &str, value: &str) -> String {
+    const MAX_LINE: usize = 72;
+
+    let header = format!("{key}: {value}");
+    let mut out = String::with_capacity(header.len() + header.len() / MAX_LINE * 3 + 2);
+    let mut line_len = 0;
+    for c in header.chars() {
+        // continuation lines start with a space, which counts towards their length
+        if line_len + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            line_len = 1;
+        }
+        out.push(c);
+        line_len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_verify_library() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/lib.jar", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            // one checksum request for each check below
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let _ = stream.read(&mut [0; 4096]).await.unwrap();
+                let body = format!("{}  lib.jar\n", sha1_hex(b"library"));
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let pb = ProgressBar::hidden();
+        verify_library("lib", &url, b"library", &pb).await.unwrap();
+        let err = verify_library("lib", &url, b"tampered", &pb)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<McdlError>(),
+            Some(McdlError::ChecksumMismatch { file, .. }) if file == "lib"
+        ));
+    }
+
+    #[test]
+    fn wrap_manifest_line() {
+        assert_eq!(
+            manifest_line("Main-Class", "a.b.Main"),
+            "Main-Class: a.b.Main\r\n"
+        );
+
+        let line = manifest_line("Class-Path", &"libraries/a.jar ".repeat(10));
+        let lines = line
+            .strip_suffix("\r\n")
+            .unwrap()
+            .split("\r\n")
+            .collect::<Vec<_>>();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= 72));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        let unwrapped = lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| if i == 0 { *l } else { &l[1..] })
+            .collect::<String>();
+        assert_eq!(
+            unwrapped,
+            format!("Class-Path: {}", "libraries/a.jar ".repeat(10))
+        );
+    }
+}
@@ -33,13 +33,12 @@ macro_rules! parse_variants {
         impl std::str::FromStr for $enum_name {
             type Err = color_eyre::eyre::Report;
 
-            #[allow(irrefutable_let_patterns)]
+            #[allow(irrefutable_let_patterns, unreachable_code)]
             fn from_str(s: &str) -> color_eyre::eyre::Result<Self, Self::Err> {
                 $( if let Ok(v) = s.parse::<$ty>() {
                     return Ok(Self::$variant(v.into()));
-                } else )* {
-                    return Err(color_eyre::eyre::eyre!("Failed to parse input string: {s}"));
-                }
+                } )*
+                Err(color_eyre::eyre::eyre!("Failed to parse input string: {s}"))
             }
         }
     };
@@ -47,6 +46,19 @@ macro_rules! parse_variants {
 
 pub(crate) use parse_variants;
 
+/// Prints a progress or success message, like [`println!`], unless `--quiet` is set
+///
+/// Output that was asked for, like a listing or a path, should use [`println!`] directly.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::common::QUIET.load(std::sync::atomic::Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use status;
+
 #[cfg(test)]
 mod tests {
     #[test]
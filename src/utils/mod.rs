@@ -1,2 +1,8 @@
+pub(crate) mod archive;
+pub(crate) mod atomic;
+pub(crate) mod hash;
+pub(crate) mod loader;
 pub(crate) mod macros;
 pub(crate) mod net;
+pub(crate) mod parse;
+pub(crate) mod properties;
@@ -1,34 +1,74 @@
 use std::fmt::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
 
-use bytes::Bytes;
-use color_eyre::eyre::{Result, eyre};
-use reqwest::StatusCode;
+use bytes::{Bytes, BytesMut};
+use color_eyre::eyre::{Report, Result, WrapErr, eyre};
+use indicatif::{ProgressBar, ProgressStyle};
+use itertools::Itertools;
+use reqwest::{RequestBuilder, Response, StatusCode, Url, header};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, instrument};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, instrument, warn};
 
-use crate::common::{PROJ_DIRS, REQWEST_CLIENT};
+use crate::common::{
+    MANIFEST_URL, META, NO_CACHE, OFFLINE, PROGRESS, REQWEST_CLIENT, TRANSFER_STATS, cache_dir,
+};
+use crate::error::McdlError;
+use crate::types::jre::{AvailableReleases, JreAssets, JrePackage, JreSpec};
 use crate::types::net::CachedResponse;
+use crate::types::stats::{TransferKind, TransferStat};
 use crate::types::version::{GameVersion, GameVersionList, VersionMetadata};
+use crate::utils::hash::sha256_hex;
 
-static CACHE_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(|| PROJ_DIRS.cache_dir().to_path_buf());
+pub(crate) static CACHE_BASE_DIR: LazyLock<PathBuf> = LazyLock::new(cache_dir);
+/// Downloaded JRE archives, kept so reinstalling doesn't download them again
+static JRE_CACHE_DIR: LazyLock<PathBuf> = LazyLock::new(|| CACHE_BASE_DIR.join("jre"));
 
 const PISTON_API_URL: &str = "https://piston-meta.mojang.com/";
-// const FABRIC_API_URL: &str = "https://meta.fabricmc.net/";
+const ADOPTIUM_API_URL: &str = "https://api.adoptium.net/";
 
-const CACHE_EXPIRATION_TIME: u64 = 60 * 10; // 10 minutes
+const CACHE_EXPIRATION_TIME: u64 = 60 * 10; // 10 minutes, unless configured
+
+/// The number of times a download is attempted before giving up
+pub(crate) const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// The style of a bar while a download of known size is running,
+/// which replaces the spinner style of the bar until it is done
+static DOWNLOAD_PB_STYLE: LazyLock<ProgressStyle> = LazyLock::new(|| {
+    ProgressStyle::with_template(
+        "{prefix:.bold.blue.bright} {spinner:.green.bright} {msg} [{bar:30.green.bright/white}] {bytes}/{total_bytes} ({percent}%) {bytes_per_sec} [{elapsed}, ETA {eta}]",
+    )
+    .unwrap()
+    .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏-")
+    .progress_chars("=> ")
+});
 
 #[inline]
 fn api_path(path: &str) -> String {
-    format!("{PISTON_API_URL}{path}")
+    format!("{}{path}", api_base_url())
 }
 
-// #[inline]
-// fn fabric_api_path(path: &str) -> String {
-//     format!("{FABRIC_API_URL}{path}")
-// }
+/// Where Mojang's metadata API is reached, which is [`MANIFEST_URL`] if a mirror is set
+fn api_base_url() -> &'static str {
+    MANIFEST_URL.get().map_or(PISTON_API_URL, Url::as_str)
+}
+
+/// Points a URL from the version manifest at the mirror `base` instead of Mojang's API,
+/// leaving URLs elsewhere alone
+fn mirror_url(url: &str, base: &str) -> String {
+    url.strip_prefix(PISTON_API_URL)
+        .map_or_else(|| url.to_string(), |path| format!("{base}{path}"))
+}
+
+#[inline]
+fn adoptium_api_path(path: &str) -> String {
+    format!("{ADOPTIUM_API_URL}{path}")
+}
 
 #[instrument(err)]
 pub(crate) async fn get_version_manifest() -> Result<GameVersionList> {
@@ -37,17 +77,93 @@ pub(crate) async fn get_version_manifest() -> Result<GameVersionList> {
     get_maybe_cached(&api_path("mc/game/version_manifest.json"), &cache_file).await
 }
 
+/// Reads a version manifest from a local source instead of the network
+///
+/// `source` is either a path, a `file://` URL, or `-` for stdin.
+/// Local manifests are never cached
+#[instrument(err)]
+pub(crate) async fn read_local_manifest(source: &str) -> Result<GameVersionList> {
+    let data = if source == "-" {
+        let mut data = Vec::new();
+        tokio::io::stdin()
+            .read_to_end(&mut data)
+            .await
+            .wrap_err("Failed to read version manifest from stdin")?;
+        data
+    } else {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        fs::read(path)
+            .await
+            .wrap_err(format!("Failed to read version manifest at {path}"))?
+    };
+
+    serde_json::from_slice(&data)
+        .wrap_err(format!("Failed to parse version manifest from {source}"))
+}
+
 #[instrument(err, skip(version), fields(version = %version.id))]
 pub(crate) async fn get_version_metadata(version: &GameVersion) -> Result<VersionMetadata> {
     let cache_file = CACHE_BASE_DIR.join(format!("{}.mpk", version.id));
 
-    get_maybe_cached(&version.url, &cache_file).await
+    get_maybe_cached(&mirror_url(&version.url, api_base_url()), &cache_file).await
+}
+
+/// The metadata of a version as Mojang serves it, including the fields
+/// [`VersionMetadata`] leaves out
+#[instrument(err, skip(version), fields(version = %version.id))]
+pub(crate) async fn get_raw_version_metadata(version: &GameVersion) -> Result<serde_json::Value> {
+    let cache_file = CACHE_BASE_DIR.join(format!("{}-raw.mpk", version.id));
+
+    get_maybe_cached(&mirror_url(&version.url, api_base_url()), &cache_file).await
+}
+
+/// The Java versions Adoptium has JRE builds for
+#[instrument(err)]
+pub(crate) async fn get_available_jre_releases() -> Result<AvailableReleases> {
+    let cache_file = CACHE_BASE_DIR.join("adoptium-releases.mpk");
+
+    get_maybe_cached(
+        &adoptium_api_path("v3/info/available_releases"),
+        &cache_file,
+    )
+    .await
 }
 
 #[instrument(err)] // ret is huge
 pub(crate) async fn get_maybe_cached<T>(url: &str, cache_file: &PathBuf) -> Result<T>
 where T: Serialize + for<'de> Deserialize<'de> {
-    if let Ok(cached) = CachedResponse::<T>::from_file(&cache_file).await
+    get_maybe_cached_with(url, cache_file, async |response| {
+        Ok(response.json().await?)
+    })
+    .await
+}
+
+/// Like [`get_maybe_cached`], but with a custom strategy for parsing
+/// the response body, for endpoints that don't return JSON
+#[instrument(err, skip(parse))]
+pub(crate) async fn get_maybe_cached_with<T, F>(
+    url: &str,
+    cache_file: &PathBuf,
+    parse: F,
+) -> Result<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+    F: AsyncFnOnce(Response) -> Result<T>,
+{
+    if OFFLINE.load(Ordering::Relaxed) {
+        let cached = CachedResponse::<T>::from_file(&cache_file)
+            .await
+            .wrap_err(format!("Offline and no cached data for {url}"))?;
+        if cached.is_expired() {
+            debug!("Using expired cached response (offline)");
+        }
+        return Ok(cached.data);
+    }
+
+    let ttl = cache_ttl();
+    if !ttl.is_zero()
+        && !NO_CACHE.load(Ordering::Relaxed)
+        && let Ok(cached) = CachedResponse::<T>::from_file(&cache_file).await
         && !cached.is_expired()
     {
         let mut msg = "Using cached response".to_string();
@@ -61,49 +177,931 @@ where T: Serialize + for<'de> Deserialize<'de> {
     }
 
     debug!("Downloading fresh data");
-    let response: T = REQWEST_CLIENT.get(url).send().await?.json().await?;
+    let response: T = parse(REQWEST_CLIENT.get(url).send().await?).await?;
 
-    let cached_response = CachedResponse::new(
-        &response,
-        SystemTime::now() + Duration::from_secs(CACHE_EXPIRATION_TIME),
-    );
+    let cached_response = CachedResponse::new(&response, SystemTime::now() + ttl);
     cached_response.save(&cache_file).await?;
     debug!("Saved cached response");
 
     Ok(response)
 }
 
-#[instrument(err)]
-pub(crate) async fn download_jre(major_version: u8) -> Result<Bytes> {
-    let url = format!(
-        "https://api.adoptium.net/v3/binary/latest/{feature_version}/{release_type}/{os}/{arch}/{image_type}/{jvm_impl}/{heap_size}/{vendor}",
-        feature_version = major_version,
-        release_type = "ga",
-        os = match std::env::consts::OS {
-            "macos" => "mac",
-            os => os,
-        },
-        arch = std::env::consts::ARCH,
-        image_type = "jre",
-        jvm_impl = "hotspot",
-        heap_size = "normal",
-        vendor = "eclipse",
-    );
+/// How long cached responses stay fresh, where zero means always refetching
+///
+/// `MCDL_CACHE_TTL` takes precedence over the TTL saved with `mcdl clean --set-ttl`,
+/// which takes precedence over the default of 10 minutes.
+/// Negative or invalid values of `MCDL_CACHE_TTL` are ignored.
+fn cache_ttl() -> Duration {
+    let from_env = std::env::var("MCDL_CACHE_TTL").ok().and_then(|value| {
+        let secs = parse_cache_ttl(&value);
+        if secs.is_none() {
+            warn!(value, "Ignoring invalid MCDL_CACHE_TTL");
+        }
+        secs
+    });
+
+    let secs = from_env
+        .or_else(|| META.lock().cache_ttl)
+        .unwrap_or(CACHE_EXPIRATION_TIME);
+    Duration::from_secs(secs)
+}
+
+/// Parses a cache TTL in seconds, rejecting negative values
+fn parse_cache_ttl(value: &str) -> Option<u64> {
+    value
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| u64::try_from(secs).ok())
+}
+
+/// Sends a request, retrying on network errors and server errors (5xx)
+/// with exponential backoff, up to `max_attempts` times in total
+///
+/// Client errors (4xx) are deterministic, so they are returned as-is.
+/// `on_retry` is called with the number of the attempt about to be made.
+/// Fails immediately in offline mode
+#[instrument(err, skip(builder, on_retry))]
+pub(crate) async fn retry_request(
+    builder: RequestBuilder,
+    max_attempts: u32,
+    on_retry: impl Fn(u32),
+) -> Result<Response> {
+    if OFFLINE.load(Ordering::Relaxed) {
+        let url = builder
+            .try_clone()
+            .and_then(|b| b.build().ok())
+            .map_or_else(|| "<unknown>".to_string(), |r| r.url().to_string());
+        return Err(eyre!("Cannot download {url} in offline mode"));
+    }
+
+    let mut attempt = 1;
+    loop {
+        let request = builder
+            .try_clone()
+            .ok_or_else(|| eyre!("Request body can't be retried"))?;
+
+        let error = match request.send().await {
+            Ok(response) if !response.status().is_server_error() => return Ok(response),
+            Ok(response) if attempt >= max_attempts => return Ok(response),
+            Ok(response) => eyre!("Server error (status code {})", response.status()),
+            Err(e) if attempt >= max_attempts => return Err(e.into()),
+            Err(e) => e.into(),
+        };
+
+        let delay = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+        warn!(%error, attempt, ?delay, "Request failed, retrying");
+        tokio::time::sleep(delay).await;
+
+        attempt += 1;
+        on_retry(attempt);
+    }
+}
+
+/// Reads a response body, showing its progress on `pb` in bytes
+///
+/// The bar keeps its spinner style if the server doesn't send a `Content-Length`,
+/// and is restored to it once the body has been read.
+pub(crate) async fn read_with_progress(mut response: Response, pb: &ProgressBar) -> Result<Bytes> {
+    let Some(len) = response.content_length() else {
+        return Ok(response.bytes().await?);
+    };
+
+    let style = pb.style();
+    pb.set_style(DOWNLOAD_PB_STYLE.clone());
+    pb.set_length(len);
+    pb.set_position(0);
+    pb.reset_eta();
+
+    let mut body = BytesMut::with_capacity(usize::try_from(len).unwrap_or_default());
+    let result = async {
+        while let Some(chunk) = response.chunk().await? {
+            pb.inc(chunk.len() as u64);
+            body.extend_from_slice(&chunk);
+        }
+        Ok::<_, Report>(())
+    }
+    .await;
+
+    pb.set_style(style);
+    pb.unset_length();
+    result.map(|()| body.freeze())
+}
+
+/// A download written to a partial file by [`download_resumable`]
+pub(crate) struct PartialDownload {
+    /// The URL the file was served from, after redirects
+    pub url: Url,
+    /// How long the first response took to arrive
+    pub response_time: Duration,
+}
+
+/// Downloads `url` into `part_path`, continuing where an earlier attempt left off
+///
+/// If the file already has data, a `Range` request is sent for the rest, with an `If-Range`
+/// of the validator the data was downloaded with, so a file that changed in the meantime
+/// (e.g. a newer release behind a `latest` URL) is sent whole instead.
+/// The data is appended if the server answers with 206 Partial Content,
+/// and replaced if it answers with 200 and the whole file.
+/// The body is written as it arrives, so an interrupted attempt keeps what it got.
+/// Other statuses are turned into errors by `status_error`
+#[instrument(err, skip(pb, on_retry, status_error))]
+pub(crate) async fn download_resumable(
+    url: &str,
+    part_path: &Path,
+    pb: &ProgressBar,
+    on_retry: impl Fn(u32),
+    status_error: impl Fn(StatusCode) -> Report,
+) -> Result<PartialDownload> {
+    let start = Instant::now();
+    let mut response_time = None;
+    let mut attempt = 1;
+    loop {
+        let mut existing = fs::metadata(part_path).await.map_or(0, |m| m.len());
+        let resume = if existing > 0 {
+            ResumeInfo::read(part_path).await
+        } else {
+            None
+        };
+        let mut request = REQWEST_CLIENT.get(url);
+        if let Some(resume) = &resume {
+            debug!(existing, "Resuming partial download");
+            request = request
+                .header(header::RANGE, format!("bytes={existing}-"))
+                .header(header::IF_RANGE, &resume.validator);
+        } else if existing > 0 {
+            // without a validator, the rest could be from another file
+            debug!(existing, "Partial download can't be validated, restarting");
+            fs::remove_file(part_path).await?;
+            existing = 0;
+        }
+
+        let response = retry_request(request, MAX_ATTEMPTS, &on_retry).await?;
+        let response_time = *response_time.get_or_insert(start.elapsed());
+        let offset = match response.status() {
+            // a server ignoring `If-Range` still can't pass off another redirect target
+            StatusCode::PARTIAL_CONTENT
+                if resume
+                    .as_ref()
+                    .is_some_and(|resume| resume.url != resume_url(response.url())) =>
+            {
+                warn!("File changed since the download started, restarting");
+                fs::remove_file(part_path).await?;
+                continue;
+            }
+            StatusCode::PARTIAL_CONTENT => existing,
+            StatusCode::OK => 0,
+            // the partial file can't be continued, e.g. it's already complete
+            StatusCode::RANGE_NOT_SATISFIABLE if existing > 0 => {
+                warn!("Server rejected the range, restarting download");
+                fs::remove_file(part_path).await?;
+                continue;
+            }
+            status => return Err(status_error(status)),
+        };
+
+        let url = response.url().clone();
+        if offset == 0 {
+            ResumeInfo::save(part_path, &response).await?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(offset > 0)
+            .write(true)
+            .truncate(offset == 0)
+            .open(part_path)
+            .await
+            .wrap_err(format!("Failed to open {}", part_path.display()))?;
+
+        match write_with_progress(response, &mut file, pb, offset).await {
+            Ok(()) => {
+                ResumeInfo::remove(part_path).await;
+                return Ok(PartialDownload { url, response_time });
+            }
+            Err(e) if attempt >= MAX_ATTEMPTS => return Err(e),
+            Err(e) => {
+                warn!(%e, attempt, "Download interrupted, resuming");
+                attempt += 1;
+                on_retry(attempt);
+            }
+        }
+    }
+}
+
+/// What the data in a partial file was downloaded from, kept next to it
+/// so the download is only continued from the same file
+#[derive(Debug, PartialEq, Eq)]
+struct ResumeInfo {
+    /// The URL the data was served from after redirects, see [`resume_url`]
+    url: String,
+    /// The `ETag` of the response, or its `Last-Modified` if it had no strong `ETag`
+    validator: String,
+}
+
+impl ResumeInfo {
+    fn path(part_path: &Path) -> PathBuf {
+        part_path.with_added_extension("resume")
+    }
+
+    async fn read(part_path: &Path) -> Option<Self> {
+        let data = fs::read_to_string(Self::path(part_path)).await.ok()?;
+        let (url, validator) = data.split_once('\n')?;
+        Some(Self {
+            url: url.to_string(),
+            validator: validator.trim_end().to_string(),
+        })
+    }
+
+    /// Records what `response` is for, or removes the record if it can't be validated
+    async fn save(part_path: &Path, response: &Response) -> Result<()> {
+        let headers = response.headers();
+        let validator = headers
+            .get(header::ETAG)
+            // `If-Range` only takes strong validators
+            .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+            .or_else(|| headers.get(header::LAST_MODIFIED))
+            .and_then(|value| value.to_str().ok());
+
+        let Some(validator) = validator else {
+            Self::remove(part_path).await;
+            return Ok(());
+        };
+        fs::write(
+            Self::path(part_path),
+            format!("{}\n{validator}", resume_url(response.url())),
+        )
+        .await
+        .wrap_err("Failed to save download progress")
+    }
+
+    async fn remove(part_path: &Path) {
+        let _ = fs::remove_file(Self::path(part_path)).await;
+    }
+}
+
+/// A URL without its query, which for a signed redirect target changes on every request
+fn resume_url(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.to_string()
+}
+
+/// Writes a response body to `file` as it arrives, showing its progress on `pb` in bytes
+///
+/// `offset` is the number of bytes already in the file, which count towards the progress.
+async fn write_with_progress(
+    mut response: Response,
+    file: &mut fs::File,
+    pb: &ProgressBar,
+    offset: u64,
+) -> Result<()> {
+    let style = pb.style();
+    if let Some(len) = response.content_length() {
+        pb.set_style(DOWNLOAD_PB_STYLE.clone());
+        pb.set_length(offset + len);
+        pb.set_position(offset);
+        // the resumed bytes weren't downloaded just now, so they shouldn't count towards the rate
+        pb.reset_eta();
+    }
+
+    let result = async {
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            pb.inc(chunk.len() as u64);
+        }
+        file.flush().await?;
+        Ok::<_, Report>(())
+    }
+    .await;
+
+    pb.set_style(style);
+    pb.unset_length();
+    result
+}
+
+/// Downloads the latest JRE archive matching `jre`, or its pinned release, into the cache,
+/// returning it with the name of the release it belongs to
+///
+/// An interrupted download is resumed the next time.
+#[instrument(err, skip(jre, pb, on_retry), fields(jre = %jre))]
+pub(crate) async fn download_jre(
+    jre: &JreSpec,
+    pb: &ProgressBar,
+    on_retry: impl Fn(u32),
+) -> Result<(Bytes, String)> {
+    let musl = is_musl();
+    let url = jre_url(jre, adoptium_os(musl))?;
 
     debug!(url, "Downloading JRE");
-    let response = REQWEST_CLIENT.get(&url).send().await?;
+    fs::create_dir_all(JRE_CACHE_DIR.as_path()).await?;
+    let part =
+        PartFile::claim(jre_archive_path(jre, adoptium_os(musl)).with_extension("archive.part"))?;
+
+    let start = Instant::now();
+    let (download, part, os) = match download_resumable(&url, &part.path, pb, &on_retry, |status| {
+        jre_status_error(jre, &url, status, musl)
+    })
+    .await
+    {
+        // not every build is made for Alpine, but the glibc one may still run with gcompat
+        Err(e)
+            if musl
+                && e.downcast_ref::<McdlError>().is_some_and(|e| {
+                    matches!(
+                        e,
+                        McdlError::JreDownloadFailed {
+                            status: StatusCode::NOT_FOUND,
+                            ..
+                        }
+                    )
+                }) =>
+        {
+            PROGRESS.suspend(|| {
+                eprintln!(
+                    "Warning: Adoptium has no musl build of JRE {jre}, using the glibc build instead"
+                );
+            });
+            let url = jre_url(jre, "linux")?;
+            debug!(url, "Downloading glibc JRE");
+            // the musl archive's partial download can't be continued with the glibc one
+            let part =
+                PartFile::claim(jre_archive_path(jre, "linux").with_extension("archive.part"))?;
+            let download = download_resumable(&url, &part.path, pb, &on_retry, |status| {
+                jre_status_error(jre, &url, status, false)
+            })
+            .await?;
+            (download, part, "linux")
+        }
+        download => (download?, part, adoptium_os(musl)),
+    };
+
+    // the binary endpoint redirects to the release asset, whose file name identifies it
+    let release = download
+        .url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .to_string();
+    let archive = Bytes::from(fs::read(&part.path).await?);
+    TRANSFER_STATS.lock().push(TransferStat {
+        label: format!("JRE {jre}"),
+        kind: TransferKind::Download,
+        bytes: archive.len() as u64,
+        response_time: Some(download.response_time),
+        elapsed: start.elapsed(),
+    });
+
+    pb.set_message("Verifying JRE...");
+    let checksum = verify_jre_archive(jre, os, &archive, &part.path).await?;
 
-    match response.status() {
-        StatusCode::TEMPORARY_REDIRECT | StatusCode::OK => Ok(response.bytes().await?),
-        StatusCode::BAD_REQUEST => Err(eyre!("Bad input parameter in URL: {url}")),
-        StatusCode::NOT_FOUND => Err(eyre!("No binary found for the given parameters: {url}")),
-        status => Err(eyre!("Unexpected error (status code {status}): {url}")),
+    cache_jre(jre, os, &part.path, &release, &checksum).await?;
+    Ok((archive, release))
+}
+
+/// A file a JRE archive is downloaded into
+struct PartFile {
+    path: PathBuf,
+    /// The lock on the shared partial download, or `None` if this process has its own file
+    lock: Option<std::fs::File>,
+}
+
+impl PartFile {
+    /// Claims the partial download at `path`, which a later run can resume,
+    /// or a file only this process uses if another process is downloading into it
+    fn claim(path: PathBuf) -> Result<Self> {
+        let lock_path = path.with_added_extension("lock");
+        let lock = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .wrap_err(format!("Error opening lockfile at {}", lock_path.display()))?;
+
+        match lock.try_lock() {
+            Ok(()) => Ok(Self {
+                path,
+                lock: Some(lock),
+            }),
+            Err(std::fs::TryLockError::WouldBlock) => {
+                debug!(
+                    ?path,
+                    "Partial download is in use, downloading into a new file"
+                );
+                let mut name = path.file_name().expect("infallible").to_os_string();
+                name.push(format!(".{}", std::process::id()));
+                Ok(Self {
+                    path: path.with_file_name(name),
+                    lock: None,
+                })
+            }
+            Err(std::fs::TryLockError::Error(e)) => {
+                Err(e).wrap_err(format!("Error locking {}", lock_path.display()))
+            }
+        }
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        // nothing would resume a file named after this process, so it's removed if it's left over
+        if self.lock.is_none() {
+            let _ = std::fs::remove_file(&self.path);
+            let _ = std::fs::remove_file(ResumeInfo::path(&self.path));
+        }
+    }
+}
+
+/// Checks a downloaded archive against the checksum Adoptium lists for it,
+/// returning its SHA-256 checksum
+///
+/// A mismatch removes the archive, so the next attempt downloads it again.
+async fn verify_jre_archive(
+    jre: &JreSpec,
+    os: &str,
+    archive: &[u8],
+    part_path: &Path,
+) -> Result<String> {
+    let actual = sha256_hex(archive);
+    let Some(package) = jre_package(jre, os).await? else {
+        warn!("Adoptium lists no checksum for the JRE, skipping verification");
+        return Ok(actual);
+    };
+
+    if !actual.eq_ignore_ascii_case(&package.checksum) {
+        let _ = fs::remove_file(part_path).await;
+        return Err(McdlError::ChecksumMismatch {
+            file: package.name,
+            expected: package.checksum,
+            actual,
+        }
+        .into());
+    }
+
+    debug!(package.name, "Verified JRE archive");
+    Ok(actual)
+}
+
+/// The archive Adoptium serves for `jre` on the Adoptium OS `os`, with its checksum
+#[instrument(err, skip(jre), fields(jre = %jre))]
+async fn jre_package(jre: &JreSpec, os: &str) -> Result<Option<JrePackage>> {
+    let url = jre_assets_url(jre, os)?;
+    let response = retry_request(REQWEST_CLIENT.get(&url), MAX_ATTEMPTS, |_| {})
+        .await?
+        .error_for_status()?;
+
+    // a release name picks one release, while the latest is the first of a page of them
+    let release = if jre.release_name().is_some() {
+        Some(response.json::<JreAssets>().await?)
+    } else {
+        response.json::<Vec<JreAssets>>().await?.into_iter().next()
+    };
+    Ok(release
+        .and_then(|release| release.binaries.into_iter().next())
+        .map(|binary| binary.package))
+}
+
+/// Where the archive for `jre` on the Adoptium OS `os` is cached,
+/// with its checksum and the name of its release in files next to it
+fn jre_archive_path(jre: &JreSpec, os: &str) -> PathBuf {
+    JRE_CACHE_DIR.join(format!("{}-{os}-{}.archive", jre.key(), jre.target_arch()))
+}
+
+/// The Adoptium OSes whose JREs run here, most preferred first
+///
+/// On musl systems, the glibc build is what [`download_jre`] falls back to.
+fn jre_oses() -> Vec<&'static str> {
+    if is_musl() {
+        vec![adoptium_os(true), "linux"]
+    } else {
+        vec![adoptium_os(false)]
+    }
+}
+
+/// Reads the cached archive for `jre` and the name of its release, if there is one
+///
+/// An archive that no longer matches the checksum it was cached with is removed.
+pub(crate) async fn read_cached_jre(jre: &JreSpec) -> Option<(Bytes, String)> {
+    for os in jre_oses() {
+        let path = jre_archive_path(jre, os);
+        let (Ok(release), Ok(checksum)) = (
+            fs::read_to_string(path.with_extension("release")).await,
+            fs::read_to_string(path.with_extension("sha256")).await,
+        ) else {
+            continue;
+        };
+        let Ok(archive) = fs::read(&path).await else {
+            continue;
+        };
+
+        if sha256_hex(&archive) != checksum.trim() {
+            warn!(?path, "Cached JRE archive is corrupt, removing it");
+            remove_cached_archive(&path).await;
+            continue;
+        }
+        return Some((archive.into(), release));
+    }
+    None
+}
+
+/// Removes the cached archives for `jre`, so it is downloaded again next time
+pub(crate) async fn remove_cached_jre(jre: &JreSpec) {
+    for os in jre_oses() {
+        remove_cached_archive(&jre_archive_path(jre, os)).await;
+    }
+}
+
+/// Removes a cached archive along with the files next to it
+async fn remove_cached_archive(path: &Path) {
+    let _ = fs::remove_file(path.with_extension("release")).await;
+    let _ = fs::remove_file(path.with_extension("sha256")).await;
+    let _ = fs::remove_file(path).await;
+}
+
+/// Moves a downloaded archive for `jre` on the Adoptium OS `os` into the cache,
+/// replacing any older release
+#[instrument(err, skip(jre), fields(jre = %jre))]
+async fn cache_jre(
+    jre: &JreSpec,
+    os: &str,
+    part_path: &Path,
+    release: &str,
+    checksum: &str,
+) -> Result<()> {
+    let path = jre_archive_path(jre, os);
+
+    // the release is written last, so an interrupted write never looks like a complete archive
+    let _ = fs::remove_file(path.with_extension("release")).await;
+    fs::rename(part_path, &path)
+        .await
+        .wrap_err("Failed to cache JRE archive")?;
+    fs::write(path.with_extension("sha256"), checksum).await?;
+    fs::write(path.with_extension("release"), release).await?;
+    debug!(?path, "Cached JRE archive");
+    Ok(())
+}
+
+/// The URL of the binary endpoint for the archive of `jre` on the Adoptium OS `os`
+fn jre_url(jre: &JreSpec, os: &str) -> Result<String> {
+    let endpoint = match jre.release_name() {
+        // `+` would be decoded as a space
+        Some(name) => format!("version/{}", name.replace('+', "%2B")),
+        None => format!("latest/{}/ga", jre.major),
+    };
+    let arch = adoptium_arch(jre.target_arch())
+        .ok_or_else(|| McdlError::UnsupportedArch(jre.target_arch().to_string()))?;
+    Ok(format!(
+        "{ADOPTIUM_API_URL}v3/binary/{endpoint}/{os}/{arch}/{image_type}/{jvm_impl}/{heap_size}/{vendor}",
+        image_type = jre.image_type,
+        jvm_impl = jre.jvm_impl,
+        heap_size = jre.heap_size,
+        vendor = jre.vendor,
+    ))
+}
+
+/// The URL of the assets endpoint listing the build [`jre_url`] downloads, with its checksum
+fn jre_assets_url(jre: &JreSpec, os: &str) -> Result<String> {
+    let arch = adoptium_arch(jre.target_arch())
+        .ok_or_else(|| McdlError::UnsupportedArch(jre.target_arch().to_string()))?;
+    let query = format!(
+        "architecture={arch}&heap_size={}&image_type={}&jvm_impl={}&os={os}",
+        jre.heap_size, jre.image_type, jre.jvm_impl
+    );
+    Ok(match jre.release_name() {
+        Some(name) => format!(
+            "{ADOPTIUM_API_URL}v3/assets/release_name/{}/{}?{query}",
+            jre.vendor,
+            name.replace('+', "%2B")
+        ),
+        None => format!(
+            "{ADOPTIUM_API_URL}v3/assets/feature_releases/{}/ga?{query}&vendor={}&page_size=1",
+            jre.major, jre.vendor
+        ),
+    })
+}
+
+/// The error for an unexpected status from the binary endpoint
+///
+/// When `musl` is set a 404 stays a [`McdlError::JreDownloadFailed`], so the glibc build can be tried.
+fn jre_status_error(jre: &JreSpec, url: &str, status: StatusCode, musl: bool) -> Report {
+    match status {
+        StatusCode::BAD_REQUEST => eyre!("Bad input parameter in URL: {url}"),
+        StatusCode::NOT_FOUND if !musl => unsupported_jre_error(jre, url),
+        status => McdlError::JreDownloadFailed {
+            status,
+            url: url.to_string(),
+        }
+        .into(),
+    }
+}
+
+/// Whether this is a musl system like Alpine, which glibc JREs don't run on
+fn is_musl() -> bool {
+    if cfg!(target_env = "musl") {
+        return true;
+    }
+    // a glibc build of mcdl may still be running on a musl system with gcompat
+    std::env::consts::OS == "linux"
+        && std::fs::read_dir("/lib").is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"))
+        })
+}
+
+/// Adoptium's name for the OS mcdl runs on
+fn adoptium_os(musl: bool) -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "mac",
+        "linux" if musl => "alpine-linux",
+        os => os,
+    }
+}
+
+/// Adoptium's name for a Rust architecture name, or `None` if it has no builds for it
+fn adoptium_arch(arch: &str) -> Option<&'static str> {
+    match arch {
+        "x86_64" => Some("x64"),
+        "x86" => Some("x86"),
+        "aarch64" => Some("aarch64"),
+        "arm" => Some("arm"),
+        // big-endian `ppc64` builds are only made for AIX
+        "powerpc64" => Some("ppc64le"),
+        "s390x" => Some("s390x"),
+        "riscv64" => Some("riscv64"),
+        "sparc64" => Some("sparcv9"),
+        _ => None,
+    }
+}
+
+/// Explains a 404 from Adoptium, naming the non-default parameters that are likely at fault
+fn unsupported_jre_error(jre: &JreSpec, url: &str) -> Report {
+    let params = jre.non_default_params();
+    match params.as_slice() {
+        [] => eyre!("No binary found for the given parameters: {url}"),
+        [(name, value)] => eyre!(
+            "Adoptium has no Java {} build for this platform with {name} `{value}`: {url}",
+            jre.major
+        ),
+        _ => eyre!(
+            "Adoptium has no Java {} build for this platform with {}: {url}",
+            jre.major,
+            params
+                .iter()
+                .map(|(name, value)| format!("{name} `{value}`"))
+                .join(", ")
+        ),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
     use super::*;
+    use crate::types::jre::{Arch, ImageType, JvmImpl};
+
+    /// Serves one canned response per connection, in order
+    async fn serve(statuses: &'static [u16]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).await.unwrap();
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    /// Serves `body` once with the `ETag` `"{etag}"`, honoring a `Range: bytes=N-` header
+    /// if `ranges` is set and any `If-Range` matches
+    async fn serve_body(body: &'static [u8], etag: &'static str, ranges: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+            let if_range_matches = request
+                .lines()
+                .find_map(|line| line.strip_prefix("if-range: "))
+                .is_none_or(|validator| validator == format!("\"{etag}\""));
+            let start = request
+                .lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok())
+                .filter(|_| ranges && if_range_matches);
+
+            let (status, body) = match start {
+                Some(start) => ("206 Partial Content", &body[start..]),
+                None => ("200 OK", body),
+            };
+            let head = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\netag: \"{etag}\"\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(body).await.unwrap();
+        });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn test_download_resumable() {
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("file.part");
+        let pb = ProgressBar::hidden();
+        let write_partial = async |url: &str, etag: &str| {
+            fs::write(&part_path, b"hello ").await.unwrap();
+            fs::write(
+                ResumeInfo::path(&part_path),
+                format!("{}\n\"{etag}\"", resume_url(&Url::parse(url).unwrap())),
+            )
+            .await
+            .unwrap();
+        };
+
+        // a server that supports ranges only sends the rest of the same file
+        let url = serve_body(b"hello world", "v1", true).await;
+        write_partial(&url, "v1").await;
+        download_resumable(&url, &part_path, &pb, |_| {}, |s| eyre!("{s}"))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&part_path).await.unwrap(), b"hello world");
+        assert!(!ResumeInfo::path(&part_path).exists());
+
+        // but sends everything if the file changed since, which replaces the partial data
+        let url = serve_body(b"hello there", "v2", true).await;
+        write_partial(&url, "v1").await;
+        download_resumable(&url, &part_path, &pb, |_| {}, |s| eyre!("{s}"))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&part_path).await.unwrap(), b"hello there");
+
+        // one that ignores ranges sends everything too
+        let url = serve_body(b"hello world", "v1", false).await;
+        write_partial(&url, "v1").await;
+        download_resumable(&url, &part_path, &pb, |_| {}, |s| eyre!("{s}"))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&part_path).await.unwrap(), b"hello world");
+
+        // partial data that can't be validated is never continued
+        fs::write(&part_path, b"jello ").await.unwrap();
+        let url = serve_body(b"hello world", "v1", true).await;
+        download_resumable(&url, &part_path, &pb, |_| {}, |s| eyre!("{s}"))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&part_path).await.unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_parse_cache_ttl() {
+        assert_eq!(parse_cache_ttl("0"), Some(0));
+        assert_eq!(parse_cache_ttl(" 3600 "), Some(3600));
+        assert_eq!(parse_cache_ttl("-1"), None);
+        assert_eq!(parse_cache_ttl("10m"), None);
+    }
+
+    #[test]
+    fn test_unsupported_jre_error() {
+        let jre = JreSpec::new(8).with_jvm_impl(JvmImpl::Openj9);
+        let message = unsupported_jre_error(&jre, "url").to_string();
+        assert!(message.contains("jvm_impl `openj9`"), "{message}");
+
+        let jre = jre.with_image_type(ImageType::Jdk);
+        let message = unsupported_jre_error(&jre, "url").to_string();
+        assert!(
+            message.contains("jvm_impl `openj9`, image_type `jdk`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_mirror_url() {
+        let url = "https://piston-meta.mojang.com/v1/packages/abc/1.20.1.json";
+        assert_eq!(
+            mirror_url(url, "https://bmclapi2.bangbang93.com/"),
+            "https://bmclapi2.bangbang93.com/v1/packages/abc/1.20.1.json"
+        );
+        assert_eq!(mirror_url(url, PISTON_API_URL), url);
+        assert_eq!(
+            mirror_url("https://example.com/1.20.1.json", "https://mirror/"),
+            "https://example.com/1.20.1.json"
+        );
+    }
+
+    #[test]
+    fn test_adoptium_arch() {
+        assert_eq!(adoptium_arch("x86_64"), Some("x64"));
+        assert_eq!(adoptium_arch("aarch64"), Some("aarch64"));
+        assert_eq!(adoptium_arch("arm"), Some("arm"));
+        assert_eq!(adoptium_arch("powerpc64"), Some("ppc64le"));
+        assert_eq!(adoptium_arch("mips"), None);
+    }
+
+    #[test]
+    fn test_jre_url() {
+        let jre = JreSpec::new(21).with_arch(Some(Arch::X86_64));
+        assert_eq!(
+            jre_url(&jre, "linux").unwrap(),
+            "https://api.adoptium.net/v3/binary/latest/21/ga/linux/x64/jre/hotspot/normal/eclipse"
+        );
+
+        let jre = jre
+            .with_arch(Some(Arch::Aarch64))
+            .with_release(Some("21.0.2+13".to_string()));
+        assert_eq!(
+            jre_url(&jre, "alpine-linux").unwrap(),
+            "https://api.adoptium.net/v3/binary/version/jdk-21.0.2%2B13/alpine-linux/aarch64/jre/hotspot/normal/eclipse"
+        );
+    }
+
+    #[test]
+    fn test_jre_assets_url() {
+        let jre = JreSpec::new(21).with_arch(Some(Arch::X86_64));
+        assert_eq!(
+            jre_assets_url(&jre, "linux").unwrap(),
+            "https://api.adoptium.net/v3/assets/feature_releases/21/ga?architecture=x64&heap_size=normal&image_type=jre&jvm_impl=hotspot&os=linux&vendor=eclipse&page_size=1"
+        );
+
+        let jre = jre.with_release(Some("21.0.2+13".to_string()));
+        assert_eq!(
+            jre_assets_url(&jre, "mac").unwrap(),
+            "https://api.adoptium.net/v3/assets/release_name/eclipse/jdk-21.0.2%2B13?architecture=x64&heap_size=normal&image_type=jre&jvm_impl=hotspot&os=mac"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_jre() {
+        // tests have their own cache directory, so nothing the user cached is clobbered
+        let jre = JreSpec::new(255).with_jvm_impl(JvmImpl::Openj9);
+        assert!(JRE_CACHE_DIR.starts_with(std::env::temp_dir()));
+
+        assert!(read_cached_jre(&jre).await.is_none());
+
+        let os = adoptium_os(is_musl());
+        let part_path = jre_archive_path(&jre, os).with_extension("archive.part");
+        fs::create_dir_all(JRE_CACHE_DIR.as_path()).await.unwrap();
+        for (archive, release) in [(b"old", "release-1"), (b"new", "release-2")] {
+            fs::write(&part_path, archive).await.unwrap();
+            cache_jre(&jre, os, &part_path, release, &sha256_hex(archive))
+                .await
+                .unwrap();
+        }
+        let (archive, release) = read_cached_jre(&jre).await.unwrap();
+        assert_eq!(archive.as_ref(), b"new");
+        assert_eq!(release, "release-2");
+
+        // a different variant has its own archive
+        assert!(read_cached_jre(&JreSpec::new(255)).await.is_none());
+        // and so do the musl and glibc builds
+        assert_ne!(
+            jre_archive_path(&jre, "alpine-linux"),
+            jre_archive_path(&jre, "linux")
+        );
+
+        // a corrupt archive isn't used, and is removed
+        fs::write(jre_archive_path(&jre, os), b"bad").await.unwrap();
+        assert!(read_cached_jre(&jre).await.is_none());
+        assert!(!jre_archive_path(&jre, os).exists());
+    }
+
+    #[tokio::test]
+    async fn test_retry_request() {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let retries = AtomicU32::new(0);
+
+        let url = serve(&[502, 503, 200]).await;
+        let response = retry_request(client.get(&url), MAX_ATTEMPTS, |_| {
+            retries.fetch_add(1, Ordering::Relaxed);
+        })
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(retries.load(Ordering::Relaxed), 2);
+
+        // client errors are not retried
+        let url = serve(&[404]).await;
+        let response = retry_request(client.get(&url), MAX_ATTEMPTS, |_| panic!("retried"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // gives up after the last attempt
+        let url = serve(&[500, 500]).await;
+        let response = retry_request(client.get(&url), 2, |_| {}).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 
     #[tokio::test]
     async fn test_get_version_manifest() {
@@ -111,6 +1109,31 @@ mod tests {
         assert!(!manifest.versions.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_read_local_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("version_manifest.json");
+        fs::write(
+            &path,
+            r#"{
+                "latest": { "release": "1.20.1", "snapshot": "1.20.1" },
+                "versions": [
+                    { "id": "1.20.1", "type": "release", "url": "", "time": "2023-06-12T00:00:00+00:00", "releaseTime": "2023-06-12T00:00:00+00:00" }
+                ]
+            }"#,
+        )
+        .await
+        .unwrap();
+
+        let manifest = read_local_manifest(&format!("file://{}", path.display()))
+            .await
+            .unwrap();
+        assert_eq!(manifest.versions.len(), 1);
+
+        let manifest = read_local_manifest(path.to_str().unwrap()).await.unwrap();
+        assert_eq!(manifest.latest.release.to_string(), "1.20.1");
+    }
+
     #[tokio::test]
     async fn test_get_version_metadata() {
         let manifest = get_version_manifest().await.unwrap();
@@ -128,9 +1151,10 @@ mod tests {
 
         let mut tries = 0;
         while tries < 3 {
-            match download_jre(version).await {
-                Ok(jre) => {
+            match download_jre(&JreSpec::new(version), &ProgressBar::hidden(), |_| {}).await {
+                Ok((jre, release)) => {
                     assert!(!jre.is_empty());
+                    assert!(!release.is_empty());
                     break;
                 }
                 Err(e) => {
@@ -0,0 +1,291 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{Result, eyre};
+use lazy_regex::regex;
+use reqwest::{NoProxy, Proxy, Url};
+
+/// Parses a human-readable duration, such as `90`, `30s`, `5m`, or `1h`
+///
+/// A bare number is interpreted as seconds
+pub(crate) fn parse_duration(s: &str) -> Result<Duration> {
+    let re = regex!(r"^(\d+)\s*(s|m|h)?$");
+
+    let caps = re
+        .captures(s.trim())
+        .ok_or_else(|| eyre!("Invalid duration (expected N[s|m|h], got: {s})"))?;
+    let value: u64 = caps[1].parse()?;
+    let multiplier = match caps.get(2).map(|m| m.as_str()) {
+        None | Some("s") => 1,
+        Some("m") => 60,
+        Some("h") => 60 * 60,
+        Some(_) => unreachable!(),
+    };
+
+    Ok(Duration::from_secs(value * multiplier))
+}
+
+/// Parses a memory size for the JVM, such as `512M`, `4G`, or `4gb`
+///
+/// The result is normalized to the form the JVM expects, e.g. `4G`
+pub(crate) fn parse_memory(s: &str) -> Result<String> {
+    let re = regex!(r"^(\d+)\s*([KkMmGg])[Bb]?$");
+
+    let caps = re
+        .captures(s.trim())
+        .ok_or_else(|| eyre!("Invalid memory size (expected N<K|M|G>, got: {s})"))?;
+    let value: u64 = caps[1].parse()?;
+    if value == 0 {
+        return Err(eyre!("Memory size must be greater than zero"));
+    }
+
+    Ok(format!("{value}{}", caps[2].to_ascii_uppercase()))
+}
+
+/// Parses a memory size in any form [`parse_memory`] accepts into bytes
+pub(crate) fn parse_memory_bytes(s: &str) -> Result<u64> {
+    let memory = parse_memory(s)?;
+    let (value, unit) = memory.split_at(memory.len() - 1);
+    let shift = match unit {
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => unreachable!(),
+    };
+
+    value
+        .parse::<u64>()?
+        .checked_mul(1 << shift)
+        .ok_or_else(|| eyre!("Memory size is too large: {s}"))
+}
+
+/// Parses an instance name, which is used as a directory and file name
+///
+/// Names may contain letters, digits, `.`, `-`, and `_`, and can't start with `.`
+pub(crate) fn parse_instance_name(s: &str) -> Result<String> {
+    let re = regex!(r"^[A-Za-z0-9_-][A-Za-z0-9._-]*$");
+
+    if !re.is_match(s) {
+        return Err(eyre!(
+            "Invalid instance name (expected letters, digits, `.`, `-`, or `_`, got: {s})"
+        ));
+    }
+
+    Ok(s.to_string())
+}
+
+/// Parses a proxy URL, such as `http://proxy:8080` or `socks5://localhost:1080`
+///
+/// Hosts in `NO_PROXY` still bypass the proxy
+pub(crate) fn parse_proxy(s: &str) -> Result<Proxy> {
+    let proxy = Proxy::all(s).map_err(|e| eyre!("Invalid proxy URL `{s}`: {e}"))?;
+
+    Ok(proxy.no_proxy(NoProxy::from_env()))
+}
+
+/// Parses the URL of a paste service to upload crash reports to, which must be HTTP(S)
+pub(crate) fn parse_paste_url(s: &str) -> Result<Url> {
+    let url = Url::parse(s).map_err(|e| eyre!("Invalid paste URL `{s}`: {e}"))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(eyre!(
+            "Invalid paste URL `{s}` (expected an http or https URL)"
+        ));
+    }
+
+    Ok(url)
+}
+
+/// Parses the base URL of a mirror of Mojang's metadata API, which must be HTTP(S)
+/// and end in a slash, since paths are appended to it
+pub(crate) fn parse_manifest_url(s: &str) -> Result<Url> {
+    let url = Url::parse(s).map_err(|e| eyre!("Invalid manifest URL `{s}`: {e}"))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(eyre!(
+            "Invalid manifest URL `{s}` (expected an http or https URL)"
+        ));
+    }
+    if !url.as_str().ends_with('/') {
+        return Err(eyre!("Invalid manifest URL `{s}` (must end in a slash)"));
+    }
+
+    Ok(url)
+}
+
+/// Parses a full JRE version to pin, such as `17.0.9+9`, or `8u392-b08` for Java 8
+///
+/// The build number is required, since Adoptium names its releases after it
+pub(crate) fn parse_jre_version(s: &str) -> Result<String> {
+    let re = regex!(r"^\d+(u\d+-b\d+|(\.\d+)*\+\d+)$");
+
+    let s = s.trim().trim_start_matches("jdk").trim_start_matches('-');
+    if !re.is_match(s) {
+        return Err(eyre!(
+            "Invalid JRE version (expected e.g. `17.0.9+9` or `8u392-b08`, got: {s})"
+        ));
+    }
+
+    Ok(s.to_string())
+}
+
+/// Splits version lists on commas and whitespace, dropping empty entries
+///
+/// Makes `1.20.1 1.19.4,1.18.2`, `"1.20.1, 1.19.4"`, and `1.20.1,` mean the same thing
+/// however the shell split them into arguments
+pub(crate) fn split_version_list(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|value| value.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|version| !version.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// The entries of a version list file with their line numbers, one version per line
+///
+/// Blank lines and lines starting with `#` are skipped.
+pub(crate) fn parse_version_file(contents: &str) -> Vec<(usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+/// Parses the major version from the output of `java -version`,
+/// e.g. `openjdk version "17.0.8" 2023-07-18`
+///
+/// Handles the `1.8.0_382` style used by Java 8 and older
+pub(crate) fn parse_java_version(output: &str) -> Option<u8> {
+    let re = regex!(r#"version "(\d+)(?:\.(\d+))?"#);
+
+    let caps = re.captures(output)?;
+    match caps[1].parse().ok()? {
+        1 => caps.get(2)?.as_str().parse().ok(),
+        major => Some(major),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proxies() {
+        assert!(parse_proxy("http://proxy.example.com:8080").is_ok());
+        assert!(parse_proxy("socks5://localhost:1080").is_ok());
+        assert!(parse_proxy("http://[::1").is_err());
+    }
+
+    #[test]
+    fn parse_paste_urls() {
+        assert!(parse_paste_url("https://api.mclo.gs/1/log").is_ok());
+        assert!(parse_paste_url("http://localhost:8080/1/log").is_ok());
+        assert!(parse_paste_url("ftp://example.com/log").is_err());
+        assert!(parse_paste_url("api.mclo.gs/1/log").is_err());
+    }
+
+    #[test]
+    fn parse_manifest_urls() {
+        assert!(parse_manifest_url("https://bmclapi2.bangbang93.com/").is_ok());
+        assert!(parse_manifest_url("http://localhost:8080/mojang/").is_ok());
+        assert!(parse_manifest_url("https://bmclapi2.bangbang93.com/mojang").is_err());
+        assert!(parse_manifest_url("ftp://example.com/").is_err());
+    }
+
+    #[test]
+    fn parse_version_files() {
+        let contents = "# servers\n1.20.1\n\n  1.19.4  \n#1.18.2\nlatest-snapshot\n";
+        assert_eq!(
+            parse_version_file(contents),
+            vec![(2, "1.20.1"), (4, "1.19.4"), (6, "latest-snapshot")]
+        );
+        assert!(parse_version_file("\n# nothing\n").is_empty());
+    }
+
+    #[test]
+    fn parse_java_versions() {
+        assert_eq!(
+            parse_java_version(
+                "openjdk version \"17.0.8\" 2023-07-18\nOpenJDK Runtime Environment"
+            ),
+            Some(17)
+        );
+        assert_eq!(
+            parse_java_version("openjdk version \"1.8.0_382\"\n"),
+            Some(8)
+        );
+        assert_eq!(
+            parse_java_version("openjdk version \"21\" 2023-09-19"),
+            Some(21)
+        );
+        assert_eq!(parse_java_version("Segmentation fault"), None);
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_mins(5));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_hours(1));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn parse_memory_sizes() {
+        assert_eq!(parse_memory("4G").unwrap(), "4G");
+        assert_eq!(parse_memory("4gb").unwrap(), "4G");
+        assert_eq!(parse_memory("512m").unwrap(), "512M");
+        assert!(parse_memory("4").is_err());
+        assert!(parse_memory("0G").is_err());
+        assert!(parse_memory("4T").is_err());
+        assert_eq!(parse_memory_bytes("4G").unwrap(), 4 << 30);
+        assert_eq!(parse_memory_bytes("512m").unwrap(), 512 << 20);
+    }
+
+    #[test]
+    fn parse_jre_versions() {
+        assert_eq!(parse_jre_version("17.0.9+9").unwrap(), "17.0.9+9");
+        assert_eq!(parse_jre_version("21+35").unwrap(), "21+35");
+        assert_eq!(parse_jre_version("jdk-17.0.9+9").unwrap(), "17.0.9+9");
+        assert_eq!(parse_jre_version("8u392-b08").unwrap(), "8u392-b08");
+        assert_eq!(parse_jre_version("jdk8u392-b08").unwrap(), "8u392-b08");
+        assert!(parse_jre_version("17.0.9").is_err());
+        assert!(parse_jre_version("17").is_err());
+        assert!(parse_jre_version("latest").is_err());
+    }
+
+    #[test]
+    fn split_version_lists() {
+        let split = |values: &[&str]| {
+            split_version_list(&values.iter().map(ToString::to_string).collect::<Vec<_>>())
+        };
+
+        assert_eq!(split(&["1.20.1,1.19.4"]), ["1.20.1", "1.19.4"]);
+        assert_eq!(split(&["1.20.1 1.19.4"]), ["1.20.1", "1.19.4"]);
+        assert_eq!(split(&["1.20.1, 1.19.4"]), ["1.20.1", "1.19.4"]);
+        assert_eq!(split(&["1.20.1,", ",1.19.4,,"]), ["1.20.1", "1.19.4"]);
+        assert_eq!(
+            split(&["1.20.1", "1.19.4,1.18.2"]),
+            ["1.20.1", "1.19.4", "1.18.2"]
+        );
+        assert!(split(&[",", " "]).is_empty());
+    }
+
+    #[test]
+    fn parse_instance_names() {
+        assert_eq!(parse_instance_name("survival").unwrap(), "survival");
+        assert_eq!(
+            parse_instance_name("1.20.1-creative_2").unwrap(),
+            "1.20.1-creative_2"
+        );
+        assert!(parse_instance_name("").is_err());
+        assert!(parse_instance_name("..").is_err());
+        assert!(parse_instance_name(".hidden").is_err());
+        assert!(parse_instance_name("a/b").is_err());
+        assert!(parse_instance_name("my world").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use color_eyre::eyre::{Result, WrapErr};
+use tokio::fs;
+
+/// A `server.properties` file, edited line by line so comments and the order of keys are kept
+///
+/// Only the subset of the Java properties format the server writes is supported,
+/// i.e. no line continuations
+#[derive(Debug, Default)]
+pub(crate) struct ServerProperties {
+    lines: Vec<String>,
+}
+
+impl ServerProperties {
+    pub fn parse(s: &str) -> Self {
+        Self {
+            lines: s.lines().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Reads the properties file at `path`, which is empty if it doesn't exist yet
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)
+            .await
+            .wrap_err(format!("Failed to read {}", path.display()))?;
+        Ok(Self::parse(&data))
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_string())
+            .await
+            .wrap_err(format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lines
+            .iter()
+            .filter_map(|line| split_line(line))
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| unescape(value))
+    }
+
+    /// Sets a key, replacing its value in place or appending it if it isn't set yet
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{}={}", escape(key), escape(value));
+
+        match self
+            .lines
+            .iter()
+            .position(|l| split_line(l).is_some_and(|(k, _)| k == key))
+        {
+            Some(i) => self.lines[i] = line,
+            None => self.lines.push(line),
+        }
+    }
+}
+
+impl Display for ServerProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a line into its unescaped key and its raw value,
+/// or `None` for comments and blank lines
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let line = line.trim_start();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+        return None;
+    }
+
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | ':' => return Some((unescape(line[..i].trim_end()), line[i + 1..].trim_start())),
+            _ => {}
+        }
+    }
+    Some((unescape(line.trim_end()), ""))
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\u{c}'),
+            Some('u') => {
+                let hex = chars.by_ref().take(4).collect::<String>();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(c);
+                }
+            }
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Escapes a key or value the way `java.util.Properties` writes it
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        match c {
+            '\\' | '=' | ':' | '#' | '!' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' ' if i == 0 => out.push_str("\\ "),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\u{c}' => out.push_str("\\f"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROPERTIES: &str = "#Minecraft server properties\n\
+        #Mon Jan 01 00:00:00 UTC 2024\n\
+        difficulty=easy\n\
+        motd=A Minecraft Server\n\
+        resource-pack=https\\://example.com/pack.zip\n\
+        server-port=25565\n";
+
+    #[test]
+    fn get_properties() {
+        let properties = ServerProperties::parse(PROPERTIES);
+        assert_eq!(properties.get("difficulty").as_deref(), Some("easy"));
+        assert_eq!(
+            properties.get("motd").as_deref(),
+            Some("A Minecraft Server")
+        );
+        assert_eq!(
+            properties.get("resource-pack").as_deref(),
+            Some("https://example.com/pack.zip")
+        );
+        assert_eq!(properties.get("Mon Jan 01 00"), None);
+        assert_eq!(properties.get("max-players"), None);
+    }
+
+    #[test]
+    fn set_properties() {
+        let mut properties = ServerProperties::parse(PROPERTIES);
+        properties.set("server-port", "25566");
+        properties.set("max-players", "5");
+        properties.set("motd", "Hi: there");
+
+        assert_eq!(
+            properties.to_string(),
+            "#Minecraft server properties\n\
+            #Mon Jan 01 00:00:00 UTC 2024\n\
+            difficulty=easy\n\
+            motd=Hi\\: there\n\
+            resource-pack=https\\://example.com/pack.zip\n\
+            server-port=25566\n\
+            max-players=5\n"
+        );
+        assert_eq!(properties.get("motd").as_deref(), Some("Hi: there"));
+    }
+}
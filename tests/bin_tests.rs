@@ -49,6 +49,18 @@ fn test_info() {
     );
 }
 
+#[test]
+fn test_info_latest_conflicts_with_version() {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("info")
+        .arg("--latest")
+        .arg("--version")
+        .arg("1.19.4");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
 #[test]
 fn test_locate_config() {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
@@ -74,3 +86,99 @@ fn test_locate_config() {
         }
     }
 }
+
+#[test]
+fn test_locate_data_dir_env() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // only instances, JREs, server jars, and backups move
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("locate")
+        .arg("all")
+        .arg("--manifest")
+        .arg("-")
+        .env("MCDL_DATA_DIR", dir.path())
+        .write_stdin(r#"{"latest":{"release":"1.20.1","snapshot":"1.20.1"},"versions":[]}"#);
+    cmd.assert().success().stdout(
+        predicate::str::contains(dir.path().join("instance").to_string_lossy())
+            .and(predicate::str::contains(dir.path().join("log").to_string_lossy()).not()),
+    );
+}
+
+#[test]
+fn test_locate_data_dir_over_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join("data");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("locate")
+        .arg("instance")
+        .arg("--manifest")
+        .arg("-")
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .env("MCDL_DATA_DIR", dir.path().join("env"))
+        .write_stdin(r#"{"latest":{"release":"1.20.1","snapshot":"1.20.1"},"versions":[]}"#);
+    cmd.assert().success().stdout(predicate::str::contains(
+        data_dir.join("instance").to_string_lossy(),
+    ));
+}
+
+#[test]
+fn test_locate_config_dir_override() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_dir = dir.path().join("config");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("locate")
+        .arg("config")
+        .arg("--manifest")
+        .arg("-")
+        .arg("--data-dir")
+        .arg(dir.path().join("data"))
+        .arg("--config-dir")
+        .arg(&config_dir)
+        .write_stdin(r#"{"latest":{"release":"1.20.1","snapshot":"1.20.1"},"versions":[]}"#);
+    cmd.assert().success().stdout(predicate::str::contains(
+        config_dir.join("instance").to_string_lossy(),
+    ));
+}
+
+#[test]
+fn test_uninstall_space_separated() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // both names parse, so the missing instance is reported rather than a usage error
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("uninstall")
+        .arg("--version")
+        .arg("no-such-instance")
+        .arg("another")
+        .arg("--manifest")
+        .arg("-")
+        .arg("--data-dir")
+        .arg(dir.path())
+        .write_stdin(r#"{"latest":{"release":"1.20.1","snapshot":"1.20.1"},"versions":[]}"#);
+    cmd.assert()
+        .code(3)
+        .stderr(predicate::str::contains("no-such-instance"));
+}
+
+#[test]
+fn test_completions_offline() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // no manifest is cached, so anything needing one would fail
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("completions")
+        .arg("bash")
+        .arg("--offline")
+        .arg("--data-dir")
+        .arg(dir.path())
+        .arg("--cache-dir")
+        .arg(dir.path().join("cache"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("complete"));
+    assert!(!dir.path().join("log").exists());
+}